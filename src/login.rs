@@ -10,7 +10,6 @@ use tdgrand::{enums::AuthorizationState, functions, types};
 
 use crate::config;
 use crate::utils::{do_async, parse_formatted_text};
-use crate::DATA_DIR;
 
 mod imp {
     use super::*;
@@ -24,6 +23,8 @@ mod imp {
     #[template(resource = "/com/github/melix99/telegrand/ui/login.ui")]
     pub struct Login {
         pub client_id: Cell<i32>,
+        pub database_directory: RefCell<String>,
+        pub cancellable: Cell<bool>,
         pub tos_text: RefCell<String>,
         pub show_tos_popup: Cell<bool>,
         pub has_recovery_email_address: Cell<bool>,
@@ -147,7 +148,10 @@ mod imp {
     impl ObjectImpl for Login {
         fn signals() -> &'static [Signal] {
             static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
-                vec![Signal::builder("new-session", &[], <()>::static_type().into()).build()]
+                vec![
+                    Signal::builder("new-session", &[], <()>::static_type().into()).build(),
+                    Signal::builder("cancel", &[], <()>::static_type().into()).build(),
+                ]
             });
             SIGNALS.as_ref()
         }
@@ -201,9 +205,11 @@ impl Login {
         glib::Object::new(&[]).expect("Failed to create Login")
     }
 
-    pub fn login_client(&self, client_id: i32) {
+    pub fn login_client(&self, client_id: i32, database_directory: String, cancellable: bool) {
         let self_ = imp::Login::from_instance(self);
         self_.client_id.set(client_id);
+        self_.database_directory.replace(database_directory);
+        self_.cancellable.set(cancellable);
 
         // We don't know what login page to show at this point, so we show an empty page until we
         // receive an AuthenticationState that will eventually show the related login page.
@@ -408,6 +414,9 @@ impl Login {
         self_.main_stack.set_visible_child_name("login-flow-page");
 
         self.unfreeze();
+        // The page might be the same as before, but the actions could still need an update,
+        // for example when the login has become cancellable in the meanwhile.
+        self.update_actions_for_visible_page();
         if let Some(widget_to_focus) = widget_to_focus {
             widget_to_focus.grab_focus();
         }
@@ -418,7 +427,10 @@ impl Login {
 
         let visible_page = self_.content.visible_child_name().unwrap();
 
-        let is_previous_valid = visible_page.as_str() != "phone-number-page";
+        // The first page allows to go back only if the login can be cancelled, that is
+        // when there are other accounts already logged in
+        let is_previous_valid =
+            visible_page.as_str() != "phone-number-page" || self_.cancellable.get();
         let is_next_valid = visible_page.as_str() != "password-forgot-page"
             && visible_page.as_str() != "qr-code-page";
 
@@ -464,6 +476,10 @@ impl Login {
                 None,
                 None,
             ),
+            "phone-number-page" => {
+                self.disable_actions();
+                self.emit_by_name("cancel", &[]).unwrap();
+            }
             _ => self.navigate_to_page::<gtk::Editable, _, _>(
                 "phone-number-page",
                 [],
@@ -626,34 +642,9 @@ impl Login {
         let client_id = self_.client_id.get();
         let use_test_dc = self_.use_test_dc_switch.state();
 
-        let database_directory = DATA_DIR
-            .get()
-            .unwrap()
-            .to_str()
-            .expect("Data directory path is not a valid unicode string")
-            .to_owned();
-
-        let system_language_code = {
-            let locale = Locale::current().to_string();
-            if !locale.is_empty() {
-                locale
-            } else {
-                "en_US".to_string()
-            }
-        };
-        let parameters = types::TdlibParameters {
-            use_test_dc,
-            database_directory,
-            use_message_database: true,
-            use_secret_chats: true,
-            api_id: config::TG_API_ID,
-            api_hash: config::TG_API_HASH.to_string(),
-            system_language_code,
-            device_model: "Desktop".to_string(),
-            application_version: config::VERSION.to_string(),
-            enable_storage_optimizer: true,
-            ..types::TdlibParameters::default()
-        };
+        let database_directory = self_.database_directory.borrow().clone();
+        let parameters = tdlib_parameters(database_directory, use_test_dc);
+
         do_async(
             glib::PRIORITY_DEFAULT_IDLE,
             async move {
@@ -1018,6 +1009,42 @@ impl Login {
         })
         .unwrap()
     }
+
+    pub fn connect_cancel<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("cancel", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            f(&obj);
+
+            None
+        })
+        .unwrap()
+    }
+}
+
+/// Returns the parameters of a client using the given database directory.
+pub fn tdlib_parameters(database_directory: String, use_test_dc: bool) -> types::TdlibParameters {
+    let system_language_code = {
+        let locale = Locale::current().to_string();
+        if !locale.is_empty() {
+            locale
+        } else {
+            "en_US".to_string()
+        }
+    };
+
+    types::TdlibParameters {
+        use_test_dc,
+        database_directory,
+        use_message_database: true,
+        use_secret_chats: true,
+        api_id: config::TG_API_ID,
+        api_hash: config::TG_API_HASH.to_string(),
+        system_language_code,
+        device_model: "Desktop".to_string(),
+        application_version: config::VERSION.to_string(),
+        enable_storage_optimizer: true,
+        ..types::TdlibParameters::default()
+    }
 }
 
 fn show_error_label(error_label: &gtk::Label, message: &str) {
@@ -37,6 +37,7 @@ fn main() {
 
     gtk::init().expect("Unable to start GTK4");
     adw::init();
+    sourceview::init();
 
     let res = gio::Resource::load(RESOURCES_FILE).expect("Could not load gresource file");
     gio::resources_register(&res);
@@ -116,8 +117,5 @@ fn setup_cli<A: IsA<gio::Application>>(app: A) -> A {
 }
 
 fn default_data_dir() -> PathBuf {
-    // TODO: In the future when multi account is a thing, only use the parent directory.
-    PathBuf::from(glib::user_data_dir().to_str().unwrap())
-        .join("telegrand")
-        .join("db0")
+    PathBuf::from(glib::user_data_dir().to_str().unwrap()).join("telegrand")
 }
@@ -126,8 +126,7 @@ impl Avatar {
 
                 self.set_image(Some(texture.upcast()));
             } else if file.local.can_be_downloaded && !file.local.is_downloading_active {
-                let (sender, receiver) =
-                    glib::MainContext::sync_channel::<File>(Default::default(), 5);
+                let (sender, receiver) = glib::MainContext::channel::<File>(Default::default());
 
                 receiver.attach(
                     None,
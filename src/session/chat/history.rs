@@ -1,7 +1,9 @@
 use glib::clone;
 use gtk::{gio, glib, prelude::*, subclass::prelude::*};
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::hash_map::Entry;
+use std::rc::Rc;
 use tdgrand::enums::{self, Update};
 use tdgrand::functions;
 use tdgrand::types::Message as TelegramMessage;
@@ -20,6 +22,8 @@ mod imp {
     pub struct History {
         pub chat: OnceCell<Chat>,
         pub loading: Cell<bool>,
+        pub has_newer_messages: Cell<bool>,
+        pub search_query: RefCell<String>,
         pub list: RefCell<VecDeque<Item>>,
         pub message_map: RefCell<HashMap<i64, Message>>,
     }
@@ -50,6 +54,13 @@ mod imp {
                         false,
                         glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
                     ),
+                    glib::ParamSpec::new_string(
+                        "search-query",
+                        "Search Query",
+                        "The text searched in the messages of this history",
+                        None,
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                    ),
                 ]
             });
 
@@ -69,6 +80,7 @@ mod imp {
                     self.chat.set(chat).unwrap();
                 }
                 "loading" => obj.set_loading(value.get().unwrap()),
+                "search-query" => obj.set_search_query(value.get().unwrap()),
                 _ => unimplemented!(),
             }
         }
@@ -77,6 +89,7 @@ mod imp {
             match pspec.name() {
                 "chat" => self.chat.get().to_value(),
                 "loading" => obj.loading().to_value(),
+                "search-query" => obj.search_query().to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -152,26 +165,220 @@ impl History {
         );
     }
 
+    /// Loads the messages that are newer than the newest loaded one, in case the history
+    /// doesn't reach the last message of the chat.
+    pub fn load_newer_messages(&self) {
+        if self.loading() || !self.has_newer_messages() {
+            return;
+        }
+
+        let self_ = imp::History::from_instance(self);
+        let chat = self.chat();
+        let client_id = chat.session().client_id();
+        let chat_id = chat.id();
+        let newest_message_id = self_
+            .list
+            .borrow()
+            .iter()
+            .rev()
+            .find_map(|item| item.message())
+            .map(|m| m.id())
+            .unwrap_or_default();
+
+        self.set_loading(true);
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::GetChatHistory::new()
+                    .chat_id(chat_id)
+                    .from_message_id(newest_message_id)
+                    .offset(-20)
+                    .limit(20)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                match result {
+                    Ok(enums::Messages::Messages(result)) => {
+                        let messages = result
+                            .messages
+                            .unwrap_or_default()
+                            .into_iter()
+                            .filter(|message| message.id > newest_message_id)
+                            .collect::<Vec<_>>();
+
+                        obj.append_newer(messages);
+                    }
+                    Err(_) => obj.set_has_newer_messages(false),
+                }
+
+                obj.set_loading(false);
+            }),
+        );
+    }
+
+    /// Loads the messages around the last read message of the chat, marking the start of the
+    /// unread messages with a divider. The `callback` is called when the messages are loaded.
+    pub fn load_unread_messages<F: FnOnce(&Self) + 'static>(&self, callback: F) {
+        let chat = self.chat();
+        let last_read_message_id = chat.last_read_inbox_message_id();
+
+        self.remove_unread_divider();
+
+        self.load_messages_around(last_read_message_id, move |obj| {
+            obj.insert_unread_divider(last_read_message_id);
+            callback(obj);
+        });
+    }
+
+    /// Loads the messages around the given message, if it's not loaded yet. The `callback`
+    /// is called when the messages are loaded.
+    pub fn load_messages_around<F: FnOnce(&Self) + 'static>(&self, message_id: i64, callback: F) {
+        if self.message_by_id(message_id).is_some() {
+            callback(self);
+        } else {
+            self.reload(message_id, -10, 20, callback);
+        }
+    }
+
+    /// Loads the last messages of the chat, in case the history doesn't reach them. The
+    /// `callback` is called when the messages are loaded.
+    pub fn load_newest_messages<F: FnOnce(&Self) + 'static>(&self, callback: F) {
+        if self.has_newer_messages() {
+            self.reload(0, 0, 20, callback);
+        } else {
+            callback(self);
+        }
+    }
+
+    /// Replaces the loaded messages with the ones starting from `from_message_id`.
+    fn reload<F: FnOnce(&Self) + 'static>(
+        &self,
+        from_message_id: i64,
+        offset: i32,
+        limit: i32,
+        callback: F,
+    ) {
+        // Retry once the current loading has finished, so that the callback isn't lost
+        if self.loading() {
+            let handler_id = Rc::new(RefCell::new(None));
+            let callback = RefCell::new(Some(callback));
+            let id = self.connect_notify_local(
+                Some("loading"),
+                clone!(@strong handler_id => move |obj, _| {
+                    if obj.loading() {
+                        return;
+                    }
+                    if let Some(handler_id) = handler_id.borrow_mut().take() {
+                        obj.disconnect(handler_id);
+                    }
+                    if let Some(callback) = callback.borrow_mut().take() {
+                        obj.reload(from_message_id, offset, limit, callback);
+                    }
+                }),
+            );
+            handler_id.replace(Some(id));
+            return;
+        }
+
+        let chat = self.chat();
+        let client_id = chat.session().client_id();
+        let chat_id = chat.id();
+
+        self.set_loading(true);
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::GetChatHistory::new()
+                    .chat_id(chat_id)
+                    .from_message_id(from_message_id)
+                    .offset(offset)
+                    .limit(limit)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                if let Ok(enums::Messages::Messages(result)) = result {
+                    obj.replace_messages(result.messages.unwrap_or_default());
+                }
+
+                obj.set_loading(false);
+                callback(&obj);
+            }),
+        );
+    }
+
     pub fn message_by_id(&self, id: i64) -> Option<Message> {
         let self_ = imp::History::from_instance(self);
         self_.message_map.borrow().get(&id).cloned()
     }
 
+    /// Returns the position in the list of the message with the given id, if it's loaded.
+    pub fn message_position(&self, id: i64) -> Option<u32> {
+        let self_ = imp::History::from_instance(self);
+        self_
+            .list
+            .borrow()
+            .iter()
+            .position(|item| item.message().map(|m| m.id() == id).unwrap_or_default())
+            .map(|position| position as u32)
+    }
+
+    /// Returns the ids of the loaded messages that were sent by others.
+    pub fn incoming_message_ids(&self) -> Vec<i64> {
+        let self_ = imp::History::from_instance(self);
+        self_
+            .message_map
+            .borrow()
+            .values()
+            .filter(|message| !message.is_outgoing())
+            .map(Message::id)
+            .collect()
+    }
+
+    /// Returns the position in the list of the unread messages divider, if any.
+    pub fn unread_divider_position(&self) -> Option<u32> {
+        let self_ = imp::History::from_instance(self);
+        self_
+            .list
+            .borrow()
+            .iter()
+            .position(|item| matches!(item.type_(), ItemType::UnreadDivider(_)))
+            .map(|position| position as u32)
+    }
+
     pub fn handle_update(&self, update: Update) {
         let self_ = imp::History::from_instance(self);
 
         match update {
             Update::NewMessage(update) => {
-                self.append(update.message);
+                self.insert(update.message);
             }
             Update::MessageSendSucceeded(update) => {
                 self.remove(update.old_message_id);
+                self.insert(update.message);
+            }
+            Update::MessageSendFailed(update) => {
+                self.remove(update.old_message_id);
+                self.insert(update.message);
             }
             Update::MessageContent(ref update_) => {
                 if let Some(message) = self_.message_map.borrow().get(&update_.message_id) {
                     message.handle_update(update);
                 }
             }
+            Update::MessageEdited(ref update_) => {
+                if let Some(message) = self_.message_map.borrow().get(&update_.message_id) {
+                    message.handle_update(update);
+                }
+            }
+            Update::MessageIsPinned(ref update_) => {
+                if let Some(message) = self_.message_map.borrow().get(&update_.message_id) {
+                    message.handle_update(update);
+                }
+            }
             Update::DeleteMessages(update) => {
                 if !update.from_cache {
                     for message_id in update.message_ids {
@@ -192,12 +399,10 @@ impl History {
             let added = added as usize;
 
             let mut list = self_.list.borrow_mut();
-            let mut previous_timestamp = if position > 0 {
-                list.get(position - 1)
-                    .and_then(|item| item.message_timestamp())
-            } else {
-                None
-            };
+            let mut previous_timestamp = list
+                .range(..position)
+                .rev()
+                .find_map(|item| item.message_timestamp());
             let mut dividers: Vec<(usize, Item)> = vec![];
             let mut index = position;
 
@@ -284,24 +489,59 @@ impl History {
             .items_changed(position, removed, added);
     }
 
-    pub fn append(&self, message: TelegramMessage) {
+    /// Inserts a new message, keeping the messages sorted by their id. The message is usually
+    /// the newest one, but a message that has just been sent can get an id that is lower than
+    /// the ones of the messages received while it was being sent.
+    pub fn insert(&self, message: TelegramMessage) {
         let self_ = imp::History::from_instance(self);
 
+        // The message would be disconnected from the loaded ones, so it will be loaded later
+        if self.has_newer_messages() {
+            return;
+        }
+
         if let Entry::Vacant(entry) = self_.message_map.borrow_mut().entry(message.id) {
-            let message = Message::new(message, &self.chat());
+            let message = self.new_message(message);
+            let index = self.insertion_index(message.id());
 
             entry.insert(message.clone());
 
             self_
                 .list
                 .borrow_mut()
-                .push_back(Item::for_message(message));
+                .insert(index, Item::for_message(message));
 
-            let index = self_.list.borrow().len() - 1;
             self.items_changed(index as u32, 0, 1);
         }
     }
 
+    /// Returns the position in the list where a message with the given id must be inserted,
+    /// which is right before the first item that comes after it.
+    fn insertion_index(&self, message_id: i64) -> usize {
+        let self_ = imp::History::from_instance(self);
+        let list = self_.list.borrow();
+
+        // The dividers are always followed by the message they belong to, so they can be
+        // compared by the id of the next message
+        let next_message_id = |index: usize| {
+            list.range(index..)
+                .find_map(|item| item.message())
+                .map(|message| message.id())
+        };
+
+        let mut low = 0;
+        let mut high = list.len();
+        while low < high {
+            let middle = (low + high) / 2;
+            match next_message_id(middle) {
+                Some(id) if id < message_id => low = middle + 1,
+                _ => high = middle,
+            }
+        }
+
+        low
+    }
+
     fn prepend(&self, messages: Vec<TelegramMessage>) {
         let self_ = imp::History::from_instance(self);
         let chat = self.chat();
@@ -326,6 +566,110 @@ impl History {
         self.items_changed(0, 0, added as u32);
     }
 
+    fn append_newer(&self, messages: Vec<TelegramMessage>) {
+        let self_ = imp::History::from_instance(self);
+        let last_message_id = self.chat().last_message().map(|m| m.id());
+        let has_last_message = messages.iter().any(|m| Some(m.id) == last_message_id);
+        let position = self_.list.borrow().len();
+        let added = messages.len();
+
+        self.set_has_newer_messages(added > 0 && !has_last_message);
+
+        // The messages are sorted from the newest to the oldest
+        for message in messages.into_iter().rev() {
+            let message = self.new_message(message);
+
+            self_
+                .message_map
+                .borrow_mut()
+                .insert(message.id(), message.clone());
+
+            self_
+                .list
+                .borrow_mut()
+                .push_back(Item::for_message(message));
+        }
+
+        if added > 0 {
+            self.items_changed(position as u32, 0, added as u32);
+        }
+    }
+
+    fn replace_messages(&self, messages: Vec<TelegramMessage>) {
+        let self_ = imp::History::from_instance(self);
+        let last_message_id = self.chat().last_message().map(|m| m.id());
+        let has_last_message = messages.iter().any(|m| Some(m.id) == last_message_id);
+        let removed = self_.list.borrow().len();
+        let added = messages.len();
+
+        self.set_has_newer_messages(!messages.is_empty() && !has_last_message);
+
+        let mut old_message_map = self_.message_map.take();
+        self_.list.borrow_mut().clear();
+
+        // The messages are sorted from the newest to the oldest
+        for message in messages.into_iter().rev() {
+            // Keep the already existing message objects, so that they keep getting updates
+            let message = match old_message_map.remove(&message.id) {
+                Some(message) => message,
+                None => self.new_message(message),
+            };
+
+            self_
+                .message_map
+                .borrow_mut()
+                .insert(message.id(), message.clone());
+
+            self_
+                .list
+                .borrow_mut()
+                .push_back(Item::for_message(message));
+        }
+
+        self.items_changed(0, removed as u32, added as u32);
+    }
+
+    /// Creates the object for the given message, reusing the last message of the chat if
+    /// it's the same message.
+    fn new_message(&self, message: TelegramMessage) -> Message {
+        let chat = self.chat();
+        match chat.last_message() {
+            Some(last_message) if last_message.id() == message.id => last_message,
+            _ => Message::new(message, &chat),
+        }
+    }
+
+    fn insert_unread_divider(&self, last_read_message_id: i64) {
+        let self_ = imp::History::from_instance(self);
+
+        let position = self_.list.borrow().iter().position(|item| {
+            item.message()
+                .map(|m| m.id() > last_read_message_id)
+                .unwrap_or_default()
+        });
+
+        if let Some(position) = position {
+            self_
+                .list
+                .borrow_mut()
+                .insert(position, Item::for_unread_divider(last_read_message_id));
+
+            self.upcast_ref::<gio::ListModel>()
+                .items_changed(position as u32, 0, 1);
+        }
+    }
+
+    fn remove_unread_divider(&self) {
+        let self_ = imp::History::from_instance(self);
+
+        if let Some(position) = self.unread_divider_position() {
+            self_.list.borrow_mut().remove(position as usize);
+
+            self.upcast_ref::<gio::ListModel>()
+                .items_changed(position, 1, 0);
+        }
+    }
+
     fn remove(&self, message_id: i64) {
         let self_ = imp::History::from_instance(self);
 
@@ -341,6 +685,13 @@ impl History {
                 let index = list
                     .binary_search_by(|m| match m.type_() {
                         ItemType::Message(message) => message.id().cmp(&message_id),
+                        ItemType::UnreadDivider(last_read_message_id) => {
+                            if *last_read_message_id < message_id {
+                                Ordering::Less
+                            } else {
+                                Ordering::Greater
+                            }
+                        }
                         ItemType::DayDivider(date_time) => {
                             let ordering = date_time.cmp(
                                 &glib::DateTime::from_unix_utc(message.date() as i64).unwrap(),
@@ -368,6 +719,33 @@ impl History {
         self.property("chat").unwrap().get().unwrap()
     }
 
+    /// Whether there are messages newer than the loaded ones, because the history has been
+    /// loaded from an older message.
+    pub fn has_newer_messages(&self) -> bool {
+        let self_ = imp::History::from_instance(self);
+        self_.has_newer_messages.get()
+    }
+
+    fn set_has_newer_messages(&self, has_newer_messages: bool) {
+        let self_ = imp::History::from_instance(self);
+        self_.has_newer_messages.set(has_newer_messages);
+    }
+
+    pub fn search_query(&self) -> String {
+        let self_ = imp::History::from_instance(self);
+        self_.search_query.borrow().clone()
+    }
+
+    pub fn set_search_query(&self, search_query: &str) {
+        if self.search_query() == search_query {
+            return;
+        }
+
+        let self_ = imp::History::from_instance(self);
+        self_.search_query.replace(search_query.to_string());
+        self.notify("search-query");
+    }
+
     pub fn set_loading(&self, loading: bool) {
         let priv_ = imp::History::from_instance(self);
 
@@ -7,6 +7,9 @@ use crate::session::chat::Message;
 pub enum ItemType {
     Message(Message),
     DayDivider(DateTime),
+    /// Marks the start of the unread messages, placed after the last read message with the
+    /// given id.
+    UnreadDivider(i64),
 }
 
 mod imp {
@@ -73,6 +76,11 @@ impl Item {
         glib::Object::new(&[("type", &type_)]).expect("Failed to create Item")
     }
 
+    pub fn for_unread_divider(last_read_message_id: i64) -> Self {
+        let type_ = ItemType::UnreadDivider(last_read_message_id);
+        glib::Object::new(&[("type", &type_)]).expect("Failed to create Item")
+    }
+
     pub fn type_(&self) -> &ItemType {
         let self_ = imp::Item::from_instance(self);
         self_.type_.get().unwrap()
@@ -1,13 +1,28 @@
+use gettextrs::{gettext, ngettext};
 use gtk::{glib, prelude::*, subclass::prelude::*};
-use tdgrand::enums::{MessageContent, MessageSender as TelegramMessageSender, Update};
-use tdgrand::types::Message as TelegramMessage;
+use tdgrand::enums::{
+    ChatType, MessageContent, MessageForwardOrigin, MessageSender as TelegramMessageSender,
+    MessageSendingState, Update,
+};
+use tdgrand::types::{Message as TelegramMessage, MessageForwardInfo};
 
 use crate::session::{Chat, User};
+use crate::utils::{
+    escape, human_friendly_duration, message_call_description, message_content_preview,
+};
 
 #[derive(Clone, Debug, PartialEq, glib::GBoxed)]
 #[gboxed(type_name = "BoxedMessageContent")]
 pub struct BoxedMessageContent(pub MessageContent);
 
+#[derive(Clone, Debug, Default, PartialEq, glib::GBoxed)]
+#[gboxed(type_name = "BoxedMessageForwardInfo")]
+pub struct BoxedMessageForwardInfo(pub Option<MessageForwardInfo>);
+
+#[derive(Clone, Debug, Default, PartialEq, glib::GBoxed)]
+#[gboxed(type_name = "BoxedMessageSendingState")]
+pub struct BoxedMessageSendingState(pub Option<MessageSendingState>);
+
 #[derive(Clone, Debug, glib::GBoxed)]
 #[gboxed(type_name = "MessageSender")]
 pub enum MessageSender {
@@ -33,7 +48,16 @@ mod imp {
         pub id: Cell<i64>,
         pub sender: OnceCell<MessageSender>,
         pub is_outgoing: Cell<bool>,
+        pub is_pinned: Cell<bool>,
+        pub can_be_edited: Cell<bool>,
+        pub can_be_forwarded: Cell<bool>,
+        pub can_be_deleted_only_for_self: Cell<bool>,
+        pub can_be_deleted_for_all_users: Cell<bool>,
         pub date: Cell<i32>,
+        pub edit_date: Cell<i32>,
+        pub forward_info: RefCell<BoxedMessageForwardInfo>,
+        pub reply_to_message_id: Cell<i64>,
+        pub sending_state: RefCell<BoxedMessageSendingState>,
         pub content: RefCell<Option<BoxedMessageContent>>,
         pub chat: OnceCell<Chat>,
     }
@@ -72,6 +96,41 @@ mod imp {
                         false,
                         glib::ParamFlags::READWRITE | glib::ParamFlags::CONSTRUCT_ONLY,
                     ),
+                    glib::ParamSpec::new_boolean(
+                        "is-pinned",
+                        "Is Pinned",
+                        "Whether this message is pinned or not",
+                        false,
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                    ),
+                    glib::ParamSpec::new_boolean(
+                        "can-be-edited",
+                        "Can Be Edited",
+                        "Whether this message can be edited or not",
+                        false,
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                    ),
+                    glib::ParamSpec::new_boolean(
+                        "can-be-forwarded",
+                        "Can Be Forwarded",
+                        "Whether this message can be forwarded or not",
+                        false,
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::CONSTRUCT_ONLY,
+                    ),
+                    glib::ParamSpec::new_boolean(
+                        "can-be-deleted-only-for-self",
+                        "Can Be Deleted Only For Self",
+                        "Whether this message can be deleted only for the current user or not",
+                        false,
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::CONSTRUCT_ONLY,
+                    ),
+                    glib::ParamSpec::new_boolean(
+                        "can-be-deleted-for-all-users",
+                        "Can Be Deleted For All Users",
+                        "Whether this message can be deleted for all users or not",
+                        false,
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::CONSTRUCT_ONLY,
+                    ),
                     glib::ParamSpec::new_int(
                         "date",
                         "Date",
@@ -81,6 +140,38 @@ mod imp {
                         0,
                         glib::ParamFlags::READWRITE | glib::ParamFlags::CONSTRUCT_ONLY,
                     ),
+                    glib::ParamSpec::new_int(
+                        "edit-date",
+                        "Edit Date",
+                        "The point in time when this message was last edited",
+                        std::i32::MIN,
+                        std::i32::MAX,
+                        0,
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                    ),
+                    glib::ParamSpec::new_boxed(
+                        "forward-info",
+                        "Forward Info",
+                        "The information about the original message, if this message is forwarded",
+                        BoxedMessageForwardInfo::static_type(),
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::CONSTRUCT_ONLY,
+                    ),
+                    glib::ParamSpec::new_int64(
+                        "reply-to-message-id",
+                        "Reply To Message Id",
+                        "The id of the message this message is replying to",
+                        std::i64::MIN,
+                        std::i64::MAX,
+                        0,
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::CONSTRUCT_ONLY,
+                    ),
+                    glib::ParamSpec::new_boxed(
+                        "sending-state",
+                        "Sending State",
+                        "The sending state of this message, if it's not sent yet",
+                        BoxedMessageSendingState::static_type(),
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::CONSTRUCT_ONLY,
+                    ),
                     glib::ParamSpec::new_boxed(
                         "content",
                         "Content",
@@ -103,7 +194,7 @@ mod imp {
 
         fn set_property(
             &self,
-            _obj: &Self::Type,
+            obj: &Self::Type,
             _id: usize,
             value: &glib::Value,
             pspec: &glib::ParamSpec,
@@ -115,7 +206,24 @@ mod imp {
                     self.sender.set(sender).unwrap();
                 }
                 "is-outgoing" => self.is_outgoing.set(value.get().unwrap()),
+                "is-pinned" => obj.set_is_pinned(value.get().unwrap()),
+                "can-be-edited" => obj.set_can_be_edited(value.get().unwrap()),
+                "can-be-forwarded" => self.can_be_forwarded.set(value.get().unwrap()),
+                "can-be-deleted-only-for-self" => {
+                    self.can_be_deleted_only_for_self.set(value.get().unwrap())
+                }
+                "can-be-deleted-for-all-users" => {
+                    self.can_be_deleted_for_all_users.set(value.get().unwrap())
+                }
                 "date" => self.date.set(value.get().unwrap()),
+                "edit-date" => obj.set_edit_date(value.get().unwrap()),
+                "forward-info" => {
+                    self.forward_info.replace(value.get().unwrap());
+                }
+                "reply-to-message-id" => self.reply_to_message_id.set(value.get().unwrap()),
+                "sending-state" => {
+                    self.sending_state.replace(value.get().unwrap());
+                }
                 "content" => {
                     let content = value.get().unwrap();
                     self.content.replace(Some(content));
@@ -129,7 +237,16 @@ mod imp {
             match pspec.name() {
                 "id" => obj.id().to_value(),
                 "is-outgoing" => obj.is_outgoing().to_value(),
+                "is-pinned" => obj.is_pinned().to_value(),
+                "can-be-edited" => obj.can_be_edited().to_value(),
+                "can-be-forwarded" => obj.can_be_forwarded().to_value(),
+                "can-be-deleted-only-for-self" => obj.can_be_deleted_only_for_self().to_value(),
+                "can-be-deleted-for-all-users" => obj.can_be_deleted_for_all_users().to_value(),
                 "date" => obj.date().to_value(),
+                "edit-date" => obj.edit_date().to_value(),
+                "forward-info" => obj.forward_info().to_value(),
+                "reply-to-message-id" => obj.reply_to_message_id().to_value(),
+                "sending-state" => obj.sending_state().to_value(),
                 "content" => self.content.borrow().as_ref().unwrap().to_value(),
                 "chat" => obj.chat().to_value(),
                 _ => unimplemented!(),
@@ -145,6 +262,8 @@ glib::wrapper! {
 impl Message {
     pub fn new(message: TelegramMessage, chat: &Chat) -> Self {
         let content = BoxedMessageContent(message.content);
+        let sending_state = BoxedMessageSendingState(message.sending_state);
+        let forward_info = BoxedMessageForwardInfo(message.forward_info);
         let sender = match message.sender {
             TelegramMessageSender::User(data) => {
                 let user = chat.session().user_list().get_or_create_user(data.user_id);
@@ -160,7 +279,22 @@ impl Message {
             ("id", &message.id),
             ("sender", &sender),
             ("is-outgoing", &message.is_outgoing),
+            ("is-pinned", &message.is_pinned),
+            ("can-be-edited", &message.can_be_edited),
+            ("can-be-forwarded", &message.can_be_forwarded),
+            (
+                "can-be-deleted-only-for-self",
+                &message.can_be_deleted_only_for_self,
+            ),
+            (
+                "can-be-deleted-for-all-users",
+                &message.can_be_deleted_for_all_users,
+            ),
             ("date", &message.date),
+            ("edit-date", &message.edit_date),
+            ("forward-info", &forward_info),
+            ("reply-to-message-id", &message.reply_to_message_id),
+            ("sending-state", &sending_state),
             ("content", &content),
             ("chat", chat),
         ])
@@ -168,9 +302,18 @@ impl Message {
     }
 
     pub fn handle_update(&self, update: Update) {
-        if let Update::MessageContent(data) = update {
-            let new_content = BoxedMessageContent(data.new_content);
-            self.set_content(new_content);
+        match update {
+            Update::MessageContent(data) => {
+                let new_content = BoxedMessageContent(data.new_content);
+                self.set_content(new_content);
+            }
+            Update::MessageEdited(data) => {
+                self.set_edit_date(data.edit_date);
+            }
+            Update::MessageIsPinned(data) => {
+                self.set_is_pinned(data.is_pinned);
+            }
+            _ => {}
         }
     }
 
@@ -189,11 +332,86 @@ impl Message {
         self_.is_outgoing.get()
     }
 
+    pub fn is_pinned(&self) -> bool {
+        let self_ = imp::Message::from_instance(self);
+        self_.is_pinned.get()
+    }
+
+    fn set_is_pinned(&self, is_pinned: bool) {
+        if self.is_pinned() == is_pinned {
+            return;
+        }
+
+        let self_ = imp::Message::from_instance(self);
+        self_.is_pinned.set(is_pinned);
+        self.notify("is-pinned");
+    }
+
     pub fn date(&self) -> i32 {
         let self_ = imp::Message::from_instance(self);
         self_.date.get()
     }
 
+    pub fn can_be_edited(&self) -> bool {
+        let self_ = imp::Message::from_instance(self);
+        self_.can_be_edited.get()
+    }
+
+    pub fn set_can_be_edited(&self, can_be_edited: bool) {
+        if self.can_be_edited() == can_be_edited {
+            return;
+        }
+
+        let self_ = imp::Message::from_instance(self);
+        self_.can_be_edited.set(can_be_edited);
+        self.notify("can-be-edited");
+    }
+
+    pub fn can_be_forwarded(&self) -> bool {
+        let self_ = imp::Message::from_instance(self);
+        self_.can_be_forwarded.get()
+    }
+
+    pub fn can_be_deleted_only_for_self(&self) -> bool {
+        let self_ = imp::Message::from_instance(self);
+        self_.can_be_deleted_only_for_self.get()
+    }
+
+    pub fn can_be_deleted_for_all_users(&self) -> bool {
+        let self_ = imp::Message::from_instance(self);
+        self_.can_be_deleted_for_all_users.get()
+    }
+
+    pub fn edit_date(&self) -> i32 {
+        let self_ = imp::Message::from_instance(self);
+        self_.edit_date.get()
+    }
+
+    fn set_edit_date(&self, edit_date: i32) {
+        if self.edit_date() == edit_date {
+            return;
+        }
+
+        let self_ = imp::Message::from_instance(self);
+        self_.edit_date.set(edit_date);
+        self.notify("edit-date");
+    }
+
+    pub fn forward_info(&self) -> BoxedMessageForwardInfo {
+        let self_ = imp::Message::from_instance(self);
+        self_.forward_info.borrow().clone()
+    }
+
+    pub fn reply_to_message_id(&self) -> i64 {
+        let self_ = imp::Message::from_instance(self);
+        self_.reply_to_message_id.get()
+    }
+
+    pub fn sending_state(&self) -> BoxedMessageSendingState {
+        let self_ = imp::Message::from_instance(self);
+        self_.sending_state.borrow().clone()
+    }
+
     pub fn content(&self) -> BoxedMessageContent {
         self.property("content").unwrap().get().unwrap()
     }
@@ -204,11 +422,188 @@ impl Message {
         }
     }
 
+    pub fn connect_content_notify<F: Fn(&Self, &glib::ParamSpec) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_notify_local(Some("content"), f)
+    }
+
     pub fn chat(&self) -> &Chat {
         let self_ = imp::Message::from_instance(self);
         self_.chat.get().unwrap()
     }
 
+    /// Returns the ids of the users that are named in the text of this service message,
+    /// other than its sender, like the added members.
+    pub fn event_user_ids(&self) -> Vec<i32> {
+        let sender_user_id = self.sender().as_user().map(User::id);
+
+        match self.content().0 {
+            MessageContent::MessageChatAddMembers(data) => {
+                if sender_user_id.as_ref() == data.member_user_ids.get(0) {
+                    Vec::new()
+                } else {
+                    data.member_user_ids
+                }
+            }
+            MessageContent::MessageChatDeleteMember(data) => {
+                if sender_user_id == Some(data.user_id) {
+                    Vec::new()
+                } else {
+                    vec![data.user_id]
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the markup of the text of this message if it's a service message, like
+    /// "Alice joined the group", or `None` if it's a normal message. The names of the sender
+    /// and of the users returned by `event_user_ids` must be already escaped.
+    pub fn event_text(&self, sender_name: &str, user_names: &[String]) -> Option<String> {
+        let is_outgoing = self.is_outgoing();
+        let is_channel =
+            matches!(self.chat().type_(), ChatType::Supergroup(data) if data.is_channel);
+        let sender_user_id = self.sender().as_user().map(User::id);
+
+        let text = match self.content().0 {
+            MessageContent::MessageBasicGroupChatCreate(data) => {
+                let title = escape(&data.title);
+                if is_outgoing {
+                    gettext!("You created the group \"{}\"", title)
+                } else {
+                    gettext!("{} created the group \"{}\"", sender_name, title)
+                }
+            }
+            MessageContent::MessageSupergroupChatCreate(data) => {
+                let title = escape(&data.title);
+                if is_channel {
+                    gettext("Channel created")
+                } else if is_outgoing {
+                    gettext!("You created the group \"{}\"", title)
+                } else {
+                    gettext!("{} created the group \"{}\"", sender_name, title)
+                }
+            }
+            MessageContent::MessageChatUpgradeTo(_) | MessageContent::MessageChatUpgradeFrom(_) => {
+                gettext("The group was upgraded to a supergroup")
+            }
+            MessageContent::MessageChatChangeTitle(data) => {
+                let title = escape(&data.title);
+                if is_channel {
+                    gettext!("Channel name changed to \"{}\"", title)
+                } else if is_outgoing {
+                    gettext!("You changed the group name to \"{}\"", title)
+                } else {
+                    gettext!("{} changed the group name to \"{}\"", sender_name, title)
+                }
+            }
+            MessageContent::MessageChatChangePhoto(_) => {
+                if is_channel {
+                    gettext("Channel photo changed")
+                } else if is_outgoing {
+                    gettext("You changed the group photo")
+                } else {
+                    gettext!("{} changed the group photo", sender_name)
+                }
+            }
+            MessageContent::MessageChatDeletePhoto => {
+                if is_channel {
+                    gettext("Channel photo removed")
+                } else if is_outgoing {
+                    gettext("You removed the group photo")
+                } else {
+                    gettext!("{} removed the group photo", sender_name)
+                }
+            }
+            MessageContent::MessageChatJoinByLink => {
+                if is_outgoing {
+                    gettext("You joined the group via invite link")
+                } else {
+                    gettext!("{} joined the group via invite link", sender_name)
+                }
+            }
+            MessageContent::MessageChatAddMembers(data) => {
+                if sender_user_id.as_ref() == data.member_user_ids.get(0) {
+                    if is_outgoing {
+                        gettext("You joined the group")
+                    } else {
+                        gettext!("{} joined the group", sender_name)
+                    }
+                } else if is_outgoing {
+                    gettext!("You added {}", join_names(user_names))
+                } else {
+                    gettext!("{} added {}", sender_name, join_names(user_names))
+                }
+            }
+            MessageContent::MessageChatDeleteMember(data) => {
+                if sender_user_id == Some(data.user_id) {
+                    if is_outgoing {
+                        gettext("You left the group")
+                    } else {
+                        gettext!("{} left the group", sender_name)
+                    }
+                } else if is_outgoing {
+                    gettext!("You removed {}", join_names(user_names))
+                } else {
+                    gettext!("{} removed {}", sender_name, join_names(user_names))
+                }
+            }
+            MessageContent::MessagePinMessage(data) => {
+                // Show a preview of the pinned message, if it's loaded
+                let pinned_message_preview = self
+                    .chat()
+                    .history()
+                    .message_by_id(data.message_id)
+                    .map(|message| escape(&message_content_preview(&message.content().0, false)));
+
+                match (is_outgoing, pinned_message_preview) {
+                    (true, Some(preview)) => gettext!("You pinned \"{}\"", preview),
+                    (true, None) => gettext("You pinned a message"),
+                    (false, Some(preview)) => gettext!("{} pinned \"{}\"", sender_name, preview),
+                    (false, None) => gettext!("{} pinned a message", sender_name),
+                }
+            }
+            MessageContent::MessageScreenshotTaken => {
+                if is_outgoing {
+                    gettext("You took a screenshot")
+                } else {
+                    gettext!("{} took a screenshot", sender_name)
+                }
+            }
+            MessageContent::MessageChatSetTtl(data) => {
+                if data.ttl == 0 {
+                    if is_outgoing {
+                        gettext("You disabled the auto-delete timer")
+                    } else {
+                        gettext!("{} disabled the auto-delete timer", sender_name)
+                    }
+                } else if is_outgoing {
+                    gettext!(
+                        "You set messages to auto-delete after {}",
+                        ttl_description(data.ttl)
+                    )
+                } else {
+                    gettext!(
+                        "{} set messages to auto-delete after {}",
+                        sender_name,
+                        ttl_description(data.ttl)
+                    )
+                }
+            }
+            MessageContent::MessageContactRegistered => {
+                gettext!("{} joined Telegram", sender_name)
+            }
+            MessageContent::MessageCall(data) => {
+                escape(&message_call_description(is_outgoing, data))
+            }
+            _ => return None,
+        };
+
+        Some(text)
+    }
+
     pub fn sender_name_expression(&self) -> gtk::Expression {
         match self.sender() {
             MessageSender::User(user) => {
@@ -218,4 +613,53 @@ impl Message {
             MessageSender::Chat(chat) => chat.title_expression(),
         }
     }
+
+    /// Returns an expression of the name of the original sender, if this message is forwarded.
+    pub fn forward_sender_name_expression(&self) -> Option<gtk::Expression> {
+        let forward_info = self.forward_info().0?;
+        let session = self.chat().session();
+
+        let chat_title_expression = |chat_id| match session.chat_list().get_chat(chat_id) {
+            Some(chat) => chat.title_expression(),
+            None => gtk::ConstantExpression::new(&gettext("Unknown Chat")).upcast(),
+        };
+
+        let expression = match forward_info.origin {
+            MessageForwardOrigin::User(data) => {
+                let user = session.user_list().get_or_create_user(data.sender_user_id);
+                let user_expression = gtk::ConstantExpression::new(&user);
+                User::full_name_expression(&user_expression)
+            }
+            MessageForwardOrigin::Chat(data) => chat_title_expression(data.sender_chat_id),
+            MessageForwardOrigin::Channel(data) => chat_title_expression(data.chat_id),
+            MessageForwardOrigin::HiddenUser(data) => {
+                gtk::ConstantExpression::new(&data.sender_name).upcast()
+            }
+            MessageForwardOrigin::MessageImport(data) => {
+                gtk::ConstantExpression::new(&data.sender_name).upcast()
+            }
+        };
+
+        Some(expression)
+    }
+}
+
+/// Joins the names in a list like "Alice, Bob and Carol".
+fn join_names(names: &[String]) -> String {
+    match names.split_last() {
+        Some((last_name, [])) => last_name.to_owned(),
+        Some((last_name, first_names)) => {
+            gettext!("{} and {}", first_names.join(&gettext(", ")), last_name)
+        }
+        None => String::new(),
+    }
+}
+
+fn ttl_description(ttl: i32) -> String {
+    let days = ttl / (60 * 60 * 24);
+    if days > 0 && ttl % (60 * 60 * 24) == 0 {
+        ngettext!("{} day", "{} days", days as u32, days)
+    } else {
+        human_friendly_duration(ttl)
+    }
 }
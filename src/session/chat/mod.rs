@@ -2,18 +2,22 @@ mod history;
 mod item;
 mod message;
 
-use self::history::History;
+pub use self::history::History;
 pub use self::item::{Item, ItemType};
 pub use self::message::{BoxedMessageContent, Message, MessageSender};
 
+use gettextrs::{gettext, ngettext};
+use glib::clone;
 use gtk::glib;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
-use tdgrand::enums::{self, ChatType, Update};
+use tdgrand::enums::{self, ChatAction, ChatType, SearchMessagesFilter, Update};
+use tdgrand::functions;
 use tdgrand::types::{Chat as TelegramChat, ChatNotificationSettings, DraftMessage};
 
 use crate::session::Avatar;
-use crate::Session;
+use crate::utils::do_async;
+use crate::{Session, RUNTIME};
 
 #[derive(Clone, Debug, glib::GBoxed)]
 #[gboxed(type_name = "BoxedChatType")]
@@ -29,6 +33,7 @@ pub struct BoxedChatNotificationSettings(pub ChatNotificationSettings);
 
 mod imp {
     use super::*;
+    use glib::subclass::Signal;
     use once_cell::sync::{Lazy, OnceCell};
     use std::cell::{Cell, RefCell};
 
@@ -42,9 +47,20 @@ mod imp {
         pub order: Cell<i64>,
         pub is_pinned: Cell<bool>,
         pub unread_count: Cell<i32>,
+        pub last_read_inbox_message_id: Cell<i64>,
+        pub last_read_outbox_message_id: Cell<i64>,
+        pub member_count: Cell<i32>,
+        pub online_member_count: Cell<i32>,
         pub draft_message: RefCell<BoxedDraftMessage>,
         pub notification_settings: RefCell<Option<BoxedChatNotificationSettings>>,
         pub history: OnceCell<History>,
+        /// The actions currently performed by the users in this chat, with the timeouts
+        /// after which they expire
+        pub actions: RefCell<Vec<(i32, ChatAction, glib::SourceId)>>,
+        /// The pinned messages of this chat, from the newest to the oldest
+        pub pinned_messages: RefCell<Vec<Message>>,
+        /// Whether the pinned messages have been loaded, which happens when the chat is opened
+        pub pinned_messages_loaded: Cell<bool>,
         pub session: OnceCell<Session>,
     }
 
@@ -56,6 +72,16 @@ mod imp {
     }
 
     impl ObjectImpl for Chat {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("pinned-messages-changed", &[], <()>::static_type().into())
+                        .build(),
+                ]
+            });
+            SIGNALS.as_ref()
+        }
+
         fn properties() -> &'static [glib::ParamSpec] {
             static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
                 vec![
@@ -121,6 +147,42 @@ mod imp {
                         0,
                         glib::ParamFlags::READWRITE,
                     ),
+                    glib::ParamSpec::new_int64(
+                        "last-read-inbox-message-id",
+                        "Last Read Inbox Message Id",
+                        "The id of the last read incoming message of this chat",
+                        std::i64::MIN,
+                        std::i64::MAX,
+                        0,
+                        glib::ParamFlags::READWRITE,
+                    ),
+                    glib::ParamSpec::new_int64(
+                        "last-read-outbox-message-id",
+                        "Last Read Outbox Message Id",
+                        "The id of the last read outgoing message of this chat",
+                        std::i64::MIN,
+                        std::i64::MAX,
+                        0,
+                        glib::ParamFlags::READWRITE,
+                    ),
+                    glib::ParamSpec::new_int(
+                        "member-count",
+                        "Member Count",
+                        "The number of members of this chat, if it's a group or a channel",
+                        0,
+                        std::i32::MAX,
+                        0,
+                        glib::ParamFlags::READWRITE,
+                    ),
+                    glib::ParamSpec::new_int(
+                        "online-member-count",
+                        "Online Member Count",
+                        "The number of online members of this chat",
+                        0,
+                        std::i32::MAX,
+                        0,
+                        glib::ParamFlags::READWRITE,
+                    ),
                     glib::ParamSpec::new_boxed(
                         "draft-message",
                         "Draft Message",
@@ -135,6 +197,13 @@ mod imp {
                         BoxedChatNotificationSettings::static_type(),
                         glib::ParamFlags::READWRITE,
                     ),
+                    glib::ParamSpec::new_string(
+                        "action-description",
+                        "Action Description",
+                        "The description of what the users are currently doing in this chat",
+                        None,
+                        glib::ParamFlags::READABLE,
+                    ),
                     glib::ParamSpec::new_object(
                         "history",
                         "History",
@@ -194,6 +263,24 @@ mod imp {
                     let unread_count = value.get().unwrap();
                     self.unread_count.set(unread_count);
                 }
+                "last-read-inbox-message-id" => {
+                    let last_read_inbox_message_id = value.get().unwrap();
+                    self.last_read_inbox_message_id
+                        .set(last_read_inbox_message_id);
+                }
+                "last-read-outbox-message-id" => {
+                    let last_read_outbox_message_id = value.get().unwrap();
+                    self.last_read_outbox_message_id
+                        .set(last_read_outbox_message_id);
+                }
+                "member-count" => {
+                    let member_count = value.get().unwrap();
+                    self.member_count.set(member_count);
+                }
+                "online-member-count" => {
+                    let online_member_count = value.get().unwrap();
+                    self.online_member_count.set(online_member_count);
+                }
                 "draft-message" => {
                     let draft_message = value.get().unwrap();
                     self.draft_message.replace(draft_message);
@@ -220,6 +307,10 @@ mod imp {
                 "order" => self.order.get().to_value(),
                 "is-pinned" => self.is_pinned.get().to_value(),
                 "unread-count" => self.unread_count.get().to_value(),
+                "last-read-inbox-message-id" => self.last_read_inbox_message_id.get().to_value(),
+                "last-read-outbox-message-id" => self.last_read_outbox_message_id.get().to_value(),
+                "member-count" => self.member_count.get().to_value(),
+                "online-member-count" => self.online_member_count.get().to_value(),
                 "draft-message" => self.draft_message.borrow().to_value(),
                 "notification-settings" => self
                     .notification_settings
@@ -227,6 +318,7 @@ mod imp {
                     .as_ref()
                     .unwrap()
                     .to_value(),
+                "action-description" => obj.action_description().to_value(),
                 "history" => self.history.get().to_value(),
                 "session" => self.session.get().to_value(),
                 _ => unimplemented!(),
@@ -262,6 +354,14 @@ impl Chat {
             ("avatar", &avatar),
             ("draft-message", &BoxedDraftMessage(chat.draft_message)),
             ("unread-count", &chat.unread_count),
+            (
+                "last-read-inbox-message-id",
+                &chat.last_read_inbox_message_id,
+            ),
+            (
+                "last-read-outbox-message-id",
+                &chat.last_read_outbox_message_id,
+            ),
             (
                 "notification-settings",
                 &BoxedChatNotificationSettings(chat.notification_settings),
@@ -275,10 +375,17 @@ impl Chat {
         match update {
             Update::NewMessage(_)
             | Update::MessageSendSucceeded(_)
+            | Update::MessageSendFailed(_)
             | Update::MessageContent(_)
+            | Update::MessageEdited(_)
             | Update::DeleteMessages(_) => {
                 self.history().handle_update(update);
             }
+            Update::MessageIsPinned(ref data) => {
+                let (message_id, is_pinned) = (data.message_id, data.is_pinned);
+                self.history().handle_update(update);
+                self.handle_message_is_pinned(message_id, is_pinned);
+            }
             Update::ChatTitle(update) => {
                 self.set_title(update.title);
             }
@@ -290,10 +397,15 @@ impl Chat {
                     Some(last_message) => {
                         let message = match self.history().message_by_id(last_message.id) {
                             Some(message) => message,
+                            // Don't leave a gap in the history if the newest messages
+                            // aren't loaded
+                            None if self.history().has_newer_messages() => {
+                                Message::new(last_message, self)
+                            }
                             None => {
                                 let last_message_id = last_message.id;
 
-                                self.history().append(last_message);
+                                self.history().insert(last_message);
                                 self.history().message_by_id(last_message_id).unwrap()
                             }
                         };
@@ -321,10 +433,20 @@ impl Chat {
             }
             Update::ChatReadInbox(update) => {
                 self.set_unread_count(update.unread_count);
+                self.set_last_read_inbox_message_id(update.last_read_inbox_message_id);
+            }
+            Update::ChatReadOutbox(update) => {
+                self.set_last_read_outbox_message_id(update.last_read_outbox_message_id);
             }
             Update::ChatDraftMessage(update) => {
                 self.set_draft_message(BoxedDraftMessage(update.draft_message));
             }
+            Update::ChatOnlineMemberCount(update) => {
+                self.set_online_member_count(update.online_member_count);
+            }
+            Update::UserChatAction(update) => {
+                self.set_user_action(update.user_id, update.action);
+            }
             _ => {}
         }
     }
@@ -400,6 +522,320 @@ impl Chat {
         }
     }
 
+    pub fn last_read_inbox_message_id(&self) -> i64 {
+        self.property("last-read-inbox-message-id")
+            .unwrap()
+            .get()
+            .unwrap()
+    }
+
+    fn set_last_read_inbox_message_id(&self, last_read_inbox_message_id: i64) {
+        if self.last_read_inbox_message_id() != last_read_inbox_message_id {
+            self.set_property("last-read-inbox-message-id", &last_read_inbox_message_id)
+                .unwrap();
+        }
+    }
+
+    pub fn last_read_outbox_message_id(&self) -> i64 {
+        self.property("last-read-outbox-message-id")
+            .unwrap()
+            .get()
+            .unwrap()
+    }
+
+    fn set_last_read_outbox_message_id(&self, last_read_outbox_message_id: i64) {
+        if self.last_read_outbox_message_id() != last_read_outbox_message_id {
+            self.set_property("last-read-outbox-message-id", &last_read_outbox_message_id)
+                .unwrap();
+        }
+    }
+
+    /// Marks the given messages as viewed by the user, updating the read state of the chat
+    /// without waiting for the next `ChatReadInbox` update.
+    pub fn view_messages(&self, message_ids: Vec<i64>) {
+        let last_read_message_id = match message_ids.iter().max() {
+            Some(id) if *id > self.last_read_inbox_message_id() => *id,
+            _ => return,
+        };
+
+        // All the incoming messages up to the last read one are now read
+        let newly_read_count = self
+            .history()
+            .incoming_message_ids()
+            .into_iter()
+            .filter(|id| *id > self.last_read_inbox_message_id() && *id <= last_read_message_id)
+            .count() as i32;
+
+        self.set_last_read_inbox_message_id(last_read_message_id);
+        self.set_unread_count((self.unread_count() - newly_read_count).max(0));
+
+        let client_id = self.session().client_id();
+        let chat_id = self.id();
+
+        RUNTIME.spawn(async move {
+            functions::ViewMessages::new()
+                .chat_id(chat_id)
+                .message_ids(message_ids)
+                .force_read(true)
+                .send(client_id)
+                .await
+                .unwrap();
+        });
+    }
+
+    pub fn member_count(&self) -> i32 {
+        self.property("member-count").unwrap().get().unwrap()
+    }
+
+    fn set_member_count(&self, member_count: i32) {
+        if self.member_count() != member_count {
+            self.set_property("member-count", &member_count).unwrap();
+        }
+    }
+
+    pub fn online_member_count(&self) -> i32 {
+        self.property("online-member-count").unwrap().get().unwrap()
+    }
+
+    fn set_online_member_count(&self, online_member_count: i32) {
+        if self.online_member_count() != online_member_count {
+            self.set_property("online-member-count", &online_member_count)
+                .unwrap();
+        }
+    }
+
+    /// Loads the number of members of the chat from its full info, if it's a group or
+    /// a channel.
+    pub fn load_member_count(&self) {
+        let client_id = self.session().client_id();
+
+        match self.type_() {
+            ChatType::BasicGroup(data) => {
+                let basic_group_id = data.basic_group_id;
+                do_async(
+                    glib::PRIORITY_DEFAULT_IDLE,
+                    async move {
+                        functions::GetBasicGroupFullInfo::new()
+                            .basic_group_id(basic_group_id)
+                            .send(client_id)
+                            .await
+                    },
+                    clone!(@weak self as obj => move |result| async move {
+                        if let Ok(enums::BasicGroupFullInfo::BasicGroupFullInfo(info)) = result {
+                            obj.set_member_count(info.members.len() as i32);
+                        }
+                    }),
+                );
+            }
+            ChatType::Supergroup(data) => {
+                let supergroup_id = data.supergroup_id;
+                do_async(
+                    glib::PRIORITY_DEFAULT_IDLE,
+                    async move {
+                        functions::GetSupergroupFullInfo::new()
+                            .supergroup_id(supergroup_id)
+                            .send(client_id)
+                            .await
+                    },
+                    clone!(@weak self as obj => move |result| async move {
+                        if let Ok(enums::SupergroupFullInfo::SupergroupFullInfo(info)) = result {
+                            obj.set_member_count(info.member_count);
+                        }
+                    }),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    pub fn pinned_messages(&self) -> Vec<Message> {
+        let self_ = imp::Chat::from_instance(self);
+        self_.pinned_messages.borrow().clone()
+    }
+
+    /// Loads the pinned messages of this chat and emits the `pinned-messages-changed`
+    /// signal when they're loaded.
+    pub fn load_pinned_messages(&self) {
+        let client_id = self.session().client_id();
+        let chat_id = self.id();
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::SearchChatMessages::new()
+                    .chat_id(chat_id)
+                    .filter(SearchMessagesFilter::Pinned)
+                    .limit(100)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                if let Ok(enums::Messages::Messages(result)) = result {
+                    let history = obj.history();
+                    let pinned_messages = result
+                        .messages
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|message| match history.message_by_id(message.id) {
+                            Some(loaded_message) => loaded_message,
+                            None => Message::new(message, &obj),
+                        })
+                        .collect();
+
+                    let self_ = imp::Chat::from_instance(&obj);
+                    self_.pinned_messages.replace(pinned_messages);
+                    self_.pinned_messages_loaded.set(true);
+                    obj.emit_by_name("pinned-messages-changed", &[]).unwrap();
+                }
+            }),
+        );
+    }
+
+    /// Updates the pinned messages after a message has been pinned or unpinned. They're
+    /// left untouched if they haven't been loaded yet, because they will be loaded when
+    /// the chat is opened.
+    fn handle_message_is_pinned(&self, message_id: i64, is_pinned: bool) {
+        let self_ = imp::Chat::from_instance(self);
+        if !self_.pinned_messages_loaded.get() {
+            return;
+        }
+
+        if !is_pinned {
+            self_
+                .pinned_messages
+                .borrow_mut()
+                .retain(|message| message.id() != message_id);
+            self.emit_by_name("pinned-messages-changed", &[]).unwrap();
+        } else if let Some(message) = self.history().message_by_id(message_id) {
+            self.insert_pinned_message(message);
+        } else {
+            let client_id = self.session().client_id();
+            let chat_id = self.id();
+
+            do_async(
+                glib::PRIORITY_DEFAULT_IDLE,
+                async move {
+                    functions::GetMessage::new()
+                        .chat_id(chat_id)
+                        .message_id(message_id)
+                        .send(client_id)
+                        .await
+                },
+                clone!(@weak self as obj => move |result| async move {
+                    match result {
+                        Ok(enums::Message::Message(data)) => {
+                            obj.insert_pinned_message(Message::new(data, &obj));
+                        }
+                        Err(e) => log::warn!("Failed to get the pinned message: {:?}", e),
+                    }
+                }),
+            );
+        }
+    }
+
+    fn insert_pinned_message(&self, message: Message) {
+        let self_ = imp::Chat::from_instance(self);
+        {
+            let mut pinned_messages = self_.pinned_messages.borrow_mut();
+            if pinned_messages.iter().any(|m| m.id() == message.id()) {
+                return;
+            }
+
+            // Keep the pinned messages sorted from the newest to the oldest
+            let index = pinned_messages
+                .iter()
+                .position(|m| m.id() < message.id())
+                .unwrap_or_else(|| pinned_messages.len());
+            pinned_messages.insert(index, message);
+        }
+
+        self.emit_by_name("pinned-messages-changed", &[]).unwrap();
+    }
+
+    pub fn connect_pinned_messages_changed<F: Fn(&Self) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_local("pinned-messages-changed", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            f(&obj);
+
+            None
+        })
+        .unwrap()
+    }
+
+    fn set_user_action(&self, user_id: i32, action: ChatAction) {
+        let self_ = imp::Chat::from_instance(self);
+
+        {
+            let mut actions = self_.actions.borrow_mut();
+            if let Some(position) = actions.iter().position(|(id, ..)| *id == user_id) {
+                let (_, _, source_id) = actions.remove(position);
+                source_id.remove();
+            }
+
+            // Consider the action cancelled if it's not renewed in time
+            if !matches!(action, ChatAction::Cancel) {
+                let source_id = glib::timeout_add_seconds_local_once(
+                    6,
+                    clone!(@weak self as obj => move || {
+                        let self_ = imp::Chat::from_instance(&obj);
+                        self_.actions.borrow_mut().retain(|(id, ..)| *id != user_id);
+                        obj.notify("action-description");
+                    }),
+                );
+                actions.push((user_id, action, source_id));
+            }
+        }
+
+        self.notify("action-description");
+    }
+
+    /// Returns a description of what the users are currently doing in this chat, like
+    /// "Alice is typing…", or an empty string if nobody is doing anything.
+    pub fn action_description(&self) -> String {
+        let self_ = imp::Chat::from_instance(self);
+        let actions = self_.actions.borrow();
+        let first_name = |user_id: i32| {
+            self.session()
+                .user_list()
+                .get_or_create_user(user_id)
+                .first_name()
+        };
+
+        if let ChatType::Private(_) | ChatType::Secret(_) = self.type_() {
+            return actions
+                .first()
+                .map(|(_, action, _)| describe_action(action, ActionUsers::Interlocutor))
+                .unwrap_or_default();
+        }
+
+        // Group the users by the kind of their action, so that users performing the same
+        // kind of action are described together
+        let mut groups: Vec<(String, &ChatAction, Vec<i32>)> = Vec::new();
+        for (user_id, action, _) in actions.iter() {
+            let kind = describe_action(action, ActionUsers::Interlocutor);
+            match groups.iter_mut().find(|(k, ..)| *k == kind) {
+                Some((.., user_ids)) => user_ids.push(*user_id),
+                None => groups.push((kind, action, vec![*user_id])),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(_, action, user_ids)| match user_ids.as_slice() {
+                [user_id] => describe_action(action, ActionUsers::One(&first_name(*user_id))),
+                [first_user_id, second_user_id] => describe_action(
+                    action,
+                    ActionUsers::Two(&first_name(*first_user_id), &first_name(*second_user_id)),
+                ),
+                _ => describe_action(action, ActionUsers::Many(user_ids.len())),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     pub fn draft_message(&self) -> BoxedDraftMessage {
         self.property("draft-message").unwrap().get().unwrap()
     }
@@ -441,3 +877,145 @@ impl Chat {
         gtk::PropertyExpression::new(Chat::static_type(), Some(&chat_expression), "title").upcast()
     }
 }
+
+/// The users performing the same kind of action in a chat.
+enum ActionUsers<'a> {
+    /// The other user of a private or secret chat
+    Interlocutor,
+    One(&'a str),
+    Two(&'a str, &'a str),
+    Many(usize),
+}
+
+/// Describes the action performed by the given users.
+fn describe_action(action: &ChatAction, users: ActionUsers) -> String {
+    match users {
+        ActionUsers::Interlocutor => match action {
+            ChatAction::RecordingVideo | ChatAction::RecordingVideoNote => {
+                gettext("recording a video…")
+            }
+            ChatAction::UploadingVideo(_) | ChatAction::UploadingVideoNote(_) => {
+                gettext("sending a video…")
+            }
+            ChatAction::RecordingVoiceNote => gettext("recording a voice message…"),
+            ChatAction::UploadingVoiceNote(_) => gettext("sending a voice message…"),
+            ChatAction::UploadingPhoto(_) => gettext("sending a photo…"),
+            ChatAction::UploadingDocument(_) => gettext("sending a file…"),
+            ChatAction::ChoosingLocation => gettext("choosing a location…"),
+            ChatAction::ChoosingContact => gettext("choosing a contact…"),
+            ChatAction::StartPlayingGame => gettext("playing a game…"),
+            _ => gettext("typing…"),
+        },
+        ActionUsers::One(name) => match action {
+            ChatAction::RecordingVideo | ChatAction::RecordingVideoNote => {
+                gettext!("{} is recording a video…", name)
+            }
+            ChatAction::UploadingVideo(_) | ChatAction::UploadingVideoNote(_) => {
+                gettext!("{} is sending a video…", name)
+            }
+            ChatAction::RecordingVoiceNote => gettext!("{} is recording a voice message…", name),
+            ChatAction::UploadingVoiceNote(_) => gettext!("{} is sending a voice message…", name),
+            ChatAction::UploadingPhoto(_) => gettext!("{} is sending a photo…", name),
+            ChatAction::UploadingDocument(_) => gettext!("{} is sending a file…", name),
+            ChatAction::ChoosingLocation => gettext!("{} is choosing a location…", name),
+            ChatAction::ChoosingContact => gettext!("{} is choosing a contact…", name),
+            ChatAction::StartPlayingGame => gettext!("{} is playing a game…", name),
+            _ => gettext!("{} is typing…", name),
+        },
+        ActionUsers::Two(first_name, second_name) => match action {
+            ChatAction::RecordingVideo | ChatAction::RecordingVideoNote => {
+                gettext!("{} and {} are recording a video…", first_name, second_name)
+            }
+            ChatAction::UploadingVideo(_) | ChatAction::UploadingVideoNote(_) => {
+                gettext!("{} and {} are sending a video…", first_name, second_name)
+            }
+            ChatAction::RecordingVoiceNote => gettext!(
+                "{} and {} are recording a voice message…",
+                first_name,
+                second_name
+            ),
+            ChatAction::UploadingVoiceNote(_) => gettext!(
+                "{} and {} are sending a voice message…",
+                first_name,
+                second_name
+            ),
+            ChatAction::UploadingPhoto(_) => {
+                gettext!("{} and {} are sending a photo…", first_name, second_name)
+            }
+            ChatAction::UploadingDocument(_) => {
+                gettext!("{} and {} are sending a file…", first_name, second_name)
+            }
+            ChatAction::ChoosingLocation => gettext!(
+                "{} and {} are choosing a location…",
+                first_name,
+                second_name
+            ),
+            ChatAction::ChoosingContact => {
+                gettext!("{} and {} are choosing a contact…", first_name, second_name)
+            }
+            ChatAction::StartPlayingGame => {
+                gettext!("{} and {} are playing a game…", first_name, second_name)
+            }
+            _ => gettext!("{} and {} are typing…", first_name, second_name),
+        },
+        // This is only used for three or more users, but languages can have more plural
+        // forms than English
+        ActionUsers::Many(n) => match action {
+            ChatAction::RecordingVideo | ChatAction::RecordingVideoNote => ngettext!(
+                "{} person is recording a video…",
+                "{} people are recording a video…",
+                n as u32,
+                n
+            ),
+            ChatAction::UploadingVideo(_) | ChatAction::UploadingVideoNote(_) => ngettext!(
+                "{} person is sending a video…",
+                "{} people are sending a video…",
+                n as u32,
+                n
+            ),
+            ChatAction::RecordingVoiceNote => ngettext!(
+                "{} person is recording a voice message…",
+                "{} people are recording a voice message…",
+                n as u32,
+                n
+            ),
+            ChatAction::UploadingVoiceNote(_) => ngettext!(
+                "{} person is sending a voice message…",
+                "{} people are sending a voice message…",
+                n as u32,
+                n
+            ),
+            ChatAction::UploadingPhoto(_) => ngettext!(
+                "{} person is sending a photo…",
+                "{} people are sending a photo…",
+                n as u32,
+                n
+            ),
+            ChatAction::UploadingDocument(_) => ngettext!(
+                "{} person is sending a file…",
+                "{} people are sending a file…",
+                n as u32,
+                n
+            ),
+            ChatAction::ChoosingLocation => ngettext!(
+                "{} person is choosing a location…",
+                "{} people are choosing a location…",
+                n as u32,
+                n
+            ),
+            ChatAction::ChoosingContact => ngettext!(
+                "{} person is choosing a contact…",
+                "{} people are choosing a contact…",
+                n as u32,
+                n
+            ),
+            ChatAction::StartPlayingGame => ngettext!(
+                "{} person is playing a game…",
+                "{} people are playing a game…",
+                n as u32,
+                n
+            ),
+            _ => ngettext!("{} person is typing…", "{} people are typing…", n as u32, n),
+        },
+    }
+}
@@ -3,9 +3,13 @@ use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::{gio, glib};
 use tdgrand::types::Chat as TelegramChat;
-use tdgrand::{enums::Update, functions};
+use tdgrand::{
+    enums::{self, Update},
+    functions,
+};
 
 use crate::session::Chat;
+use crate::utils::do_async;
 use crate::{Session, RUNTIME};
 
 mod imp {
@@ -130,11 +134,26 @@ impl ChatList {
                     chat.handle_update(update);
                 }
             }
+            Update::MessageSendFailed(ref update_) => {
+                if let Some(chat) = self_.list.borrow().get(&update_.message.chat_id) {
+                    chat.handle_update(update);
+                }
+            }
             Update::MessageContent(ref update_) => {
                 if let Some(chat) = self_.list.borrow().get(&update_.chat_id) {
                     chat.handle_update(update);
                 }
             }
+            Update::MessageEdited(ref update_) => {
+                if let Some(chat) = self_.list.borrow().get(&update_.chat_id) {
+                    chat.handle_update(update);
+                }
+            }
+            Update::MessageIsPinned(ref update_) => {
+                if let Some(chat) = self_.list.borrow().get(&update_.chat_id) {
+                    chat.handle_update(update);
+                }
+            }
             Update::NewChat(update) => {
                 self.insert_chat(update.chat);
             }
@@ -168,11 +187,26 @@ impl ChatList {
                     chat.handle_update(update);
                 }
             }
+            Update::ChatReadOutbox(ref update_) => {
+                if let Some(chat) = self_.list.borrow().get(&update_.chat_id) {
+                    chat.handle_update(update);
+                }
+            }
             Update::ChatDraftMessage(ref update_) => {
                 if let Some(chat) = self_.list.borrow().get(&update_.chat_id) {
                     chat.handle_update(update);
                 }
             }
+            Update::ChatOnlineMemberCount(ref update_) => {
+                if let Some(chat) = self_.list.borrow().get(&update_.chat_id) {
+                    chat.handle_update(update);
+                }
+            }
+            Update::UserChatAction(ref update_) => {
+                if let Some(chat) = self_.list.borrow().get(&update_.chat_id) {
+                    chat.handle_update(update);
+                }
+            }
             Update::DeleteMessages(ref update_) => {
                 if let Some(chat) = self_.list.borrow().get(&update_.chat_id) {
                     chat.handle_update(update);
@@ -182,6 +216,26 @@ impl ChatList {
         }
     }
 
+    /// Searches the chats matching the query and passes their ids to the callback.
+    pub fn search<F: FnOnce(Vec<i64>) + 'static>(&self, query: String, callback: F) {
+        let client_id = self.session().client_id();
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::SearchChats::new()
+                    .query(query)
+                    .limit(100)
+                    .send(client_id)
+                    .await
+            },
+            move |result| async move {
+                if let Ok(enums::Chats::Chats(chats)) = result {
+                    callback(chats.chat_ids);
+                }
+            },
+        );
+    }
+
     pub fn get_chat(&self, chat_id: i64) -> Option<Chat> {
         let self_ = imp::ChatList::from_instance(self);
         self_.list.borrow().get(&chat_id).cloned()
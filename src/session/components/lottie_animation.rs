@@ -0,0 +1,179 @@
+use flate2::read::GzDecoder;
+use glib::clone;
+use gtk::{gdk, glib, graphene, prelude::*, subclass::prelude::*};
+use std::fs::File;
+use std::io::Read;
+use std::time::Duration;
+
+mod imp {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default)]
+    pub struct LottieAnimation {
+        pub animation: RefCell<Option<rlottie::Animation>>,
+        pub surface: RefCell<Option<rlottie::Surface>>,
+        pub texture: RefCell<Option<gdk::Texture>>,
+        pub frame: Cell<usize>,
+        pub timeout_id: RefCell<Option<glib::SourceId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for LottieAnimation {
+        const NAME: &'static str = "ComponentsLottieAnimation";
+        type Type = super::LottieAnimation;
+        type ParentType = glib::Object;
+        type Interfaces = (gdk::Paintable,);
+    }
+
+    impl ObjectImpl for LottieAnimation {
+        fn dispose(&self, _obj: &Self::Type) {
+            if let Some(timeout_id) = self.timeout_id.take() {
+                timeout_id.remove();
+            }
+        }
+    }
+
+    impl PaintableImpl for LottieAnimation {
+        fn intrinsic_width(&self, _paintable: &Self::Type) -> i32 {
+            self.surface
+                .borrow()
+                .as_ref()
+                .map(|surface| surface.width() as i32)
+                .unwrap_or_default()
+        }
+
+        fn intrinsic_height(&self, _paintable: &Self::Type) -> i32 {
+            self.surface
+                .borrow()
+                .as_ref()
+                .map(|surface| surface.height() as i32)
+                .unwrap_or_default()
+        }
+
+        fn snapshot(
+            &self,
+            paintable: &Self::Type,
+            snapshot: &gdk::Snapshot,
+            width: f64,
+            height: f64,
+        ) {
+            if let Some(texture) = paintable.current_texture() {
+                let snapshot = snapshot.downcast_ref::<gtk::Snapshot>().unwrap();
+                snapshot.append_texture(
+                    &texture,
+                    &graphene::Rect::new(0.0, 0.0, width as f32, height as f32),
+                );
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    /// A paintable that renders an animated sticker in the TGS format, which is a
+    /// gzipped Lottie animation.
+    pub struct LottieAnimation(ObjectSubclass<imp::LottieAnimation>)
+        @implements gdk::Paintable;
+}
+
+impl LottieAnimation {
+    /// Loads the animation from a TGS file, rendering its frames at the given size. Only
+    /// the first frame is shown until the animation is played.
+    pub fn from_file(path: &str, width: i32, height: i32) -> Option<Self> {
+        let mut json_data = String::new();
+        GzDecoder::new(File::open(path).ok()?)
+            .read_to_string(&mut json_data)
+            .ok()?;
+
+        let animation = rlottie::Animation::from_data(json_data, path, "")?;
+        let surface = rlottie::Surface::new(rlottie::Size::new(
+            width.max(1) as usize,
+            height.max(1) as usize,
+        ));
+
+        let obj: Self = glib::Object::new(&[]).expect("Failed to create LottieAnimation");
+        let self_ = imp::LottieAnimation::from_instance(&obj);
+        self_.animation.replace(Some(animation));
+        self_.surface.replace(Some(surface));
+
+        Some(obj)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        let self_ = imp::LottieAnimation::from_instance(self);
+        self_.timeout_id.borrow().is_some()
+    }
+
+    /// Plays the animation in a loop or pauses it at the current frame.
+    pub fn set_playing(&self, playing: bool) {
+        if self.is_playing() == playing {
+            return;
+        }
+
+        let self_ = imp::LottieAnimation::from_instance(self);
+
+        if playing {
+            let framerate = self_
+                .animation
+                .borrow()
+                .as_ref()
+                .map(rlottie::Animation::framerate)
+                .filter(|framerate| *framerate > 0.0)
+                .unwrap_or(60.0);
+
+            let timeout_id = glib::timeout_add_local(
+                Duration::from_secs_f64(1.0 / framerate),
+                clone!(@weak self as obj => @default-return glib::Continue(false), move || {
+                    obj.next_frame();
+                    glib::Continue(true)
+                }),
+            );
+            self_.timeout_id.replace(Some(timeout_id));
+        } else if let Some(timeout_id) = self_.timeout_id.take() {
+            timeout_id.remove();
+        }
+    }
+
+    fn next_frame(&self) {
+        let self_ = imp::LottieAnimation::from_instance(self);
+        let total_frames = self_
+            .animation
+            .borrow()
+            .as_ref()
+            .map(rlottie::Animation::totalframe)
+            .unwrap_or(1)
+            .max(1);
+
+        self_.frame.set((self_.frame.get() + 1) % total_frames);
+        self_.texture.replace(None);
+        self.invalidate_contents();
+    }
+
+    /// Returns the texture of the current frame, rendering it if needed.
+    fn current_texture(&self) -> Option<gdk::Texture> {
+        let self_ = imp::LottieAnimation::from_instance(self);
+
+        if self_.texture.borrow().is_none() {
+            let mut animation = self_.animation.borrow_mut();
+            let mut surface = self_.surface.borrow_mut();
+            let (animation, surface) = (animation.as_mut()?, surface.as_mut()?);
+
+            animation.render(self_.frame.get(), surface);
+
+            let width = surface.width();
+            let height = surface.height();
+            let bytes = glib::Bytes::from(surface.data_as_bytes());
+            let texture = gdk::MemoryTexture::new(
+                width as i32,
+                height as i32,
+                gdk::MemoryFormat::B8g8r8a8Premultiplied,
+                &bytes,
+                width * 4,
+            );
+
+            self_.texture.replace(Some(texture.upcast()));
+        }
+
+        self_.texture.borrow().clone()
+    }
+}
@@ -1,3 +1,9 @@
 mod avatar;
+mod lottie_animation;
+mod sticker;
+mod web_page_preview;
 
 pub use self::avatar::Avatar;
+pub use self::lottie_animation::LottieAnimation;
+pub use self::sticker::{BoxedSticker, Sticker};
+pub use self::web_page_preview::WebPagePreview;
@@ -0,0 +1,174 @@
+use glib::clone;
+use gtk::{gdk, glib, prelude::*, subclass::prelude::*};
+use tdgrand::types::{File, Sticker as TelegramSticker};
+
+use crate::session::components::LottieAnimation;
+use crate::session::Session;
+
+#[derive(Clone, Debug, glib::GBoxed)]
+#[gboxed(type_name = "BoxedSticker")]
+pub struct BoxedSticker(pub TelegramSticker);
+
+mod imp {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Debug, Default)]
+    pub struct Sticker {
+        pub sticker: RefCell<Option<TelegramSticker>>,
+        pub size: Cell<i32>,
+        pub autoplay: Cell<bool>,
+        pub is_hovered: Cell<bool>,
+        pub animation: RefCell<Option<LottieAnimation>>,
+        pub picture: gtk::Picture,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Sticker {
+        const NAME: &'static str = "ComponentsSticker";
+        type Type = super::Sticker;
+        type ParentType = gtk::Widget;
+    }
+
+    impl ObjectImpl for Sticker {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            self.picture.set_parent(obj);
+
+            // Animated stickers that don't play automatically are played on hover
+            let motion_controller = gtk::EventControllerMotion::new();
+            motion_controller.connect_enter(clone!(@weak obj => move |_, _, _| {
+                let self_ = Sticker::from_instance(&obj);
+                self_.is_hovered.set(true);
+                obj.update_playing();
+            }));
+            motion_controller.connect_leave(clone!(@weak obj => move |_| {
+                let self_ = Sticker::from_instance(&obj);
+                self_.is_hovered.set(false);
+                obj.update_playing();
+            }));
+            obj.add_controller(&motion_controller);
+        }
+
+        fn dispose(&self, _obj: &Self::Type) {
+            self.picture.unparent();
+        }
+    }
+
+    impl WidgetImpl for Sticker {
+        fn map(&self, widget: &Self::Type) {
+            self.parent_map(widget);
+            widget.update_playing();
+        }
+
+        fn unmap(&self, widget: &Self::Type) {
+            self.parent_unmap(widget);
+            widget.update_playing();
+        }
+
+        fn measure(
+            &self,
+            _widget: &Self::Type,
+            _orientation: gtk::Orientation,
+            _for_size: i32,
+        ) -> (i32, i32, i32, i32) {
+            let size = self.size.get();
+            (size, size, -1, -1)
+        }
+
+        fn size_allocate(&self, _widget: &Self::Type, width: i32, height: i32, baseline: i32) {
+            self.picture.allocate(width, height, baseline, None);
+        }
+    }
+}
+
+glib::wrapper! {
+    /// A widget that shows a sticker in a square of the given size. Animated stickers
+    /// show their first frame, unless they're played automatically or hovered.
+    pub struct Sticker(ObjectSubclass<imp::Sticker>)
+        @extends gtk::Widget;
+}
+
+impl Sticker {
+    pub fn new(size: i32, autoplay: bool) -> Self {
+        let sticker: Self = glib::Object::new(&[]).expect("Failed to create Sticker");
+        let self_ = imp::Sticker::from_instance(&sticker);
+        self_.size.set(size);
+        self_.autoplay.set(autoplay);
+        sticker
+    }
+
+    pub fn sticker(&self) -> Option<TelegramSticker> {
+        let self_ = imp::Sticker::from_instance(self);
+        self_.sticker.borrow().clone()
+    }
+
+    pub fn set_sticker(&self, session: &Session, sticker: TelegramSticker) {
+        let self_ = imp::Sticker::from_instance(self);
+
+        if let Some(animation) = self_.animation.take() {
+            animation.set_playing(false);
+        }
+        self_.picture.set_paintable(gdk::NONE_PAINTABLE);
+
+        let file = sticker.sticker.clone();
+        self_.sticker.replace(Some(sticker));
+
+        if file.local.is_downloading_completed {
+            self.load_sticker(&file.local.path);
+        } else {
+            let (sender, receiver) = glib::MainContext::channel::<File>(Default::default());
+
+            receiver.attach(
+                None,
+                clone!(@weak self as obj => @default-return glib::Continue(false), move |file| {
+                    // Check that the widget is still showing the same sticker
+                    if obj.sticker().map(|sticker| sticker.sticker.id) != Some(file.id) {
+                        return glib::Continue(false);
+                    }
+
+                    if file.local.is_downloading_completed {
+                        obj.load_sticker(&file.local.path);
+                        return glib::Continue(false);
+                    }
+
+                    glib::Continue(true)
+                }),
+            );
+
+            session.download_file(file.id, sender);
+        }
+    }
+
+    fn load_sticker(&self, path: &str) {
+        let self_ = imp::Sticker::from_instance(self);
+        let is_animated = self_
+            .sticker
+            .borrow()
+            .as_ref()
+            .map(|sticker| sticker.is_animated)
+            .unwrap_or_default();
+
+        if is_animated {
+            let size = self_.size.get() * self.scale_factor();
+            if let Some(animation) = LottieAnimation::from_file(path, size, size) {
+                self_.picture.set_paintable(Some(&animation));
+                self_.animation.replace(Some(animation));
+                self.update_playing();
+            }
+        } else {
+            let media = gtk::MediaFile::for_filename(path);
+            self_.picture.set_paintable(Some(&media));
+        }
+    }
+
+    fn update_playing(&self) {
+        let self_ = imp::Sticker::from_instance(self);
+
+        if let Some(animation) = self_.animation.borrow().as_ref() {
+            animation
+                .set_playing(self.is_mapped() && (self_.autoplay.get() || self_.is_hovered.get()));
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use glib::clone;
+use gtk::{gdk, gio, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use tdgrand::types::{File, PhotoSize, WebPage};
+
+use crate::session::Session;
+
+/// The size of the preview photo, which is shown as a square thumbnail.
+const PHOTO_SIZE: i32 = 64;
+
+mod imp {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/components-web-page-preview.ui")]
+    pub struct WebPagePreview {
+        pub url: RefCell<String>,
+        pub photo_file_id: Cell<i32>,
+        #[template_child]
+        pub site_name_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub title_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub description_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub picture: TemplateChild<gtk::Picture>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for WebPagePreview {
+        const NAME: &'static str = "ComponentsWebPagePreview";
+        type Type = super::WebPagePreview;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for WebPagePreview {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            // Open the web page when clicking anywhere on the preview
+            let click_gesture = gtk::GestureClick::new();
+            click_gesture.connect_released(clone!(@weak obj => move |_, _, _, _| {
+                obj.open_url();
+            }));
+            obj.add_controller(&click_gesture);
+        }
+
+        fn dispose(&self, obj: &Self::Type) {
+            while let Some(child) = obj.first_child() {
+                child.unparent();
+            }
+        }
+    }
+
+    impl WidgetImpl for WebPagePreview {}
+}
+
+glib::wrapper! {
+    /// A card showing the site name, the title, the description and the photo of a
+    /// web page, which opens the web page when clicked.
+    pub struct WebPagePreview(ObjectSubclass<imp::WebPagePreview>)
+        @extends gtk::Widget;
+}
+
+impl Default for WebPagePreview {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebPagePreview {
+    pub fn new() -> Self {
+        glib::Object::new(&[]).expect("Failed to create WebPagePreview")
+    }
+
+    pub fn set_web_page(&self, session: &Session, web_page: WebPage) {
+        let self_ = imp::WebPagePreview::from_instance(self);
+
+        set_label_text(&self_.site_name_label, &web_page.site_name);
+        set_label_text(&self_.title_label, &web_page.title);
+        set_label_text(&self_.description_label, &web_page.description.text);
+        self_.url.replace(web_page.url);
+
+        self_.picture.set_paintable(gdk::NONE_PAINTABLE);
+        let photo_size = web_page
+            .photo
+            .as_ref()
+            .and_then(|photo| choose_photo_size(&photo.sizes));
+        let file = match photo_size {
+            Some(photo_size) => photo_size.photo.clone(),
+            None => {
+                self_.photo_file_id.set(0);
+                self_.picture.set_visible(false);
+                return;
+            }
+        };
+
+        self_.photo_file_id.set(file.id);
+        self_.picture.set_visible(true);
+
+        if file.local.is_downloading_completed {
+            self.load_photo(&file.local.path);
+        } else {
+            let (sender, receiver) = glib::MainContext::channel::<File>(Default::default());
+
+            receiver.attach(
+                None,
+                clone!(@weak self as obj => @default-return glib::Continue(false), move |file| {
+                    let self_ = imp::WebPagePreview::from_instance(&obj);
+
+                    // Check that the widget is still showing the same photo
+                    if self_.photo_file_id.get() != file.id {
+                        return glib::Continue(false);
+                    }
+
+                    if file.local.is_downloading_completed {
+                        obj.load_photo(&file.local.path);
+                        return glib::Continue(false);
+                    }
+
+                    glib::Continue(true)
+                }),
+            );
+
+            session.download_file(file.id, sender);
+        }
+    }
+
+    fn load_photo(&self, path: &str) {
+        let self_ = imp::WebPagePreview::from_instance(self);
+        let file = gio::File::for_path(path);
+
+        match gdk::Texture::from_file(&file) {
+            Ok(texture) => self_.picture.set_paintable(Some(&texture)),
+            Err(e) => log::warn!("Failed to load web page photo: {}", e),
+        }
+    }
+
+    fn open_url(&self) {
+        let self_ = imp::WebPagePreview::from_instance(self);
+        let url = self_.url.borrow();
+        if url.is_empty() {
+            return;
+        }
+
+        let parent_window: Option<gtk::Window> = self.root().and_then(|root| root.downcast().ok());
+        gtk::show_uri(parent_window.as_ref(), &url, gdk::CURRENT_TIME);
+    }
+}
+
+fn set_label_text(label: &gtk::Label, text: &str) {
+    label.set_text(text);
+    label.set_visible(!text.is_empty());
+}
+
+/// Chooses the smallest photo size that still fills the thumbnail, falling back to the
+/// biggest one available.
+fn choose_photo_size(sizes: &[PhotoSize]) -> Option<&PhotoSize> {
+    sizes
+        .iter()
+        .filter(|size| size.width >= PHOTO_SIZE && size.height >= PHOTO_SIZE)
+        .min_by_key(|size| size.width * size.height)
+        .or_else(|| sizes.iter().max_by_key(|size| size.width * size.height))
+}
@@ -1,31 +1,79 @@
 use gettextrs::gettext;
 use glib::{clone, signal::Inhibit};
-use gtk::{gdk, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
-use std::borrow::Cow;
+use gtk::{gdk, gio, glib, pango, prelude::*, subclass::prelude::*, CompositeTemplate};
+use std::path::PathBuf;
+use std::time::Duration;
 use tdgrand::{
-    enums::{self, ChatAction, InputMessageContent},
+    enums::{self, ChatAction, ChatType, InputMessageContent, MessageContent},
     functions, types,
 };
 
-use crate::session::{chat::BoxedDraftMessage, Chat};
-use crate::utils::do_async;
+use crate::session::chat::{BoxedMessageContent, Item, Message};
+use crate::session::components::{Avatar, WebPagePreview};
+use crate::session::content::{SendMediaDialog, StickerPicker};
+use crate::session::{remove_temporary_file, Chat, User};
+use crate::utils::{
+    contains_link, do_async, escape_markdown, formatted_text_to_markdown, markdown_spans,
+    message_content_preview, parse_markdown, MENTION_URL_PREFIX,
+};
 use crate::RUNTIME;
 
+const MAX_MENTION_SUGGESTIONS: usize = 8;
+/// The delay after the last change of the text before looking for a link to preview.
+const LINK_PREVIEW_DELAY: Duration = Duration::from_millis(500);
+
 mod imp {
     use super::*;
     use once_cell::sync::Lazy;
     use std::cell::{Cell, RefCell};
+    use std::collections::HashSet;
 
     #[derive(Debug, Default, CompositeTemplate)]
     #[template(resource = "/com/github/melix99/telegrand/ui/content-chat-action-bar.ui")]
     pub struct ChatActionBar {
         pub chat: RefCell<Option<Chat>>,
+        pub mention_popover: gtk::Popover,
+        pub mention_list: gtk::ListBox,
+        pub mention_candidates: RefCell<Vec<User>>,
+        /// The offset of the `@` of the mention being completed
+        pub mention_start: Cell<i32>,
+        pub mention_query: RefCell<Option<String>>,
         pub chat_action_in_cooldown: Cell<bool>,
+        pub reply_to_message: RefCell<Option<Message>>,
+        pub editing_message: RefCell<Option<Message>>,
+        pub file_chooser: RefCell<Option<gtk::FileChooserNative>>,
+        /// The files created to send the pasted images, which are removed once sent
+        pub temporary_files: RefCell<HashSet<PathBuf>>,
+        pub link_preview_timeout_id: RefCell<Option<glib::SourceId>>,
+        /// Incremented on every change of the text, so that the results of outdated link
+        /// preview requests can be ignored
+        pub link_preview_request: Cell<u32>,
+        /// The url of the link preview currently shown, if any
+        pub link_preview_url: RefCell<Option<String>>,
+        /// The url of the link preview that was dismissed, so that the message is sent
+        /// without it
+        pub dismissed_link_preview_url: RefCell<Option<String>>,
+        #[template_child]
+        pub reply_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub reply_icon: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub reply_sender_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub reply_message_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub link_preview_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub link_preview: TemplateChild<WebPagePreview>,
+        #[template_child]
+        pub entry_box: TemplateChild<gtk::Box>,
         #[template_child]
         pub frame: TemplateChild<gtk::Frame>,
         #[template_child]
         pub message_entry: TemplateChild<gtk::TextView>,
         #[template_child]
+        pub sticker_picker: TemplateChild<StickerPicker>,
+        #[template_child]
         pub send_message_button: TemplateChild<gtk::Button>,
     }
 
@@ -36,6 +84,8 @@ mod imp {
         type ParentType = gtk::Widget;
 
         fn class_init(klass: &mut Self::Class) {
+            StickerPicker::static_type();
+            WebPagePreview::static_type();
             Self::bind_template(klass);
 
             klass.install_action(
@@ -45,6 +95,19 @@ mod imp {
                     widget.send_text_message();
                 },
             );
+            klass.install_action("chat-action-bar.cancel", None, move |widget, _, _| {
+                widget.cancel();
+            });
+            klass.install_action("chat-action-bar.select-file", None, move |widget, _, _| {
+                widget.select_file();
+            });
+            klass.install_action(
+                "chat-action-bar.dismiss-link-preview",
+                None,
+                move |widget, _, _| {
+                    widget.dismiss_link_preview();
+                },
+            );
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -93,9 +156,16 @@ mod imp {
             self.parent_constructed(obj);
 
             let message_buffer = self.message_entry.buffer();
+            obj.create_markdown_tags(&message_buffer);
+            message_buffer.connect_cursor_position_notify(clone!(@weak obj => move |_| {
+                obj.update_mention_completion();
+            }));
             message_buffer.connect_text_notify(clone!(@weak obj => move |_| {
+                obj.update_markdown_tags();
+                obj.queue_link_preview_update();
+
                 // Enable the send-text-message action only when the message entry contains text
-                let should_enable = !obj.message_entry_text().is_empty();
+                let should_enable = !obj.message_entry_text().trim().is_empty();
                 obj.action_set_enabled("chat-action-bar.send-text-message", should_enable);
 
                 // Send typing action
@@ -107,32 +177,88 @@ mod imp {
             obj.action_set_enabled("chat-action-bar.send-text-message", false);
 
             // Handle the enter key to send the message and also the combination of if with the
-            // right modifier keys to add new lines to the entry
+            // right modifier keys to add new lines to the entry. The escape key cancels the
+            // reply or the editing, if any, and Ctrl+B/I/U format the selected text.
             let key_events = gtk::EventControllerKey::new();
             self.message_entry.add_controller(&key_events);
             key_events.connect_key_pressed(
                 clone!(@weak obj => @default-return Inhibit(false), move |_, key, _, modifier| {
-                    if !modifier.contains(gdk::ModifierType::CONTROL_MASK)
+                    if obj.handle_mention_key(&key) {
+                        Inhibit(true)
+                    } else if modifier.contains(gdk::ModifierType::CONTROL_MASK)
+                        && (key == gdk::keys::constants::b
+                            || key == gdk::keys::constants::i
+                            || key == gdk::keys::constants::u)
+                    {
+                        let marker = if key == gdk::keys::constants::b {
+                            "**"
+                        } else if key == gdk::keys::constants::i {
+                            "_"
+                        } else {
+                            "__"
+                        };
+                        obj.wrap_selection(marker);
+                        Inhibit(true)
+                    } else if !modifier.contains(gdk::ModifierType::CONTROL_MASK)
                         && !modifier.contains(gdk::ModifierType::SHIFT_MASK)
                         && (key == gdk::keys::constants::Return
                             || key == gdk::keys::constants::KP_Enter)
                     {
                         obj.activate_action("chat-action-bar.send-text-message", None);
                         Inhibit(true)
+                    } else if key == gdk::keys::constants::Escape
+                        && (obj.reply_to_message().is_some() || obj.editing_message().is_some())
+                    {
+                        obj.cancel();
+                        Inhibit(true)
                     } else {
                         Inhibit(false)
                     }
                 }),
             );
+
+            // Suggest the users to mention while typing after an `@`
+            self.mention_popover.set_parent(obj);
+            self.mention_popover.set_position(gtk::PositionType::Top);
+            self.mention_popover.set_autohide(false);
+            self.mention_popover.set_has_arrow(false);
+            self.mention_popover.set_child(Some(&self.mention_list));
+            self.mention_popover.add_css_class("mention-popover");
+            self.mention_list
+                .connect_row_activated(clone!(@weak obj => move |_, row| {
+                    obj.insert_mention(row.index());
+                }));
+
+            self.sticker_picker
+                .connect_sticker_selected(clone!(@weak obj => move |_, sticker| {
+                    obj.send_sticker(sticker);
+                }));
+
+            // Attach the images pasted from the clipboard instead of pasting them as text
+            self.message_entry
+                .connect_paste_clipboard(clone!(@weak obj => move |message_entry| {
+                    let clipboard = message_entry.clipboard();
+                    if clipboard.formats().contain_gtype(gdk::Texture::static_type()) {
+                        message_entry.stop_signal_emission("paste-clipboard");
+                        obj.paste_image(&clipboard);
+                    }
+                }));
         }
 
         fn dispose(&self, _obj: &Self::Type) {
-            self.frame.unparent();
-            self.send_message_button.unparent();
+            self.mention_popover.unparent();
+            self.reply_box.unparent();
+            self.link_preview_box.unparent();
+            self.entry_box.unparent();
         }
     }
 
-    impl WidgetImpl for ChatActionBar {}
+    impl WidgetImpl for ChatActionBar {
+        fn size_allocate(&self, widget: &Self::Type, width: i32, height: i32, baseline: i32) {
+            self.parent_size_allocate(widget, width, height, baseline);
+            self.mention_popover.present();
+        }
+    }
 }
 
 glib::wrapper! {
@@ -151,22 +277,26 @@ impl ChatActionBar {
         glib::Object::new(&[]).expect("Failed to create ChatActionBar")
     }
 
+    /// Returns the text of the message entry as it is, so that the whitespace in blocks of
+    /// code is preserved. TDLib removes the surrounding whitespace when sending it.
     fn message_entry_text(&self) -> String {
         let self_ = imp::ChatActionBar::from_instance(self);
         let buffer = self_.message_entry.buffer();
         buffer
             .text(&buffer.start_iter(), &buffer.end_iter(), true)
-            .trim()
             .to_string()
     }
 
     fn compose_text_message(&self) -> InputMessageContent {
-        let text = types::FormattedText {
-            text: self.message_entry_text(),
-            ..Default::default()
+        let self_ = imp::ChatActionBar::from_instance(self);
+        let text = parse_markdown(&self.message_entry_text());
+        let disable_web_page_preview = {
+            let dismissed_url = self_.dismissed_link_preview_url.borrow();
+            dismissed_url.is_some() && *dismissed_url == *self_.link_preview_url.borrow()
         };
         let content = types::InputMessageText {
             text,
+            disable_web_page_preview,
             clear_draft: true,
             ..Default::default()
         };
@@ -174,33 +304,701 @@ impl ChatActionBar {
         InputMessageContent::InputMessageText(content)
     }
 
+    fn reply_to_message_id(&self) -> i64 {
+        self.reply_to_message()
+            .map(|message| message.id())
+            .unwrap_or_default()
+    }
+
     fn send_text_message(&self) {
+        if let Some(message) = self.editing_message() {
+            self.edit_message(&message);
+            return;
+        }
+
         if let Some(chat) = self.chat() {
             let message = self.compose_text_message();
             let client_id = chat.session().client_id();
             let chat_id = chat.id();
+            let reply_to_message_id = self.reply_to_message_id();
 
             // Send the message
             RUNTIME.spawn(async move {
                 functions::SendMessage::new()
                     .chat_id(chat_id)
+                    .reply_to_message_id(reply_to_message_id)
                     .input_message_content(message)
                     .send(client_id)
                     .await
                     .unwrap();
             });
 
-            // Reset message entry
+            // Reset message entry and reply
             let self_ = imp::ChatActionBar::from_instance(self);
             let buffer = self_.message_entry.buffer();
             buffer.set_text("");
+            self.set_reply_to_message(None);
+        }
+    }
+
+    fn edit_message(&self, message: &Message) {
+        let client_id = message.chat().session().client_id();
+        let chat_id = message.chat().id();
+        let message_id = message.id();
+
+        let content = matches!(message.content().0, MessageContent::MessageText(_))
+            .then(|| self.compose_text_message());
+        let caption = parse_markdown(&self.message_entry_text());
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                match content {
+                    Some(content) => {
+                        functions::EditMessageText::new()
+                            .chat_id(chat_id)
+                            .message_id(message_id)
+                            .input_message_content(content)
+                            .send(client_id)
+                            .await
+                    }
+                    None => {
+                        functions::EditMessageCaption::new()
+                            .chat_id(chat_id)
+                            .message_id(message_id)
+                            .caption(caption)
+                            .send(client_id)
+                            .await
+                    }
+                }
+            },
+            clone!(@weak self as obj => move |result| async move {
+                match result {
+                    Ok(_) => {
+                        // Only stop editing if the user didn't start editing something else
+                        // in the meantime
+                        if obj.editing_message().map_or(false, |m| m.id() == message_id) {
+                            obj.set_editing_message(None);
+                        }
+                    }
+                    Err(e) => {
+                        // Keep the message in editing mode, so that the user can retry
+                        let dialog = gtk::MessageDialog::new(
+                            obj.parent_window().as_ref(),
+                            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+                            gtk::MessageType::Error,
+                            gtk::ButtonsType::Close,
+                            &gettext("Failed to Edit Message"),
+                        );
+                        dialog.set_secondary_text(Some(&e.message));
+                        dialog.run_async(|dialog, _| dialog.close());
+                    }
+                }
+            }),
+        );
+    }
+
+    fn compose_file_message(
+        &self,
+        file: &gio::File,
+        is_photo: bool,
+        caption: String,
+    ) -> Option<InputMessageContent> {
+        let path = file.path()?.to_string_lossy().into_owned();
+        let input_file = enums::InputFile::Local(types::InputFileLocal { path });
+        let caption = parse_markdown(&caption);
+
+        let content = if is_photo {
+            InputMessageContent::InputMessagePhoto(types::InputMessagePhoto {
+                photo: input_file,
+                caption,
+                ..Default::default()
+            })
+        } else {
+            InputMessageContent::InputMessageDocument(types::InputMessageDocument {
+                document: input_file,
+                caption,
+                ..Default::default()
+            })
+        };
+
+        Some(content)
+    }
+
+    fn select_file(&self) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+
+        let file_chooser = gtk::FileChooserNative::new(
+            Some(&gettext("Attach File")),
+            self.parent_window().as_ref(),
+            gtk::FileChooserAction::Open,
+            Some(&gettext("_Open")),
+            Some(&gettext("_Cancel")),
+        );
+        file_chooser.set_modal(true);
+        file_chooser.set_select_multiple(true);
+
+        file_chooser.connect_response(clone!(@weak self as obj => move |file_chooser, response| {
+            if response == gtk::ResponseType::Accept {
+                let files = file_chooser.files();
+                let files = (0..files.n_items())
+                    .filter_map(|position| files.item(position)?.downcast::<gio::File>().ok())
+                    .collect();
+                obj.send_files(files);
+            }
+
+            let self_ = imp::ChatActionBar::from_instance(&obj);
+            self_.file_chooser.replace(None);
+        }));
+
+        file_chooser.show();
+        self_.file_chooser.replace(Some(file_chooser));
+    }
+
+    fn paste_image(&self, clipboard: &gdk::Clipboard) {
+        clipboard.read_texture_async(
+            None::<&gio::Cancellable>,
+            clone!(@weak self as obj => move |result| {
+                if let Ok(Some(texture)) = result {
+                    // Save the image in a temporary file, so that it can be uploaded
+                    let path = glib::tmp_dir()
+                        .join(format!("telegrand-paste-{}.png", glib::real_time()));
+                    if texture.save_to_png(&path) {
+                        let self_ = imp::ChatActionBar::from_instance(&obj);
+                        self_.temporary_files.borrow_mut().insert(path.clone());
+                        obj.send_files(vec![gio::File::for_path(&path)]);
+                    }
+                }
+            }),
+        );
+    }
+
+    /// Opens a dialog to preview each file and add a caption before sending it, one file
+    /// after the other.
+    pub fn send_files(&self, mut files: Vec<gio::File>) {
+        if self.chat().is_none() || files.is_empty() {
+            return;
+        }
+
+        let file = files.remove(0);
+        let dialog = SendMediaDialog::new(&self.parent_window(), &file);
+        dialog.connect_send(clone!(@weak self as obj => move |dialog, caption| {
+            obj.send_media(&dialog.file(), dialog.is_photo(), caption);
+        }));
+        dialog.connect_close_request(
+            clone!(@weak self as obj => @default-return Inhibit(false), move |dialog| {
+                // Remove the pasted image if it hasn't been sent
+                if let Some(path) = obj.take_temporary_file(&dialog.file()) {
+                    remove_temporary_file(&path);
+                }
+
+                obj.send_files(files.clone());
+                Inhibit(false)
+            }),
+        );
+        dialog.present();
+    }
+
+    fn send_media(&self, file: &gio::File, is_photo: bool, caption: String) {
+        let chat = match self.chat() {
+            Some(chat) => chat,
+            None => return,
+        };
+        let message = match self.compose_file_message(file, is_photo, caption) {
+            Some(message) => message,
+            None => return,
+        };
+        let temporary_file = self.take_temporary_file(file);
+        let session = chat.session();
+        let client_id = session.client_id();
+        let chat_id = chat.id();
+        let reply_to_message_id = self.reply_to_message_id();
+
+        // Send the message
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::SendMessage::new()
+                    .chat_id(chat_id)
+                    .reply_to_message_id(reply_to_message_id)
+                    .input_message_content(message)
+                    .send(client_id)
+                    .await
+            },
+            move |result| async move {
+                match result {
+                    Ok(enums::Message::Message(message)) => {
+                        if let Some(path) = temporary_file {
+                            session.add_temporary_file(chat_id, message.id, path);
+                        }
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to send the file: {:?}", e);
+                        if let Some(path) = temporary_file {
+                            remove_temporary_file(&path);
+                        }
+                    }
+                }
+            },
+        );
+
+        self.set_reply_to_message(None);
+    }
+
+    /// Returns the path of the given file if it's a temporary file, so that the caller
+    /// becomes responsible of removing it.
+    fn take_temporary_file(&self, file: &gio::File) -> Option<PathBuf> {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        let path = file.path()?;
+        self_.temporary_files.borrow_mut().take(&path)
+    }
+
+    fn send_sticker(&self, sticker: types::Sticker) {
+        if let Some(chat) = self.chat() {
+            let message = InputMessageContent::InputMessageSticker(types::InputMessageSticker {
+                sticker: enums::InputFile::Id(types::InputFileId {
+                    id: sticker.sticker.id,
+                }),
+                width: sticker.width,
+                height: sticker.height,
+                ..Default::default()
+            });
+            let client_id = chat.session().client_id();
+            let chat_id = chat.id();
+            let reply_to_message_id = self.reply_to_message_id();
+
+            // Send the message
+            RUNTIME.spawn(async move {
+                functions::SendMessage::new()
+                    .chat_id(chat_id)
+                    .reply_to_message_id(reply_to_message_id)
+                    .input_message_content(message)
+                    .send(client_id)
+                    .await
+                    .unwrap();
+            });
+
+            self.set_reply_to_message(None);
+        }
+    }
+
+    fn create_markdown_tags(&self, buffer: &gtk::TextBuffer) {
+        buffer.create_tag(Some("bold"), &[("weight", &700)]);
+        buffer.create_tag(Some("italic"), &[("style", &pango::Style::Italic)]);
+        buffer.create_tag(
+            Some("underline"),
+            &[("underline", &pango::Underline::Single)],
+        );
+        buffer.create_tag(Some("strikethrough"), &[("strikethrough", &true)]);
+        buffer.create_tag(Some("code"), &[("family", &"monospace")]);
+        buffer.create_tag(
+            Some("link"),
+            &[
+                ("underline", &pango::Underline::Single),
+                ("foreground", &"#3584e4"),
+            ],
+        );
+        buffer.create_tag(Some("markdown-marker"), &[("foreground", &"#9a9996")]);
+    }
+
+    /// Styles the markdown in the message entry, so that the formatting that will be
+    /// sent is visible while writing.
+    fn update_markdown_tags(&self) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        let buffer = self_.message_entry.buffer();
+        let (start, end) = buffer.bounds();
+
+        for name in &[
+            "bold",
+            "italic",
+            "underline",
+            "strikethrough",
+            "code",
+            "link",
+            "markdown-marker",
+        ] {
+            buffer.remove_tag_by_name(name, &start, &end);
+        }
+
+        let text = buffer.text(&start, &end, true);
+        for span in markdown_spans(&text) {
+            if let Some(name) = span.entity_type.as_ref().and_then(markdown_tag_name) {
+                buffer.apply_tag_by_name(
+                    name,
+                    &buffer.iter_at_offset(span.content.start as i32),
+                    &buffer.iter_at_offset(span.content.end as i32),
+                );
+            }
+
+            for marker in span.markers {
+                buffer.apply_tag_by_name(
+                    "markdown-marker",
+                    &buffer.iter_at_offset(marker.start as i32),
+                    &buffer.iter_at_offset(marker.end as i32),
+                );
+            }
+        }
+    }
+
+    /// Surrounds the selected text with a markdown marker, or inserts an empty pair
+    /// of markers at the cursor if there's no selection.
+    fn wrap_selection(&self, marker: &str) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        let buffer = self_.message_entry.buffer();
+        let marker_len = marker.chars().count() as i32;
+
+        let (start, end) = match buffer.selection_bounds() {
+            Some((start, end)) => (start.offset(), end.offset()),
+            None => {
+                let offset = buffer.iter_at_mark(&buffer.get_insert()).offset();
+                (offset, offset)
+            }
+        };
+
+        buffer.begin_user_action();
+        buffer.insert(&mut buffer.iter_at_offset(end), marker);
+        buffer.insert(&mut buffer.iter_at_offset(start), marker);
+        buffer.end_user_action();
+
+        buffer.select_range(
+            &buffer.iter_at_offset(start + marker_len),
+            &buffer.iter_at_offset(end + marker_len),
+        );
+    }
+
+    /// Returns the offset of the `@` and the text typed after it, if the cursor is at
+    /// the end of a mention that is being written.
+    fn mention_query(&self) -> Option<(i32, String)> {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        let buffer = self_.message_entry.buffer();
+        if buffer.has_selection() {
+            return None;
+        }
+
+        let cursor = buffer.iter_at_mark(&buffer.get_insert());
+        let mut line_start = cursor.clone();
+        line_start.set_line_offset(0);
+
+        let chars: Vec<char> = buffer.text(&line_start, &cursor, true).chars().collect();
+        let at = chars.iter().rposition(|c| *c == '@')?;
+        if at > 0 && !chars[at - 1].is_whitespace() {
+            return None;
+        }
+
+        let query: String = chars[at + 1..].iter().collect();
+        if !query.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+
+        Some((line_start.offset() + at as i32, query))
+    }
+
+    fn update_mention_completion(&self) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        let (chat, (start, query)) = match (self.chat(), self.mention_query()) {
+            (Some(chat), Some(mention_query)) => (chat, mention_query),
+            _ => {
+                self.hide_mention_completion();
+                return;
+            }
+        };
+
+        if self_.mention_start.get() == start
+            && self_.mention_query.borrow().as_deref() == Some(query.as_str())
+        {
+            return;
+        }
+        self_.mention_start.set(start);
+        self_.mention_query.replace(Some(query.clone()));
+
+        // Show the users that recently wrote in the chat right away
+        let me = chat.session().me();
+        let candidates: Vec<User> = recent_participants(&chat)
+            .into_iter()
+            .filter(|user| Some(user) != me.as_ref() && user_matches(user, &query))
+            .take(MAX_MENTION_SUGGESTIONS)
+            .collect();
+        self.show_mention_suggestions(candidates);
+
+        // Then add the other matching members of the group
+        if !matches!(
+            chat.type_(),
+            ChatType::BasicGroup(_) | ChatType::Supergroup(_)
+        ) {
+            return;
+        }
+
+        let client_id = chat.session().client_id();
+        let chat_id = chat.id();
+        let search_query = query.clone();
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::SearchChatMembers::new()
+                    .chat_id(chat_id)
+                    .query(search_query)
+                    .limit(MAX_MENTION_SUGGESTIONS as i32)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj, @weak chat => move |result| async move {
+                let self_ = imp::ChatActionBar::from_instance(&obj);
+
+                // Ignore the result if the query changed in the meantime
+                if self_.mention_query.borrow().as_deref() != Some(query.as_str()) {
+                    return;
+                }
+
+                if let Ok(enums::ChatMembers::ChatMembers(data)) = result {
+                    let me = chat.session().me();
+                    let mut candidates = self_.mention_candidates.borrow().clone();
+
+                    for member in data.members {
+                        if let enums::MessageSender::User(data) = member.member_id {
+                            let user = chat.session().user_list().get_or_create_user(data.user_id);
+                            if Some(&user) != me.as_ref() && !candidates.contains(&user) {
+                                candidates.push(user);
+                            }
+                        }
+                    }
+
+                    candidates.truncate(MAX_MENTION_SUGGESTIONS);
+                    obj.show_mention_suggestions(candidates);
+                }
+            }),
+        );
+    }
+
+    fn show_mention_suggestions(&self, candidates: Vec<User>) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+
+        if candidates.is_empty() {
+            self_.mention_candidates.borrow_mut().clear();
+            self_.mention_popover.popdown();
+            return;
+        }
+
+        while let Some(row) = self_.mention_list.first_child() {
+            self_.mention_list.remove(&row);
+        }
+
+        for user in &candidates {
+            self_.mention_list.append(&mention_row(user));
+        }
+        self_
+            .mention_list
+            .select_row(self_.mention_list.row_at_index(0).as_ref());
+        self_.mention_candidates.replace(candidates);
+
+        // Point to the `@` of the mention
+        let message_entry = &*self_.message_entry;
+        let buffer = message_entry.buffer();
+        let location =
+            message_entry.iter_location(&buffer.iter_at_offset(self_.mention_start.get()));
+        let (x, y) = message_entry.buffer_to_window_coords(
+            gtk::TextWindowType::Widget,
+            location.x(),
+            location.y(),
+        );
+        if let Some((x, y)) = message_entry.translate_coordinates(self, x as f64, y as f64) {
+            self_.mention_popover.set_pointing_to(&gdk::Rectangle::new(
+                x as i32,
+                y as i32,
+                1,
+                location.height(),
+            ));
+        }
+
+        self_.mention_popover.popup();
+    }
+
+    fn hide_mention_completion(&self) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        self_.mention_query.replace(None);
+        self_.mention_candidates.borrow_mut().clear();
+        self_.mention_popover.popdown();
+    }
+
+    /// Handles the keys used to navigate the mention suggestions, returning whether the
+    /// key was handled.
+    fn handle_mention_key(&self, key: &gdk::keys::Key) -> bool {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        if !self_.mention_popover.is_visible() {
+            return false;
+        }
+
+        let n_candidates = self_.mention_candidates.borrow().len() as i32;
+        let selected = self_
+            .mention_list
+            .selected_row()
+            .map(|row| row.index())
+            .unwrap_or_default();
+
+        if *key == gdk::keys::constants::Up || *key == gdk::keys::constants::Down {
+            let offset = if *key == gdk::keys::constants::Up {
+                -1
+            } else {
+                1
+            };
+            let index = (selected + offset).rem_euclid(n_candidates.max(1));
+            self_
+                .mention_list
+                .select_row(self_.mention_list.row_at_index(index).as_ref());
+            true
+        } else if *key == gdk::keys::constants::Return
+            || *key == gdk::keys::constants::KP_Enter
+            || *key == gdk::keys::constants::Tab
+        {
+            self.insert_mention(selected);
+            true
+        } else if *key == gdk::keys::constants::Escape {
+            self.hide_mention_completion();
+            true
+        } else {
+            false
         }
     }
 
+    /// Replaces the mention being written with a mention of the candidate at the given
+    /// index. Users without a username are mentioned by name.
+    fn insert_mention(&self, index: i32) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        let user = match self_.mention_candidates.borrow().get(index as usize) {
+            Some(user) => user.clone(),
+            None => return,
+        };
+
+        let username = user.username();
+        let mention = if username.is_empty() {
+            let name = format!("{} {}", user.first_name(), user.last_name());
+            format!(
+                "[{}]({}{}) ",
+                escape_markdown(name.trim()),
+                MENTION_URL_PREFIX,
+                user.id()
+            )
+        } else {
+            format!("@{} ", username)
+        };
+
+        let buffer = self_.message_entry.buffer();
+        let mut start = buffer.iter_at_offset(self_.mention_start.get());
+        let mut end = buffer.iter_at_mark(&buffer.get_insert());
+
+        buffer.begin_user_action();
+        buffer.delete(&mut start, &mut end);
+        buffer.insert(&mut start, &mention);
+        buffer.end_user_action();
+
+        self.hide_mention_completion();
+    }
+
+    /// Looks for a link to preview once the text stops changing, to avoid a request for
+    /// every typed character.
+    fn queue_link_preview_update(&self) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+
+        if let Some(source_id) = self_.link_preview_timeout_id.take() {
+            source_id.remove();
+        }
+        self_
+            .link_preview_request
+            .set(self_.link_preview_request.get().wrapping_add(1));
+
+        if self.message_entry_text().trim().is_empty() {
+            self.hide_link_preview();
+            self_.dismissed_link_preview_url.replace(None);
+            return;
+        }
+
+        let source_id = glib::timeout_add_local_once(
+            LINK_PREVIEW_DELAY,
+            clone!(@weak self as obj => move || {
+                let self_ = imp::ChatActionBar::from_instance(&obj);
+                self_.link_preview_timeout_id.replace(None);
+                obj.update_link_preview();
+            }),
+        );
+        self_.link_preview_timeout_id.replace(Some(source_id));
+    }
+
+    fn update_link_preview(&self) {
+        let chat = match self.chat() {
+            Some(chat) => chat,
+            None => return,
+        };
+        let text = parse_markdown(&self.message_entry_text());
+        if !contains_link(&text) {
+            self.hide_link_preview();
+            return;
+        }
+
+        let self_ = imp::ChatActionBar::from_instance(self);
+        let request = self_.link_preview_request.get();
+        let client_id = chat.session().client_id();
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::GetWebPagePreview::new()
+                    .text(text)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                // Ignore the result if the text has changed in the meantime
+                let self_ = imp::ChatActionBar::from_instance(&obj);
+                if self_.link_preview_request.get() != request {
+                    return;
+                }
+
+                match result {
+                    Ok(enums::WebPage::WebPage(web_page)) => obj.show_link_preview(web_page),
+                    Err(_) => obj.hide_link_preview(),
+                }
+            }),
+        );
+    }
+
+    fn show_link_preview(&self, web_page: types::WebPage) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        let chat = match self.chat() {
+            Some(chat) => chat,
+            None => return,
+        };
+
+        let is_dismissed =
+            self_.dismissed_link_preview_url.borrow().as_ref() == Some(&web_page.url);
+        self_.link_preview_url.replace(Some(web_page.url.clone()));
+        self_.link_preview_box.set_visible(!is_dismissed);
+
+        if !is_dismissed {
+            self_.link_preview.set_web_page(&chat.session(), web_page);
+        }
+    }
+
+    fn hide_link_preview(&self) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        self_.link_preview_url.replace(None);
+        self_.link_preview_box.set_visible(false);
+    }
+
+    fn dismiss_link_preview(&self) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        let url = self_.link_preview_url.borrow().clone();
+        self_.dismissed_link_preview_url.replace(url);
+        self_.link_preview_box.set_visible(false);
+    }
+
+    fn parent_window(&self) -> Option<gtk::Window> {
+        self.root()?.downcast().ok()
+    }
+
     fn save_message_as_draft(&self) {
         if let Some(chat) = self.chat() {
             let message = self.compose_text_message();
             let draft_message = types::DraftMessage {
+                reply_to_message_id: self.reply_to_message_id(),
                 input_message_text: message,
                 ..Default::default()
             };
@@ -219,21 +1017,141 @@ impl ChatActionBar {
         }
     }
 
-    fn load_draft_message(&self, message: BoxedDraftMessage) {
+    fn load_draft_message(&self, chat: &Chat) {
+        let message = chat.draft_message();
+
         // TODO: Load more message types
         let message_text = message
             .0
             .as_ref()
             .map(|message| match message.input_message_text {
                 enums::InputMessageContent::InputMessageText(ref content) => {
-                    Cow::Borrowed(content.text.text.as_str())
+                    formatted_text_to_markdown(&content.text)
                 }
-                _ => Cow::Owned(gettext("Unsupported draft message type")),
+                _ => gettext("Unsupported draft message type"),
             })
             .unwrap_or_default();
 
         let self_ = imp::ChatActionBar::from_instance(self);
-        self_.message_entry.buffer().set_text(&*message_text);
+        self_.message_entry.buffer().set_text(&message_text);
+
+        // Restore the message the draft was replying to, if it's available
+        let reply_to_message = message
+            .0
+            .as_ref()
+            .and_then(|message| chat.history().message_by_id(message.reply_to_message_id));
+        self.set_reply_to_message(reply_to_message);
+    }
+
+    pub fn reply_to_message(&self) -> Option<Message> {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        self_.reply_to_message.borrow().clone()
+    }
+
+    pub fn set_reply_to_message(&self, message: Option<Message>) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+
+        if let Some(ref message) = message {
+            // Replying and editing can't be done at the same time
+            if self.editing_message().is_some() {
+                self.set_editing_message(None);
+            }
+
+            self.show_message_preview(
+                "mail-reply-sender-symbolic",
+                &message.sender_name_expression(),
+                message,
+            );
+        } else if self.editing_message().is_none() {
+            self_.reply_box.set_visible(false);
+        }
+
+        self_.reply_to_message.replace(message);
+    }
+
+    pub fn editing_message(&self) -> Option<Message> {
+        let self_ = imp::ChatActionBar::from_instance(self);
+        self_.editing_message.borrow().clone()
+    }
+
+    pub fn set_editing_message(&self, message: Option<Message>) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+
+        match message {
+            Some(message) => {
+                let text = match editable_text(&message) {
+                    Some(text) => text,
+                    None => return,
+                };
+
+                // Save the current draft, so that it can be restored after editing
+                if self.editing_message().is_none() {
+                    self.save_message_as_draft();
+                }
+
+                self_.reply_to_message.replace(None);
+
+                let title_expression = gtk::ConstantExpression::new(&gettext("Edit Message"));
+                self.show_message_preview(
+                    "document-edit-symbolic",
+                    title_expression.upcast_ref(),
+                    &message,
+                );
+
+                self_.editing_message.replace(Some(message));
+                self_.message_entry.buffer().set_text(&text);
+            }
+            None => {
+                if self_.editing_message.take().is_none() {
+                    return;
+                }
+
+                self_.reply_box.set_visible(false);
+
+                match self.chat() {
+                    Some(chat) => self.load_draft_message(&chat),
+                    None => self_.message_entry.buffer().set_text(""),
+                }
+            }
+        }
+    }
+
+    fn cancel(&self) {
+        if self.editing_message().is_some() {
+            self.set_editing_message(None);
+        } else {
+            self.set_reply_to_message(None);
+        }
+    }
+
+    fn show_message_preview(
+        &self,
+        icon_name: &str,
+        title_expression: &gtk::Expression,
+        message: &Message,
+    ) {
+        let self_ = imp::ChatActionBar::from_instance(self);
+
+        self_.reply_icon.set_icon_name(Some(icon_name));
+        title_expression.bind(&*self_.reply_sender_label, "label", gtk::NONE_WIDGET);
+
+        let message_expression = gtk::ConstantExpression::new(message);
+        let content_expression = gtk::PropertyExpression::new(
+            Message::static_type(),
+            Some(&message_expression),
+            "content",
+        );
+        let preview_expression = gtk::ClosureExpression::new(
+            move |expressions| -> String {
+                let content = expressions[1].get::<BoxedMessageContent>().unwrap();
+                message_content_preview(&content.0, false)
+            },
+            &[content_expression.upcast()],
+        );
+        preview_expression.bind(&*self_.reply_message_label, "label", gtk::NONE_WIDGET);
+
+        self_.reply_box.set_visible(true);
+        self_.message_entry.grab_focus();
     }
 
     fn send_chat_action(&self, action: ChatAction) {
@@ -286,17 +1204,118 @@ impl ChatActionBar {
             return;
         }
 
-        self.save_message_as_draft();
-
         let self_ = imp::ChatActionBar::from_instance(self);
 
+        // The draft was already saved when the editing started
+        if self_.editing_message.take().is_some() {
+            self_.reply_box.set_visible(false);
+        } else {
+            self.save_message_as_draft();
+        }
+
+        self_
+            .sticker_picker
+            .set_session(chat.as_ref().map(|chat| chat.session()));
+
         if let Some(ref chat) = chat {
-            self.load_draft_message(chat.draft_message());
+            self.load_draft_message(chat);
 
             self_.chat_action_in_cooldown.set(false);
+        } else {
+            self.set_reply_to_message(None);
         }
 
         self_.chat.replace(chat);
         self.notify("chat");
     }
 }
+
+/// Returns the text of the message that can be changed by editing it, if it's editable.
+fn editable_text(message: &Message) -> Option<String> {
+    match message.content().0 {
+        MessageContent::MessageText(data) => Some(formatted_text_to_markdown(&data.text)),
+        MessageContent::MessagePhoto(data) => Some(formatted_text_to_markdown(&data.caption)),
+        MessageContent::MessageDocument(data) => Some(formatted_text_to_markdown(&data.caption)),
+        _ => None,
+    }
+}
+
+/// Returns the users that wrote the most recent loaded messages of a chat, starting from
+/// the most recent one.
+fn recent_participants(chat: &Chat) -> Vec<User> {
+    let history = chat.history();
+    let mut users: Vec<User> = Vec::new();
+
+    for position in (0..history.n_items()).rev() {
+        let item = match history
+            .item(position)
+            .and_then(|item| item.downcast::<Item>().ok())
+        {
+            Some(item) => item,
+            None => continue,
+        };
+
+        if let Some(user) = item
+            .message()
+            .and_then(|message| message.sender().as_user().cloned())
+        {
+            if !users.contains(&user) {
+                users.push(user);
+            }
+        }
+    }
+
+    users
+}
+
+/// Returns whether the first name, the last name or the username of the user starts with
+/// the query, ignoring the case.
+fn user_matches(user: &User, query: &str) -> bool {
+    let query = query.to_lowercase();
+    [user.first_name(), user.last_name(), user.username()]
+        .iter()
+        .any(|name| name.to_lowercase().starts_with(&query))
+}
+
+fn mention_row(user: &User) -> gtk::Box {
+    let avatar = Avatar::new();
+    avatar.set_size(32);
+    avatar.set_item(Some(user.avatar().clone()));
+
+    let name = format!("{} {}", user.first_name(), user.last_name());
+    let name_label = gtk::Label::new(Some(name.trim()));
+    name_label.set_xalign(0.0);
+    name_label.set_ellipsize(pango::EllipsizeMode::End);
+
+    let labels = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    labels.set_valign(gtk::Align::Center);
+    labels.append(&name_label);
+
+    let username = user.username();
+    if !username.is_empty() {
+        let username_label = gtk::Label::new(Some(&format!("@{}", username)));
+        username_label.set_xalign(0.0);
+        username_label.add_css_class("dim-label");
+        labels.append(&username_label);
+    }
+
+    let row = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+    row.append(&avatar);
+    row.append(&labels);
+    row
+}
+
+/// Returns the name of the text tag used to style the text of an entity in the composer.
+fn markdown_tag_name(entity_type: &enums::TextEntityType) -> Option<&'static str> {
+    match entity_type {
+        enums::TextEntityType::Bold => Some("bold"),
+        enums::TextEntityType::Italic => Some("italic"),
+        enums::TextEntityType::Underline => Some("underline"),
+        enums::TextEntityType::Strikethrough => Some("strikethrough"),
+        enums::TextEntityType::Code
+        | enums::TextEntityType::Pre
+        | enums::TextEntityType::PreCode(_) => Some("code"),
+        enums::TextEntityType::TextUrl(_) | enums::TextEntityType::MentionName(_) => Some("link"),
+        _ => None,
+    }
+}
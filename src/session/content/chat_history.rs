@@ -1,11 +1,22 @@
+use gettextrs::{gettext, ngettext};
 use glib::clone;
-use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
-use tdgrand::enums::ChatType;
+use gtk::{gdk, gio, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use std::collections::HashSet;
+use std::path::Path;
+use tdgrand::enums::{self, ChatType, MessageContent, UserStatus, UserType};
+use tdgrand::functions;
+use tdgrand::types::File;
 
+use crate::session::chat::{Item, Message};
 use crate::session::{
-    content::{ChatActionBar, ItemRow, UserDialog},
-    Chat,
+    content::{
+        ChatActionBar, ChatPickerDialog, ChatPinnedMessageBar, ChatSearchBar, ChatSelectionHeader,
+        ItemRow, UserDialog,
+    },
+    Chat, User,
 };
+use crate::utils::{do_async, message_content_preview};
+use crate::RUNTIME;
 
 mod imp {
     use super::*;
@@ -18,8 +29,31 @@ mod imp {
     pub struct ChatHistory {
         pub compact: Cell<bool>,
         pub chat: RefCell<Option<Chat>>,
+        pub initial_message_id: Cell<Option<i64>>,
+        /// The window of the history and the handler of its `is-active` notify, while mapped
+        pub window_active_handler: RefCell<Option<(gtk::Window, glib::SignalHandlerId)>>,
+        pub selection: RefCell<Option<gtk::MultiSelection>>,
+        pub selection_mode: Cell<bool>,
+        /// The id of the last toggled message, used to select ranges of messages
+        pub selection_anchor: Cell<Option<i64>>,
+        pub file_chooser: RefCell<Option<gtk::FileChooserNative>>,
+        pub chat_handlers: RefCell<Vec<(glib::Object, glib::SignalHandlerId)>>,
+        #[template_child]
+        pub header_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub window_title: TemplateChild<adw::WindowTitle>,
+        #[template_child]
+        pub selection_header: TemplateChild<ChatSelectionHeader>,
+        #[template_child]
+        pub search_bar: TemplateChild<ChatSearchBar>,
         #[template_child]
         pub list_view: TemplateChild<gtk::ListView>,
+        #[template_child]
+        pub scroll_to_bottom_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub unread_count_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub chat_action_bar: TemplateChild<ChatActionBar>,
     }
 
     #[glib::object_subclass]
@@ -31,11 +65,122 @@ mod imp {
         fn class_init(klass: &mut Self::Class) {
             ItemRow::static_type();
             ChatActionBar::static_type();
+            ChatPinnedMessageBar::static_type();
+            ChatSearchBar::static_type();
+            ChatSelectionHeader::static_type();
             Self::bind_template(klass);
 
             klass.install_action("chat-history.view-info", None, move |widget, _, _| {
                 widget.open_info_dialog();
             });
+            klass.install_action(
+                "chat-history.reply",
+                Some("x"),
+                move |widget, _, variant| {
+                    let message_id = variant.unwrap().get().unwrap();
+                    widget.reply_to_message(message_id);
+                },
+            );
+            klass.install_action(
+                "chat-history.forward",
+                Some("x"),
+                move |widget, _, variant| {
+                    let message_id = variant.unwrap().get().unwrap();
+                    widget.forward_messages(vec![message_id]);
+                },
+            );
+            klass.install_action("chat-history.edit", Some("x"), move |widget, _, variant| {
+                let message_id = variant.unwrap().get().unwrap();
+                widget.edit_message(message_id);
+            });
+            klass.install_action(
+                "chat-history.delete",
+                Some("x"),
+                move |widget, _, variant| {
+                    let message_id = variant.unwrap().get().unwrap();
+                    widget.delete_message(message_id);
+                },
+            );
+            klass.install_action("chat-history.pin", Some("x"), move |widget, _, variant| {
+                let message_id = variant.unwrap().get().unwrap();
+                widget.pin_message(message_id);
+            });
+            klass.install_action(
+                "chat-history.unpin",
+                Some("x"),
+                move |widget, _, variant| {
+                    let message_id = variant.unwrap().get().unwrap();
+                    widget.unpin_message(message_id);
+                },
+            );
+            klass.install_action(
+                "chat-history.select",
+                Some("x"),
+                move |widget, _, variant| {
+                    let message_id = variant.unwrap().get().unwrap();
+                    widget.set_selection_mode(true);
+                    widget.toggle_message_selection(message_id, false);
+                },
+            );
+            klass.install_action(
+                "chat-history.enter-selection-mode",
+                None,
+                move |widget, _, _| {
+                    widget.set_selection_mode(true);
+                },
+            );
+            klass.install_action(
+                "chat-history.exit-selection-mode",
+                None,
+                move |widget, _, _| {
+                    widget.set_selection_mode(false);
+                },
+            );
+            klass.install_action("chat-history.copy-selected", None, move |widget, _, _| {
+                widget.copy_selected_messages();
+            });
+            klass.install_action(
+                "chat-history.forward-selected",
+                None,
+                move |widget, _, _| {
+                    let message_ids = widget.selected_messages().iter().map(Message::id).collect();
+                    widget.forward_messages(message_ids);
+                },
+            );
+            klass.install_action("chat-history.delete-selected", None, move |widget, _, _| {
+                widget.delete_messages(widget.selected_messages());
+            });
+            klass.install_action(
+                "chat-history.save-selected-media",
+                None,
+                move |widget, _, _| {
+                    widget.save_selected_media();
+                },
+            );
+            klass.install_action(
+                "chat-history.search-text",
+                Some("s"),
+                move |widget, _, variant| {
+                    let text: String = variant.unwrap().get().unwrap();
+                    let self_ = imp::ChatHistory::from_instance(widget);
+                    self_.search_bar.search_text(&text);
+                },
+            );
+            klass.install_action(
+                "chat-history.scroll-to-bottom",
+                None,
+                move |widget, _, _| {
+                    widget.scroll_to_bottom();
+                },
+            );
+            klass.install_action(
+                "chat-history.scroll-to-message",
+                Some("x"),
+                move |widget, _, variant| {
+                    let message_id = variant.unwrap().get().unwrap();
+                    widget.jump_to_message(message_id);
+                },
+            );
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -100,11 +245,103 @@ mod imp {
             let adj = self.list_view.vadjustment().unwrap();
             adj.connect_value_changed(clone!(@weak obj => move |adj| {
                 obj.load_older_messages(adj);
+                obj.load_newer_messages(adj);
+                obj.update_scroll_to_bottom_revealer(adj);
+                obj.view_visible_messages();
+            }));
+            adj.connect_upper_notify(clone!(@weak obj => move |adj| {
+                obj.update_scroll_to_bottom_revealer(adj);
+                obj.view_visible_messages();
             }));
+
+            // Show the unread count of the chat in the scroll to bottom button
+            let chat_expression = gtk::PropertyExpression::new(
+                super::ChatHistory::static_type(),
+                gtk::NONE_EXPRESSION,
+                "chat",
+            );
+            let unread_count_expression = gtk::PropertyExpression::new(
+                Chat::static_type(),
+                Some(&chat_expression),
+                "unread-count",
+            );
+            let unread_count_label_expression = gtk::ClosureExpression::new(
+                |args| {
+                    let unread_count = args[1].get::<i32>().unwrap();
+                    unread_count.to_string()
+                },
+                &[unread_count_expression.clone().upcast()],
+            );
+            let unread_count_visibility_expression = gtk::ClosureExpression::new(
+                |args| {
+                    let unread_count = args[1].get::<i32>().unwrap();
+                    unread_count > 0
+                },
+                &[unread_count_expression.upcast()],
+            );
+            unread_count_label_expression.bind(&*self.unread_count_label, "label", Some(obj));
+            unread_count_visibility_expression.bind(
+                &*self.unread_count_label,
+                "visible",
+                Some(obj),
+            );
+
+            // Attach the files dropped in the chat history
+            let drop_target =
+                gtk::DropTarget::new(gdk::FileList::static_type(), gdk::DragAction::COPY);
+            drop_target.connect_drop(
+                clone!(@weak obj => @default-return false, move |_, value, _, _| {
+                    match value.get::<gdk::FileList>() {
+                        Ok(file_list) => {
+                            let self_ = imp::ChatHistory::from_instance(&obj);
+                            self_.chat_action_bar.send_files(file_list.files());
+                            true
+                        }
+                        Err(_) => false,
+                    }
+                }),
+            );
+            obj.add_controller(&drop_target);
+
+            obj.update_selection_actions();
+
+            // Keep the relative times in the subtitle up to date
+            glib::timeout_add_seconds_local(
+                60,
+                clone!(@weak obj => @default-return glib::Continue(false), move || {
+                    obj.update_subtitle();
+                    glib::Continue(true)
+                }),
+            );
+        }
+    }
+
+    impl WidgetImpl for ChatHistory {
+        fn map(&self, widget: &Self::Type) {
+            self.parent_map(widget);
+
+            // The messages are viewed only while the window is focused, so view the visible
+            // ones once it's focused again
+            if let Some(window) = widget.parent_window() {
+                let handler_id = window.connect_is_active_notify(clone!(@weak widget => move |_| {
+                    widget.view_visible_messages();
+                }));
+                self.window_active_handler
+                    .replace(Some((window, handler_id)));
+            }
+
+            widget.view_visible_messages();
+        }
+
+        fn unmap(&self, widget: &Self::Type) {
+            if let Some((window, handler_id)) = self.window_active_handler.take() {
+                window.disconnect(handler_id);
+            }
+
+            self.parent_unmap(widget);
         }
     }
 
-    impl WidgetImpl for ChatHistory {}
     impl BinImpl for ChatHistory {}
 }
 
@@ -132,6 +369,610 @@ impl ChatHistory {
         }
     }
 
+    fn update_subtitle(&self) {
+        let self_ = imp::ChatHistory::from_instance(self);
+
+        let subtitle = match self.chat() {
+            Some(chat) => {
+                let action_description = chat.action_description();
+                if !action_description.is_empty() {
+                    action_description
+                } else {
+                    match chat.type_() {
+                        ChatType::Private(_) | ChatType::Secret(_) => interlocutor(&chat)
+                            .map(|user| user_status_description(&user))
+                            .unwrap_or_default(),
+                        ChatType::Supergroup(data) if data.is_channel => {
+                            let member_count = chat.member_count();
+                            ngettext!(
+                                "{} subscriber",
+                                "{} subscribers",
+                                member_count as u32,
+                                member_count
+                            )
+                        }
+                        _ => {
+                            let member_count = chat.member_count();
+                            let online_member_count = chat.online_member_count();
+                            let members = ngettext!(
+                                "{} member",
+                                "{} members",
+                                member_count as u32,
+                                member_count
+                            );
+
+                            // The online count includes us, so only show it when someone
+                            // else is online too
+                            if online_member_count > 1 {
+                                gettext!("{}, {} online", members, online_member_count)
+                            } else {
+                                members
+                            }
+                        }
+                    }
+                }
+            }
+            None => String::new(),
+        };
+
+        self_.window_title.set_subtitle(&subtitle);
+    }
+
+    fn load_newer_messages(&self, adj: &gtk::Adjustment) {
+        if adj.upper() - adj.value() - adj.page_size() < adj.page_size() * 2.0 {
+            if let Some(chat) = self.chat() {
+                chat.history().load_newer_messages();
+            }
+        }
+    }
+
+    fn update_scroll_to_bottom_revealer(&self, adj: &gtk::Adjustment) {
+        let self_ = imp::ChatHistory::from_instance(self);
+        let has_newer_messages = self
+            .chat()
+            .map(|chat| chat.history().has_newer_messages())
+            .unwrap_or_default();
+        let is_at_bottom = adj.upper() - adj.value() - adj.page_size() < adj.page_size() / 2.0;
+
+        self_
+            .scroll_to_bottom_revealer
+            .set_reveal_child(has_newer_messages || !is_at_bottom);
+    }
+
+    /// Marks the unread messages that are currently shown in the list as viewed.
+    fn view_visible_messages(&self) {
+        let chat = match self.chat() {
+            Some(chat) if self.is_mapped() => chat,
+            _ => return,
+        };
+        if !self
+            .parent_window()
+            .map_or(false, |window| window.is_active())
+        {
+            return;
+        }
+
+        let self_ = imp::ChatHistory::from_instance(self);
+        let list_view = &*self_.list_view;
+        let height = list_view.height() as f32;
+        let last_read_message_id = chat.last_read_inbox_message_id();
+        let mut message_ids = vec![];
+
+        let mut child = list_view.first_child();
+        while let Some(widget) = child {
+            let is_visible = widget
+                .compute_bounds(list_view)
+                .map(|bounds| bounds.y() + bounds.height() > 0.0 && bounds.y() < height)
+                .unwrap_or_default();
+
+            if is_visible {
+                if let Some(message) = widget
+                    .first_child()
+                    .and_then(|w| w.downcast::<ItemRow>().ok())
+                    .and_then(|row| row.item())
+                    .and_then(|item| item.message().cloned())
+                {
+                    if !message.is_outgoing() && message.id() > last_read_message_id {
+                        message_ids.push(message.id());
+                    }
+                }
+            }
+
+            child = widget.next_sibling();
+        }
+
+        if !message_ids.is_empty() {
+            chat.view_messages(message_ids);
+        }
+    }
+
+    fn scroll_to_bottom(&self) {
+        if let Some(chat) = self.chat() {
+            chat.history()
+                .load_newest_messages(clone!(@weak self as obj => move |history| {
+                    let n_items = history.n_items();
+                    if n_items > 0 && obj.chat().as_ref() == Some(&history.chat()) {
+                        let self_ = imp::ChatHistory::from_instance(&obj);
+                        self_
+                            .list_view
+                            .activate_action("list.scroll-to-item", Some(&(n_items - 1).to_variant()));
+                    }
+                }));
+        }
+    }
+
+    /// Scrolls to the unread messages divider, loading the unread messages if needed.
+    fn scroll_to_unread_messages(&self) {
+        if let Some(chat) = self.chat() {
+            chat.history()
+                .load_unread_messages(clone!(@weak self as obj => move |history| {
+                    if obj.chat().as_ref() != Some(&history.chat()) {
+                        return;
+                    }
+
+                    if let Some(position) = history.unread_divider_position() {
+                        let self_ = imp::ChatHistory::from_instance(&obj);
+                        self_
+                            .list_view
+                            .activate_action("list.scroll-to-item", Some(&position.to_variant()));
+                    }
+                }));
+        }
+    }
+
+    fn reply_to_message(&self, message_id: i64) {
+        if let Some(chat) = self.chat() {
+            if let Some(message) = chat.history().message_by_id(message_id) {
+                let self_ = imp::ChatHistory::from_instance(self);
+                self_.chat_action_bar.set_reply_to_message(Some(message));
+            }
+        }
+    }
+
+    /// Opens a dialog to choose the chat where to forward the given messages.
+    fn forward_messages(&self, message_ids: Vec<i64>) {
+        let chat = match self.chat() {
+            Some(chat) => chat,
+            None => return,
+        };
+
+        let dialog = ChatPickerDialog::new(&self.parent_window(), &chat.session());
+        dialog.connect_chat_selected(move |_, target_chat| {
+            let client_id = chat.session().client_id();
+            let from_chat_id = chat.id();
+            let chat_id = target_chat.id();
+            let message_ids = message_ids.clone();
+
+            RUNTIME.spawn(async move {
+                functions::ForwardMessages::new()
+                    .chat_id(chat_id)
+                    .from_chat_id(from_chat_id)
+                    .message_ids(message_ids)
+                    .send(client_id)
+                    .await
+                    .unwrap();
+            });
+        });
+        dialog.present();
+    }
+
+    fn edit_message(&self, message_id: i64) {
+        let message = match self
+            .chat()
+            .and_then(|chat| chat.history().message_by_id(message_id))
+        {
+            Some(message) => message,
+            None => return,
+        };
+        let client_id = message.chat().session().client_id();
+        let chat_id = message.chat().id();
+
+        // The time window for editing a message may have expired since the message was
+        // received, so check again whether it can still be edited
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::GetMessage::new()
+                    .chat_id(chat_id)
+                    .message_id(message_id)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                match result {
+                    Ok(enums::Message::Message(data)) => {
+                        message.set_can_be_edited(data.can_be_edited)
+                    }
+                    Err(e) => log::warn!("Failed to get the message to edit: {:?}", e),
+                }
+
+                if message.can_be_edited() {
+                    let self_ = imp::ChatHistory::from_instance(&obj);
+                    self_.chat_action_bar.set_editing_message(Some(message));
+                } else {
+                    let dialog = gtk::MessageDialog::new(
+                        obj.parent_window().as_ref(),
+                        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+                        gtk::MessageType::Error,
+                        gtk::ButtonsType::Close,
+                        &gettext("Cannot Edit Message"),
+                    );
+                    dialog.set_secondary_text(Some(&gettext(
+                        "The time for editing this message has expired.",
+                    )));
+                    dialog.run_async(|dialog, _| dialog.close());
+                }
+            }),
+        );
+    }
+
+    fn pin_message(&self, message_id: i64) {
+        let chat = match self.chat() {
+            Some(chat) => chat,
+            None => return,
+        };
+
+        let dialog = gtk::MessageDialog::new(
+            self.parent_window().as_ref(),
+            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+            gtk::MessageType::Question,
+            gtk::ButtonsType::None,
+            &gettext("Pin Message?"),
+        );
+        dialog.add_buttons(&[
+            (&gettext("_Cancel"), gtk::ResponseType::Cancel),
+            (&gettext("_Pin"), gtk::ResponseType::Accept),
+        ]);
+        if let Some(button) = dialog.widget_for_response(gtk::ResponseType::Accept) {
+            button.add_css_class("suggested-action");
+        }
+
+        // Let the user choose whether to notify the members of groups and channels
+        let notify_check_button = if matches!(chat.type_(), ChatType::Private(_)) {
+            None
+        } else {
+            let check_button = gtk::CheckButton::with_label(&gettext("Notify all members"));
+            check_button.set_active(true);
+            dialog
+                .message_area()
+                .downcast::<gtk::Box>()
+                .unwrap()
+                .append(&check_button);
+            Some(check_button)
+        };
+
+        dialog.connect_response(move |dialog, response| {
+            if response == gtk::ResponseType::Accept {
+                let disable_notification = notify_check_button
+                    .as_ref()
+                    .map(|check_button| !check_button.is_active())
+                    .unwrap_or_default();
+                let client_id = chat.session().client_id();
+                let chat_id = chat.id();
+
+                RUNTIME.spawn(async move {
+                    functions::PinChatMessage::new()
+                        .chat_id(chat_id)
+                        .message_id(message_id)
+                        .disable_notification(disable_notification)
+                        .send(client_id)
+                        .await
+                        .unwrap();
+                });
+            }
+
+            dialog.close();
+        });
+
+        dialog.present();
+    }
+
+    fn unpin_message(&self, message_id: i64) {
+        if let Some(chat) = self.chat() {
+            let client_id = chat.session().client_id();
+            let chat_id = chat.id();
+
+            RUNTIME.spawn(async move {
+                functions::UnpinChatMessage::new()
+                    .chat_id(chat_id)
+                    .message_id(message_id)
+                    .send(client_id)
+                    .await
+                    .unwrap();
+            });
+        }
+    }
+
+    fn delete_message(&self, message_id: i64) {
+        if let Some(message) = self
+            .chat()
+            .and_then(|chat| chat.history().message_by_id(message_id))
+        {
+            self.delete_messages(vec![message]);
+        }
+    }
+
+    fn delete_messages(&self, messages: Vec<Message>) {
+        let chat = match self.chat() {
+            Some(chat) => chat,
+            None => return,
+        };
+        if messages.is_empty() {
+            return;
+        }
+
+        let can_be_deleted_only_for_self =
+            messages.iter().all(Message::can_be_deleted_only_for_self);
+        let can_be_deleted_for_all_users =
+            messages.iter().all(Message::can_be_deleted_for_all_users);
+
+        let dialog = gtk::MessageDialog::new(
+            self.parent_window().as_ref(),
+            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+            gtk::MessageType::Question,
+            gtk::ButtonsType::None,
+            &ngettext("Delete Message?", "Delete Messages?", messages.len() as u32),
+        );
+        dialog.add_buttons(&[
+            (&gettext("_Cancel"), gtk::ResponseType::Cancel),
+            (&gettext("_Delete"), gtk::ResponseType::Accept),
+        ]);
+        if let Some(button) = dialog.widget_for_response(gtk::ResponseType::Accept) {
+            button.add_css_class("destructive-action");
+        }
+
+        // Let the user choose whether to delete the messages for everyone, if it's possible
+        // to delete them both ways
+        let revoke_check_button = if can_be_deleted_only_for_self && can_be_deleted_for_all_users {
+            let check_button = gtk::CheckButton::with_label(&gettext("Delete for everyone"));
+            check_button.set_active(true);
+            dialog
+                .message_area()
+                .downcast::<gtk::Box>()
+                .unwrap()
+                .append(&check_button);
+            Some(check_button)
+        } else {
+            None
+        };
+
+        dialog.connect_response(clone!(@weak self as obj => move |dialog, response| {
+            if response == gtk::ResponseType::Accept {
+                let revoke = match revoke_check_button {
+                    Some(ref check_button) => check_button.is_active(),
+                    None => can_be_deleted_for_all_users,
+                };
+                let client_id = chat.session().client_id();
+                let chat_id = chat.id();
+                let message_ids = messages.iter().map(Message::id).collect();
+
+                RUNTIME.spawn(async move {
+                    functions::DeleteMessages::new()
+                        .chat_id(chat_id)
+                        .message_ids(message_ids)
+                        .revoke(revoke)
+                        .send(client_id)
+                        .await
+                        .unwrap();
+                });
+
+                obj.set_selection_mode(false);
+            }
+
+            dialog.close();
+        }));
+
+        dialog.present();
+    }
+
+    pub fn selection_mode(&self) -> bool {
+        let self_ = imp::ChatHistory::from_instance(self);
+        self_.selection_mode.get()
+    }
+
+    pub fn set_selection_mode(&self, selection_mode: bool) {
+        if self.selection_mode() == selection_mode {
+            return;
+        }
+
+        let self_ = imp::ChatHistory::from_instance(self);
+        self_.selection_mode.set(selection_mode);
+        self_.selection_anchor.set(None);
+
+        if !selection_mode {
+            if let Some(selection) = self_.selection.borrow().as_ref() {
+                selection.unselect_all();
+            }
+        }
+
+        self_
+            .header_stack
+            .set_visible_child_name(if selection_mode {
+                "selection"
+            } else {
+                "default"
+            });
+        self.update_selection_actions();
+    }
+
+    /// Toggles the selection of the given message. If `extend` is true, all the messages
+    /// between the last toggled message and this message get selected instead.
+    pub fn toggle_message_selection(&self, message_id: i64, extend: bool) {
+        let self_ = imp::ChatHistory::from_instance(self);
+
+        let history = match self.chat() {
+            Some(chat) => chat.history(),
+            None => return,
+        };
+        let position = match history.message_position(message_id) {
+            Some(position) => position,
+            None => return,
+        };
+
+        if let Some(selection) = self_.selection.borrow().as_ref() {
+            // Look up the position of the anchor again, because the positions change when
+            // messages are loaded, added or removed
+            let anchor = self_
+                .selection_anchor
+                .get()
+                .and_then(|message_id| history.message_position(message_id));
+
+            match anchor {
+                Some(anchor) if extend => {
+                    let start = anchor.min(position);
+                    let n_items = anchor.max(position) - start + 1;
+                    selection.select_range(start, n_items, false);
+                }
+                _ => {
+                    if selection.is_selected(position) {
+                        selection.unselect_item(position);
+                    } else {
+                        selection.select_item(position, false);
+                    }
+                }
+            }
+        }
+
+        self_.selection_anchor.set(Some(message_id));
+    }
+
+    fn selected_messages(&self) -> Vec<Message> {
+        let self_ = imp::ChatHistory::from_instance(self);
+
+        match self_.selection.borrow().as_ref() {
+            Some(selection) => (0..selection.n_items())
+                .filter(|position| selection.is_selected(*position))
+                .filter_map(|position| selection.item(position))
+                .filter_map(|item| item.downcast_ref::<Item>().and_then(Item::message).cloned())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn update_selection_actions(&self) {
+        let self_ = imp::ChatHistory::from_instance(self);
+        let messages = self.selected_messages();
+        let has_selection = !messages.is_empty();
+
+        self_.selection_header.set_n_selected(messages.len() as u32);
+
+        self.action_set_enabled("chat-history.copy-selected", has_selection);
+        self.action_set_enabled(
+            "chat-history.forward-selected",
+            has_selection && messages.iter().all(Message::can_be_forwarded),
+        );
+        self.action_set_enabled(
+            "chat-history.delete-selected",
+            has_selection
+                && messages.iter().all(|message| {
+                    message.can_be_deleted_only_for_self() || message.can_be_deleted_for_all_users()
+                }),
+        );
+        self.action_set_enabled(
+            "chat-history.save-selected-media",
+            messages
+                .iter()
+                .any(|message| media_file(&message.content().0).is_some()),
+        );
+    }
+
+    fn copy_selected_messages(&self) {
+        let text = self
+            .selected_messages()
+            .iter()
+            .map(|message| {
+                let sender_name = message
+                    .sender_name_expression()
+                    .evaluate(gtk::NONE_WIDGET)
+                    .and_then(|value| value.get::<String>().ok())
+                    .unwrap_or_default();
+
+                format!(
+                    "{}: {}",
+                    sender_name,
+                    message_content_preview(&message.content().0, false)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        self.clipboard().set_text(&text);
+        self.set_selection_mode(false);
+    }
+
+    /// Asks for a folder where to save the photos and the documents of the selected messages.
+    fn save_selected_media(&self) {
+        let self_ = imp::ChatHistory::from_instance(self);
+
+        let file_chooser = gtk::FileChooserNative::new(
+            Some(&gettext("Save Media")),
+            self.parent_window().as_ref(),
+            gtk::FileChooserAction::SelectFolder,
+            Some(&gettext("_Save")),
+            Some(&gettext("_Cancel")),
+        );
+        file_chooser.set_modal(true);
+
+        file_chooser.connect_response(clone!(@weak self as obj => move |file_chooser, response| {
+            if response == gtk::ResponseType::Accept {
+                if let Some(folder) = file_chooser.file() {
+                    let mut file_names = HashSet::new();
+                    for message in obj.selected_messages() {
+                        if let Some((file, file_name)) = media_file(&message.content().0) {
+                            let file_name = unique_file_name(&file_name, &mut file_names);
+                            save_file(&message, file, &folder.child(&file_name));
+                        }
+                    }
+
+                    obj.set_selection_mode(false);
+                }
+            }
+
+            let self_ = imp::ChatHistory::from_instance(&obj);
+            self_.file_chooser.replace(None);
+        }));
+
+        file_chooser.show();
+        self_.file_chooser.replace(Some(file_chooser));
+    }
+
+    fn scroll_to_message(&self, message_id: i64) {
+        if let Some(chat) = self.chat() {
+            let self_ = imp::ChatHistory::from_instance(self);
+
+            if let Some(position) = chat.history().message_position(message_id) {
+                self_
+                    .list_view
+                    .activate_action("list.scroll-to-item", Some(&position.to_variant()));
+            }
+        }
+    }
+
+    /// Loads the messages around the given message, if needed, and scrolls to it.
+    fn jump_to_message(&self, message_id: i64) {
+        if let Some(chat) = self.chat() {
+            chat.history().load_messages_around(
+                message_id,
+                clone!(@weak self as obj => move |history| {
+                    if obj.chat().as_ref() == Some(&history.chat()) {
+                        obj.scroll_to_message(message_id);
+                    }
+                }),
+            );
+        }
+    }
+
+    /// Shows the given message of the chat. If the chat is not the current one, the message
+    /// is shown when the chat is set.
+    pub fn show_message(&self, chat: &Chat, message_id: i64) {
+        if self.chat().as_ref() == Some(chat) {
+            self.jump_to_message(message_id);
+        } else {
+            let self_ = imp::ChatHistory::from_instance(self);
+            self_.initial_message_id.set(Some(message_id));
+        }
+    }
+
     fn open_info_dialog(&self) {
         if let Some(chat) = self.chat() {
             if let ChatType::Private(data) = chat.type_() {
@@ -157,20 +998,223 @@ impl ChatHistory {
         }
 
         let self_ = imp::ChatHistory::from_instance(self);
+
+        for (object, handler_id) in self_.chat_handlers.take() {
+            object.disconnect(handler_id);
+        }
+
+        // Let TDLib know which chat is opened, so that it sends the updates needed by it,
+        // like the online member count
+        if let Some(old_chat) = self.chat() {
+            let client_id = old_chat.session().client_id();
+            let chat_id = old_chat.id();
+            RUNTIME.spawn(async move {
+                functions::CloseChat::new()
+                    .chat_id(chat_id)
+                    .send(client_id)
+                    .await
+                    .unwrap();
+            });
+        }
+
         if let Some(ref chat) = chat {
+            let client_id = chat.session().client_id();
+            let chat_id = chat.id();
+            RUNTIME.spawn(async move {
+                functions::OpenChat::new()
+                    .chat_id(chat_id)
+                    .send(client_id)
+                    .await
+                    .unwrap();
+            });
+
+            // Update the subtitle when any of the information shown by it changes
+            let mut chat_handlers = vec![];
+            for property in &["action-description", "member-count", "online-member-count"] {
+                let handler_id = chat.connect_notify_local(
+                    Some(*property),
+                    clone!(@weak self as obj => move |_, _| {
+                        obj.update_subtitle();
+                    }),
+                );
+                chat_handlers.push((chat.clone().upcast(), handler_id));
+            }
+            if let Some(user) = interlocutor(chat) {
+                let handler_id = user.connect_notify_local(
+                    Some("status"),
+                    clone!(@weak self as obj => move |_, _| {
+                        obj.update_subtitle();
+                    }),
+                );
+                chat_handlers.push((user.upcast(), handler_id));
+            }
+
+            self_.chat_handlers.replace(chat_handlers);
+
+            chat.load_member_count();
+
             match chat.type_() {
                 ChatType::Private(_) => self.action_set_enabled("chat-history.view-info", true),
                 _ => self.action_set_enabled("chat-history.view-info", false),
             }
 
-            let selection = gtk::NoSelection::new(Some(&chat.history()));
+            let selection = gtk::MultiSelection::new(Some(&chat.history()));
+            selection.connect_selection_changed(clone!(@weak self as obj => move |_, _, _| {
+                obj.update_selection_actions();
+            }));
             self_.list_view.set_model(Some(&selection));
+            self_.selection.replace(Some(selection));
         }
 
+        self.set_selection_mode(false);
+
         self_.chat.replace(chat);
         self.notify("chat");
 
+        self.update_subtitle();
+
+        // Open the chat at the requested message or at the first unread message, if any
+        if let Some(chat) = self.chat() {
+            if let Some(message_id) = self_.initial_message_id.take() {
+                self.jump_to_message(message_id);
+            } else if chat.unread_count() > 0 && chat.last_read_inbox_message_id() != 0 {
+                self.scroll_to_unread_messages();
+            }
+        } else {
+            self_.initial_message_id.set(None);
+        }
+
         let adj = self_.list_view.vadjustment().unwrap();
         self.load_older_messages(&adj);
+        self.update_scroll_to_bottom_revealer(&adj);
+    }
+}
+
+/// Returns the file of a photo or document message and the name to save it with.
+fn media_file(content: &MessageContent) -> Option<(File, String)> {
+    match content {
+        MessageContent::MessagePhoto(data) => data
+            .photo
+            .sizes
+            .iter()
+            .max_by_key(|size| size.width * size.height)
+            .map(|size| (size.photo.clone(), format!("photo_{}.jpg", size.photo.id))),
+        MessageContent::MessageDocument(data) => Some((
+            data.document.document.clone(),
+            data.document.file_name.clone(),
+        )),
+        _ => None,
+    }
+}
+
+/// Returns the file name, numbered like `name (2).ext` if it has already been used.
+fn unique_file_name(file_name: &str, used_file_names: &mut HashSet<String>) -> String {
+    let path = Path::new(file_name);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut unique_file_name = file_name.to_owned();
+    let mut number = 1;
+    while used_file_names.contains(&unique_file_name) {
+        number += 1;
+        unique_file_name = format!("{} ({}){}", stem, number, extension);
+    }
+
+    used_file_names.insert(unique_file_name.clone());
+    unique_file_name
+}
+
+/// Copies the file to the destination, downloading it first if needed.
+fn save_file(message: &Message, file: File, destination: &gio::File) {
+    fn copy(file: &File, destination: &gio::File) {
+        let source = gio::File::for_path(&file.local.path);
+        source.copy_async(
+            destination,
+            gio::FileCopyFlags::OVERWRITE,
+            glib::PRIORITY_DEFAULT,
+            None::<&gio::Cancellable>,
+            None,
+            |result| {
+                if let Err(e) = result {
+                    log::warn!("Failed to save file: {}", e);
+                }
+            },
+        );
+    }
+
+    if file.local.is_downloading_completed {
+        copy(&file, destination);
+    } else {
+        let (sender, receiver) = glib::MainContext::channel::<File>(Default::default());
+        let destination = destination.clone();
+
+        receiver.attach(None, move |file| {
+            if file.local.is_downloading_completed {
+                copy(&file, &destination);
+            }
+
+            glib::Continue(file.local.is_downloading_active)
+        });
+
+        message.chat().session().download_file(file.id, sender);
+    }
+}
+
+/// Returns the other user of a private or secret chat.
+pub(super) fn interlocutor(chat: &Chat) -> Option<User> {
+    let user_id = match chat.type_() {
+        ChatType::Private(data) => data.user_id,
+        ChatType::Secret(data) => data.user_id,
+        _ => return None,
+    };
+
+    Some(chat.session().user_list().get_or_create_user(user_id))
+}
+
+/// Returns a description of the online status of the user, like "last seen 5 minutes ago".
+fn user_status_description(user: &User) -> String {
+    if let UserType::Bot(_) = user.type_().0 {
+        return gettext("bot");
+    }
+
+    match user.status().0 {
+        UserStatus::Online(_) => gettext("online"),
+        UserStatus::Offline(data) => {
+            let now = glib::DateTime::new_now_local().unwrap();
+            let was_online = glib::DateTime::from_unix_local(data.was_online as i64).unwrap();
+            let minutes = now.difference(&was_online) / 60000000;
+            let hours = minutes / 60;
+
+            if minutes < 1 {
+                gettext("last seen just now")
+            } else if hours < 1 {
+                ngettext!(
+                    "last seen {} minute ago",
+                    "last seen {} minutes ago",
+                    minutes as u32,
+                    minutes
+                )
+            } else if hours < 24 {
+                ngettext!(
+                    "last seen {} hour ago",
+                    "last seen {} hours ago",
+                    hours as u32,
+                    hours
+                )
+            } else {
+                let date = was_online.format("%x").unwrap();
+                gettext!("last seen on {}", date)
+            }
+        }
+        UserStatus::Recently => gettext("last seen recently"),
+        UserStatus::LastWeek => gettext("last seen within a week"),
+        UserStatus::LastMonth => gettext("last seen within a month"),
+        UserStatus::Empty => gettext("last seen a long time ago"),
     }
 }
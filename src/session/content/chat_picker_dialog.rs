@@ -0,0 +1,201 @@
+use glib::clone;
+use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+
+use crate::session::components::Avatar;
+use crate::session::{Chat, Session};
+
+mod imp {
+    use super::*;
+    use adw::subclass::prelude::AdwWindowImpl;
+    use glib::subclass::Signal;
+    use once_cell::sync::{Lazy, OnceCell};
+    use std::cell::RefCell;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-chat-picker-dialog.ui")]
+    pub struct ChatPickerDialog {
+        pub session: OnceCell<Session>,
+        pub filter: OnceCell<gtk::CustomFilter>,
+        pub searched_chats: RefCell<Vec<i64>>,
+        #[template_child]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child]
+        pub list_view: TemplateChild<gtk::ListView>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ChatPickerDialog {
+        const NAME: &'static str = "ContentChatPickerDialog";
+        type Type = super::ChatPickerDialog;
+        type ParentType = adw::Window;
+
+        fn class_init(klass: &mut Self::Class) {
+            Avatar::static_type();
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ChatPickerDialog {
+        fn properties() -> &'static [glib::ParamSpec] {
+            static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+                vec![glib::ParamSpec::new_object(
+                    "session",
+                    "Session",
+                    "The session of the chats shown by this dialog",
+                    Session::static_type(),
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::CONSTRUCT_ONLY,
+                )]
+            });
+            PROPERTIES.as_ref()
+        }
+
+        fn set_property(
+            &self,
+            _obj: &Self::Type,
+            _id: usize,
+            value: &glib::Value,
+            pspec: &glib::ParamSpec,
+        ) {
+            match pspec.name() {
+                "session" => self.session.set(value.get().unwrap()).unwrap(),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn property(&self, obj: &Self::Type, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            match pspec.name() {
+                "session" => obj.session().to_value(),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![Signal::builder(
+                    "chat-selected",
+                    &[Chat::static_type().into()],
+                    <()>::static_type().into(),
+                )
+                .build()]
+            });
+            SIGNALS.as_ref()
+        }
+
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            obj.setup_list_view();
+
+            self.search_entry
+                .connect_search_changed(clone!(@weak obj => move |entry| {
+                    let query = entry.text().to_string();
+                    obj.search(query);
+                }));
+        }
+    }
+
+    impl WidgetImpl for ChatPickerDialog {}
+    impl WindowImpl for ChatPickerDialog {}
+    impl AdwWindowImpl for ChatPickerDialog {}
+}
+
+glib::wrapper! {
+    pub struct ChatPickerDialog(ObjectSubclass<imp::ChatPickerDialog>)
+        @extends gtk::Widget, gtk::Window, adw::Window;
+}
+
+impl ChatPickerDialog {
+    pub fn new(parent_window: &Option<gtk::Window>, session: &Session) -> Self {
+        glib::Object::new(&[("transient-for", parent_window), ("session", session)])
+            .expect("Failed to create ChatPickerDialog")
+    }
+
+    fn setup_list_view(&self) {
+        let self_ = imp::ChatPickerDialog::from_instance(self);
+        let chat_list = self.session().chat_list();
+
+        let filter = gtk::CustomFilter::new(
+            clone!(@weak self as obj => @default-return false, move |item| {
+                let self_ = imp::ChatPickerDialog::from_instance(&obj);
+                let chat = item.downcast_ref::<Chat>().unwrap();
+
+                if self_.search_entry.text().is_empty() {
+                    chat.order() > 0
+                } else {
+                    self_.searched_chats.borrow().contains(&chat.id())
+                }
+            }),
+        );
+        let sorter = gtk::CustomSorter::new(move |obj1, obj2| {
+            let chat1 = obj1.downcast_ref::<Chat>().unwrap();
+            let chat2 = obj2.downcast_ref::<Chat>().unwrap();
+            chat2.order().cmp(&chat1.order()).into()
+        });
+
+        let filter_model = gtk::FilterListModel::new(Some(chat_list), Some(&filter));
+        let sort_model = gtk::SortListModel::new(Some(&filter_model), Some(&sorter));
+        let selection = gtk::NoSelection::new(Some(&sort_model));
+        self_.list_view.set_model(Some(&selection));
+
+        self_
+            .list_view
+            .connect_activate(clone!(@weak self as obj => move |list_view, position| {
+                let chat = list_view
+                    .model()
+                    .and_then(|model| model.item(position))
+                    .and_then(|item| item.downcast::<Chat>().ok());
+
+                if let Some(chat) = chat {
+                    obj.emit_by_name("chat-selected", &[&chat]).unwrap();
+                    obj.close();
+                }
+            }));
+
+        self_.filter.set(filter).unwrap();
+    }
+
+    fn search(&self, query: String) {
+        let self_ = imp::ChatPickerDialog::from_instance(self);
+        self_.searched_chats.borrow_mut().clear();
+
+        if query.is_empty() {
+            self_
+                .filter
+                .get()
+                .unwrap()
+                .changed(gtk::FilterChange::Different);
+        } else {
+            self.session().chat_list().search(
+                query,
+                clone!(@weak self as obj => move |chat_ids| {
+                    let self_ = imp::ChatPickerDialog::from_instance(&obj);
+                    self_.searched_chats.borrow_mut().extend(chat_ids);
+                    self_.filter.get().unwrap().changed(gtk::FilterChange::Different);
+                }),
+            );
+        }
+    }
+
+    pub fn session(&self) -> &Session {
+        let self_ = imp::ChatPickerDialog::from_instance(self);
+        self_.session.get().unwrap()
+    }
+
+    pub fn connect_chat_selected<F: Fn(&Self, Chat) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_local("chat-selected", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            let chat = values[1].get::<Chat>().unwrap();
+            f(&obj, chat);
+
+            None
+        })
+        .unwrap()
+    }
+}
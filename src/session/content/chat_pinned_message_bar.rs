@@ -0,0 +1,193 @@
+use gettextrs::gettext;
+use glib::clone;
+use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+
+use crate::session::Chat;
+use crate::utils::message_content_preview;
+
+mod imp {
+    use super::*;
+    use adw::subclass::prelude::BinImpl;
+    use once_cell::sync::Lazy;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-chat-pinned-message-bar.ui")]
+    pub struct ChatPinnedMessageBar {
+        pub chat: RefCell<Option<Chat>>,
+        pub pinned_messages_handler: RefCell<Option<glib::SignalHandlerId>>,
+        /// The index of the shown message in the pinned messages of the chat
+        pub index: Cell<usize>,
+        #[template_child]
+        pub revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub title_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub message_label: TemplateChild<gtk::Label>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ChatPinnedMessageBar {
+        const NAME: &'static str = "ContentChatPinnedMessageBar";
+        type Type = super::ChatPinnedMessageBar;
+        type ParentType = adw::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+
+            klass.install_action(
+                "chat-pinned-message-bar.show-message",
+                None,
+                move |widget, _, _| {
+                    widget.show_message();
+                },
+            );
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ChatPinnedMessageBar {
+        fn properties() -> &'static [glib::ParamSpec] {
+            static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+                vec![glib::ParamSpec::new_object(
+                    "chat",
+                    "Chat",
+                    "The chat to show the pinned messages of",
+                    Chat::static_type(),
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                )]
+            });
+
+            PROPERTIES.as_ref()
+        }
+
+        fn set_property(
+            &self,
+            obj: &Self::Type,
+            _id: usize,
+            value: &glib::Value,
+            pspec: &glib::ParamSpec,
+        ) {
+            match pspec.name() {
+                "chat" => obj.set_chat(value.get().unwrap()),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn property(&self, obj: &Self::Type, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            match pspec.name() {
+                "chat" => obj.chat().to_value(),
+                _ => unimplemented!(),
+            }
+        }
+    }
+
+    impl WidgetImpl for ChatPinnedMessageBar {}
+    impl BinImpl for ChatPinnedMessageBar {}
+}
+
+glib::wrapper! {
+    pub struct ChatPinnedMessageBar(ObjectSubclass<imp::ChatPinnedMessageBar>)
+        @extends gtk::Widget, adw::Bin;
+}
+
+impl Default for ChatPinnedMessageBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChatPinnedMessageBar {
+    pub fn new() -> Self {
+        glib::Object::new(&[]).expect("Failed to create ChatPinnedMessageBar")
+    }
+
+    /// Jumps to the shown pinned message and then shows the next older one, starting
+    /// again from the newest after the oldest.
+    fn show_message(&self) {
+        let self_ = imp::ChatPinnedMessageBar::from_instance(self);
+        let pinned_messages = match self.chat() {
+            Some(chat) => chat.pinned_messages(),
+            None => return,
+        };
+        let index = self_.index.get();
+
+        if let Some(message) = pinned_messages.get(index) {
+            self.activate_action(
+                "chat-history.scroll-to-message",
+                Some(&message.id().to_variant()),
+            );
+
+            self_.index.set((index + 1) % pinned_messages.len());
+            self.update();
+        }
+    }
+
+    fn update(&self) {
+        let self_ = imp::ChatPinnedMessageBar::from_instance(self);
+        let pinned_messages = self
+            .chat()
+            .map(|chat| chat.pinned_messages())
+            .unwrap_or_default();
+
+        if self_.index.get() >= pinned_messages.len() {
+            self_.index.set(0);
+        }
+
+        let index = self_.index.get();
+        match pinned_messages.get(index) {
+            Some(message) => {
+                let title = if pinned_messages.len() > 1 {
+                    gettext!("Pinned Message #{}", pinned_messages.len() - index)
+                } else {
+                    gettext("Pinned Message")
+                };
+                self_.title_label.set_label(&title);
+                self_
+                    .message_label
+                    .set_label(&message_content_preview(&message.content().0, false));
+                self_.revealer.set_reveal_child(true);
+            }
+            None => self_.revealer.set_reveal_child(false),
+        }
+    }
+
+    pub fn chat(&self) -> Option<Chat> {
+        let self_ = imp::ChatPinnedMessageBar::from_instance(self);
+        self_.chat.borrow().clone()
+    }
+
+    pub fn set_chat(&self, chat: Option<Chat>) {
+        if self.chat() == chat {
+            return;
+        }
+
+        let self_ = imp::ChatPinnedMessageBar::from_instance(self);
+
+        if let Some(handler_id) = self_.pinned_messages_handler.take() {
+            if let Some(old_chat) = self.chat() {
+                old_chat.disconnect(handler_id);
+            }
+        }
+
+        // The pinned messages are loaded only when the chat is opened
+        if let Some(ref chat) = chat {
+            let handler_id =
+                chat.connect_pinned_messages_changed(clone!(@weak self as obj => move |_| {
+                    obj.update();
+                }));
+            self_.pinned_messages_handler.replace(Some(handler_id));
+
+            chat.load_pinned_messages();
+        }
+
+        self_.chat.replace(chat);
+        self_.index.set(0);
+        self.update();
+
+        self.notify("chat");
+    }
+}
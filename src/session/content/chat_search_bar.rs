@@ -0,0 +1,467 @@
+use gettextrs::gettext;
+use glib::clone;
+use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use tdgrand::enums::{self, ChatType, MessageSender, SearchMessagesFilter};
+use tdgrand::functions;
+use tdgrand::types::MessageSenderUser;
+
+use crate::session::content::chat_history::interlocutor;
+use crate::session::{Chat, User};
+use crate::utils::do_async;
+
+const MAX_SENDERS: i32 = 50;
+
+mod imp {
+    use super::*;
+    use adw::subclass::prelude::BinImpl;
+    use once_cell::sync::Lazy;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-chat-search-bar.ui")]
+    pub struct ChatSearchBar {
+        pub chat: RefCell<Option<Chat>>,
+        pub search_id: Cell<u32>,
+        pub results: RefCell<Vec<i64>>,
+        pub total_count: Cell<i32>,
+        pub index: Cell<Option<usize>>,
+        /// The users that can be chosen in the sender filter, after "Anyone"
+        pub senders: RefCell<Vec<User>>,
+        #[template_child]
+        pub search_bar: TemplateChild<gtk::SearchBar>,
+        #[template_child]
+        pub entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child]
+        pub sender_drop_down: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        pub filter_drop_down: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        pub results_label: TemplateChild<gtk::Label>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ChatSearchBar {
+        const NAME: &'static str = "ContentChatSearchBar";
+        type Type = super::ChatSearchBar;
+        type ParentType = adw::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+
+            klass.install_action("chat-search-bar.previous", None, move |widget, _, _| {
+                widget.show_previous_result();
+            });
+            klass.install_action("chat-search-bar.next", None, move |widget, _, _| {
+                widget.show_next_result();
+            });
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ChatSearchBar {
+        fn properties() -> &'static [glib::ParamSpec] {
+            static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+                vec![
+                    glib::ParamSpec::new_object(
+                        "chat",
+                        "Chat",
+                        "The chat to search the messages of",
+                        Chat::static_type(),
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                    ),
+                    glib::ParamSpec::new_boolean(
+                        "search-mode",
+                        "Search Mode",
+                        "Whether the search bar is shown",
+                        false,
+                        glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                    ),
+                ]
+            });
+
+            PROPERTIES.as_ref()
+        }
+
+        fn set_property(
+            &self,
+            obj: &Self::Type,
+            _id: usize,
+            value: &glib::Value,
+            pspec: &glib::ParamSpec,
+        ) {
+            match pspec.name() {
+                "chat" => obj.set_chat(value.get().unwrap()),
+                "search-mode" => obj.set_search_mode(value.get().unwrap()),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn property(&self, obj: &Self::Type, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            match pspec.name() {
+                "chat" => obj.chat().to_value(),
+                "search-mode" => obj.search_mode().to_value(),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            self.search_bar.connect_entry(&*self.entry);
+            self.search_bar.connect_search_mode_enabled_notify(
+                clone!(@weak obj => move |search_bar| {
+                    if !search_bar.is_search_mode() {
+                        let self_ = imp::ChatSearchBar::from_instance(&obj);
+                        self_.entry.set_text("");
+                        self_.filter_drop_down.set_selected(0);
+                        self_.sender_drop_down.set_selected(0);
+                    }
+
+                    obj.notify("search-mode");
+                }),
+            );
+            self.entry
+                .connect_search_changed(clone!(@weak obj => move |_| {
+                    obj.search_messages(0);
+                }));
+            self.entry.connect_activate(clone!(@weak obj => move |_| {
+                obj.show_previous_result();
+            }));
+            self.sender_drop_down
+                .connect_selected_notify(clone!(@weak obj => move |_| {
+                    obj.search_messages(0);
+                }));
+            self.filter_drop_down
+                .connect_selected_notify(clone!(@weak obj => move |_| {
+                    obj.search_messages(0);
+                }));
+            obj.update_results();
+        }
+    }
+
+    impl WidgetImpl for ChatSearchBar {}
+    impl BinImpl for ChatSearchBar {}
+}
+
+glib::wrapper! {
+    pub struct ChatSearchBar(ObjectSubclass<imp::ChatSearchBar>)
+        @extends gtk::Widget, adw::Bin;
+}
+
+impl Default for ChatSearchBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChatSearchBar {
+    pub fn new() -> Self {
+        glib::Object::new(&[]).expect("Failed to create ChatSearchBar")
+    }
+
+    pub fn search_mode(&self) -> bool {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        self_.search_bar.is_search_mode()
+    }
+
+    pub fn set_search_mode(&self, search_mode: bool) {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        self_.search_bar.set_search_mode(search_mode);
+    }
+
+    /// Opens the search bar to search the given text in the chat.
+    pub fn search_text(&self, text: &str) {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        self.set_search_mode(true);
+        self_.entry.set_text(text);
+        self_.entry.grab_focus();
+    }
+
+    /// Searches the messages of the chat, starting from the given message, or from the last
+    /// message if `from_message_id` is 0, which also starts a new search.
+    fn search_messages(&self, from_message_id: i64) {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        let chat = match self.chat() {
+            Some(chat) => chat,
+            None => return,
+        };
+
+        if from_message_id == 0 {
+            self_.search_id.set(self_.search_id.get() + 1);
+            self_.results.borrow_mut().clear();
+            self_.total_count.set(0);
+            self_.index.set(None);
+        }
+
+        let query = self_.entry.text().to_string();
+        let filter = self.filter();
+        let sender = self.sender();
+
+        chat.history().set_search_query(&query);
+
+        if query.is_empty() && matches!(filter, SearchMessagesFilter::Empty) && sender.is_none() {
+            self.update_results();
+            return;
+        }
+
+        let client_id = chat.session().client_id();
+        let chat_id = chat.id();
+        let search_id = self_.search_id.get();
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                let mut request = functions::SearchChatMessages::new()
+                    .chat_id(chat_id)
+                    .query(query)
+                    .from_message_id(from_message_id)
+                    .limit(50)
+                    .filter(filter);
+                if let Some(sender) = sender {
+                    request = request.sender(sender);
+                }
+                request.send(client_id).await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                // Ignore the results of an outdated search
+                let self_ = imp::ChatSearchBar::from_instance(&obj);
+                if self_.search_id.get() != search_id {
+                    return;
+                }
+
+                if let Ok(enums::Messages::Messages(result)) = result {
+                    self_.total_count.set(result.total_count);
+                    self_.results.borrow_mut().extend(
+                        result
+                            .messages
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|message| message.id),
+                    );
+                }
+
+                // Show the first result of the new ones
+                let index = self_.index.get().map(|i| i + 1).unwrap_or_default();
+                obj.show_result(index);
+            }),
+        );
+    }
+
+    fn filter(&self) -> SearchMessagesFilter {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        match self_.filter_drop_down.selected() {
+            1 => SearchMessagesFilter::Photo,
+            2 => SearchMessagesFilter::Video,
+            3 => SearchMessagesFilter::Document,
+            4 => SearchMessagesFilter::Url,
+            5 => SearchMessagesFilter::VoiceNote,
+            6 => SearchMessagesFilter::Audio,
+            7 => SearchMessagesFilter::Animation,
+            _ => SearchMessagesFilter::Empty,
+        }
+    }
+
+    fn sender(&self) -> Option<MessageSender> {
+        let user_id = self.sender_user()?.id();
+
+        Some(MessageSender::User(MessageSenderUser {
+            user_id,
+            ..Default::default()
+        }))
+    }
+
+    fn sender_user(&self) -> Option<User> {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        let index = (self_.sender_drop_down.selected() as usize).checked_sub(1)?;
+        self_.senders.borrow().get(index).cloned()
+    }
+
+    /// Shows the older search result, loading more results if needed.
+    fn show_previous_result(&self) {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        let index = match self_.index.get() {
+            Some(index) => index + 1,
+            None => return,
+        };
+
+        let results = self_.results.borrow();
+        if index < results.len() {
+            drop(results);
+            self.show_result(index);
+        } else if (results.len() as i32) < self_.total_count.get() {
+            let from_message_id = *results.last().unwrap();
+            drop(results);
+            self.search_messages(from_message_id);
+        }
+    }
+
+    /// Shows the newer search result.
+    fn show_next_result(&self) {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        if let Some(index) = self_.index.get() {
+            if index > 0 {
+                self.show_result(index - 1);
+            }
+        }
+    }
+
+    fn show_result(&self, index: usize) {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        let message_id = match self_.results.borrow().get(index) {
+            Some(message_id) => *message_id,
+            None => {
+                self.update_results();
+                return;
+            }
+        };
+
+        self_.index.set(Some(index));
+        self.update_results();
+
+        self.activate_action(
+            "chat-history.scroll-to-message",
+            Some(&message_id.to_variant()),
+        );
+    }
+
+    fn update_results(&self) {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        let total_count = self_.total_count.get();
+
+        match self_.index.get() {
+            Some(index) => {
+                self_
+                    .results_label
+                    .set_label(&gettext!("{} of {}", index + 1, total_count));
+                self.action_set_enabled(
+                    "chat-search-bar.previous",
+                    (index as i32) + 1 < total_count,
+                );
+                self.action_set_enabled("chat-search-bar.next", index > 0);
+            }
+            None => {
+                let searching = !self_.entry.text().is_empty()
+                    || self_.filter_drop_down.selected() > 0
+                    || self_.sender_drop_down.selected() > 0;
+
+                self_.results_label.set_label(&if searching {
+                    gettext("No results")
+                } else {
+                    String::new()
+                });
+                self.action_set_enabled("chat-search-bar.previous", false);
+                self.action_set_enabled("chat-search-bar.next", false);
+            }
+        }
+    }
+
+    fn update_senders(&self) {
+        let chat = match self.chat() {
+            Some(chat) => chat,
+            None => {
+                self.set_senders(Vec::new());
+                return;
+            }
+        };
+        let me = chat.session().me();
+        let mut senders: Vec<User> = me.iter().cloned().collect();
+        senders.extend(interlocutor(&chat));
+        self.set_senders(senders.clone());
+
+        // Let the user choose among the members of groups too
+        if !matches!(
+            chat.type_(),
+            ChatType::BasicGroup(_) | ChatType::Supergroup(_)
+        ) {
+            return;
+        }
+
+        let client_id = chat.session().client_id();
+        let chat_id = chat.id();
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::SearchChatMembers::new()
+                    .chat_id(chat_id)
+                    .limit(MAX_SENDERS)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj, @weak chat => move |result| async move {
+                // Ignore the result if the chat changed in the meantime
+                if obj.chat().as_ref() != Some(&chat) {
+                    return;
+                }
+
+                if let Ok(enums::ChatMembers::ChatMembers(data)) = result {
+                    for member in data.members {
+                        if let MessageSender::User(data) = member.member_id {
+                            let user = chat.session().user_list().get_or_create_user(data.user_id);
+                            if Some(&user) != me.as_ref() {
+                                senders.push(user);
+                            }
+                        }
+                    }
+                    obj.set_senders(senders);
+                }
+            }),
+        );
+    }
+
+    fn set_senders(&self, senders: Vec<User>) {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        let me = self.chat().and_then(|chat| chat.session().me());
+        let mut names = vec![gettext("Anyone")];
+
+        for user in &senders {
+            if Some(user) == me.as_ref() {
+                names.push(gettext("Me"));
+            } else if user.last_name().is_empty() {
+                names.push(user.first_name());
+            } else {
+                names.push(format!("{} {}", user.first_name(), user.last_name()));
+            }
+        }
+
+        // Keep the selected sender, if any, when the list is updated
+        let selected = self
+            .sender_user()
+            .and_then(|user| senders.iter().position(|u| *u == user))
+            .map_or(0, |index| index as u32 + 1);
+
+        self_.senders.replace(senders);
+
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        self_
+            .sender_drop_down
+            .set_model(Some(&gtk::StringList::new(&names)));
+        self_.sender_drop_down.set_selected(selected);
+    }
+
+    pub fn chat(&self) -> Option<Chat> {
+        let self_ = imp::ChatSearchBar::from_instance(self);
+        self_.chat.borrow().clone()
+    }
+
+    pub fn set_chat(&self, chat: Option<Chat>) {
+        if self.chat() == chat {
+            return;
+        }
+
+        let self_ = imp::ChatSearchBar::from_instance(self);
+
+        // Close the search of the previous chat
+        self.set_search_mode(false);
+        if let Some(old_chat) = self.chat() {
+            old_chat.history().set_search_query("");
+        }
+
+        self_.chat.replace(chat);
+        self.notify("chat");
+
+        self.update_senders();
+    }
+}
@@ -0,0 +1,123 @@
+use gettextrs::{gettext, ngettext};
+use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+
+mod imp {
+    use super::*;
+    use adw::subclass::prelude::BinImpl;
+    use once_cell::sync::Lazy;
+    use std::cell::Cell;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-chat-selection-header.ui")]
+    pub struct ChatSelectionHeader {
+        pub n_selected: Cell<u32>,
+        #[template_child]
+        pub title: TemplateChild<adw::WindowTitle>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ChatSelectionHeader {
+        const NAME: &'static str = "ContentChatSelectionHeader";
+        type Type = super::ChatSelectionHeader;
+        type ParentType = adw::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for ChatSelectionHeader {
+        fn properties() -> &'static [glib::ParamSpec] {
+            static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+                vec![glib::ParamSpec::new_uint(
+                    "n-selected",
+                    "Number of selected",
+                    "The number of the selected messages",
+                    0,
+                    u32::MAX,
+                    0,
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                )]
+            });
+
+            PROPERTIES.as_ref()
+        }
+
+        fn set_property(
+            &self,
+            obj: &Self::Type,
+            _id: usize,
+            value: &glib::Value,
+            pspec: &glib::ParamSpec,
+        ) {
+            match pspec.name() {
+                "n-selected" => obj.set_n_selected(value.get().unwrap()),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn property(&self, obj: &Self::Type, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            match pspec.name() {
+                "n-selected" => obj.n_selected().to_value(),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+            obj.update_title();
+        }
+    }
+
+    impl WidgetImpl for ChatSelectionHeader {}
+    impl BinImpl for ChatSelectionHeader {}
+}
+
+glib::wrapper! {
+    pub struct ChatSelectionHeader(ObjectSubclass<imp::ChatSelectionHeader>)
+        @extends gtk::Widget, adw::Bin;
+}
+
+impl Default for ChatSelectionHeader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChatSelectionHeader {
+    pub fn new() -> Self {
+        glib::Object::new(&[]).expect("Failed to create ChatSelectionHeader")
+    }
+
+    pub fn n_selected(&self) -> u32 {
+        let self_ = imp::ChatSelectionHeader::from_instance(self);
+        self_.n_selected.get()
+    }
+
+    pub fn set_n_selected(&self, n_selected: u32) {
+        if self.n_selected() == n_selected {
+            return;
+        }
+
+        let self_ = imp::ChatSelectionHeader::from_instance(self);
+        self_.n_selected.set(n_selected);
+
+        self.update_title();
+        self.notify("n-selected");
+    }
+
+    fn update_title(&self) {
+        let self_ = imp::ChatSelectionHeader::from_instance(self);
+        let n_selected = self.n_selected();
+
+        self_.title.set_title(&if n_selected > 0 {
+            ngettext!("{} Selected", "{} Selected", n_selected, n_selected)
+        } else {
+            gettext("Select Messages")
+        });
+    }
+}
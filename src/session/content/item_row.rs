@@ -1,20 +1,24 @@
 use adw::{prelude::BinExt, subclass::prelude::BinImpl};
 use gettextrs::gettext;
-use gtk::{glib, prelude::*, subclass::prelude::*};
-use tdgrand::enums::{ChatType, MessageContent};
+use glib::clone;
+use gtk::{gdk, gio, glib, prelude::*, subclass::prelude::*};
+use tdgrand::enums::MessageContent;
 
-use crate::session::chat::{Item, ItemType};
+use crate::session::chat::{Item, ItemType, Message};
 use crate::session::content::message_row::MessageRow;
-use crate::session::content::EventRow;
+use crate::session::content::{ChatHistory, EventRow};
+use crate::session::User;
+use crate::utils::escape;
 
 mod imp {
     use super::*;
-    use once_cell::sync::Lazy;
+    use once_cell::sync::{Lazy, OnceCell};
     use std::cell::RefCell;
 
     #[derive(Debug, Default)]
     pub struct ItemRow {
         pub item: RefCell<Option<Item>>,
+        pub menu: OnceCell<gtk::PopoverMenu>,
     }
 
     #[glib::object_subclass]
@@ -22,6 +26,30 @@ mod imp {
         const NAME: &'static str = "ContentItemRow";
         type Type = super::ItemRow;
         type ParentType = adw::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.install_action("item-row.select", None, move |widget, _, _| {
+                widget.select();
+            });
+            klass.install_action("item-row.reply", None, move |widget, _, _| {
+                widget.reply();
+            });
+            klass.install_action("item-row.forward", None, move |widget, _, _| {
+                widget.forward();
+            });
+            klass.install_action("item-row.edit", None, move |widget, _, _| {
+                widget.edit();
+            });
+            klass.install_action("item-row.pin", None, move |widget, _, _| {
+                widget.pin();
+            });
+            klass.install_action("item-row.unpin", None, move |widget, _, _| {
+                widget.unpin();
+            });
+            klass.install_action("item-row.delete", None, move |widget, _, _| {
+                widget.delete();
+            });
+        }
     }
 
     impl ObjectImpl for ItemRow {
@@ -58,9 +86,66 @@ mod imp {
                 _ => unimplemented!(),
             }
         }
+
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            // Toggle the selection of the message with a click while in selection mode
+            let selection_gesture = gtk::GestureClick::new();
+            selection_gesture.set_propagation_phase(gtk::PropagationPhase::Capture);
+            selection_gesture.connect_pressed(clone!(@weak obj => move |gesture, _, _, _| {
+                if let Some(chat_history) = obj.chat_history() {
+                    if !chat_history.selection_mode() {
+                        return;
+                    }
+
+                    if let Some(message) = obj.item().as_ref().and_then(Item::message) {
+                        let extend = gesture
+                            .current_event_state()
+                            .contains(gdk::ModifierType::SHIFT_MASK);
+                        chat_history.toggle_message_selection(message.id(), extend);
+                        gesture.set_state(gtk::EventSequenceState::Claimed);
+                    }
+                }
+            }));
+            obj.add_controller(&selection_gesture);
+
+            // Open the message menu with a right click or with a long press on touchscreens
+            let click_gesture = gtk::GestureClick::new();
+            click_gesture.set_button(gdk::BUTTON_SECONDARY as u32);
+            click_gesture.connect_pressed(clone!(@weak obj => move |gesture, _, x, y| {
+                if obj.open_menu(x, y) {
+                    gesture.set_state(gtk::EventSequenceState::Claimed);
+                }
+            }));
+            obj.add_controller(&click_gesture);
+
+            let long_press_gesture = gtk::GestureLongPress::new();
+            long_press_gesture.set_touch_only(true);
+            long_press_gesture.connect_pressed(clone!(@weak obj => move |gesture, x, y| {
+                if obj.open_menu(x, y) {
+                    gesture.set_state(gtk::EventSequenceState::Claimed);
+                }
+            }));
+            obj.add_controller(&long_press_gesture);
+        }
+
+        fn dispose(&self, _obj: &Self::Type) {
+            if let Some(menu) = self.menu.get() {
+                menu.unparent();
+            }
+        }
     }
 
-    impl WidgetImpl for ItemRow {}
+    impl WidgetImpl for ItemRow {
+        fn size_allocate(&self, widget: &Self::Type, width: i32, height: i32, baseline: i32) {
+            self.parent_size_allocate(widget, width, height, baseline);
+
+            if let Some(menu) = self.menu.get() {
+                menu.present();
+            }
+        }
+    }
     impl BinImpl for ItemRow {}
 }
 
@@ -88,35 +173,11 @@ impl ItemRow {
     fn set_item(&self, item: Option<Item>) {
         if let Some(ref item) = item {
             match item.type_() {
-                ItemType::Message(message) => match message.content().0 {
-                    MessageContent::MessageChatDeletePhoto => {
-                        let is_outgoing = message.is_outgoing();
-                        let is_channel = if let ChatType::Supergroup(data) = message.chat().type_()
-                        {
-                            data.is_channel
-                        } else {
-                            false
-                        };
-
-                        let sender_name_expression = message.sender_name_expression();
-                        let label_expression = gtk::ClosureExpression::new(
-                            move |args| {
-                                if is_channel {
-                                    gettext("Channel photo removed")
-                                } else if is_outgoing {
-                                    gettext("You removed the group photo")
-                                } else {
-                                    let name = args[1].get::<&str>().unwrap();
-                                    gettext!("{} removed the group photo", name)
-                                }
-                            },
-                            &[sender_name_expression],
-                        );
-
+                ItemType::Message(message) => {
+                    if let Some(label_expression) = event_label_expression(message) {
                         let child = self.get_or_create_event_row();
                         label_expression.bind(&child, "label", Some(&child));
-                    }
-                    _ => {
+                    } else {
                         let child = if let Some(Ok(child)) =
                             self.child().map(|w| w.downcast::<MessageRow>())
                         {
@@ -129,7 +190,7 @@ impl ItemRow {
 
                         child.set_message(message);
                     }
-                },
+                }
                 ItemType::DayDivider(date) => {
                     let fmt = if date.year() == glib::DateTime::new_now_local().unwrap().year() {
                         // Translators: This is a date format in the day divider without the year
@@ -143,6 +204,10 @@ impl ItemRow {
                     let child = self.get_or_create_event_row();
                     child.set_label(&date);
                 }
+                ItemType::UnreadDivider(_) => {
+                    let child = self.get_or_create_event_row();
+                    child.set_label(&format!("<b>{}</b>", gettext("Unread Messages")));
+                }
             }
         }
 
@@ -150,6 +215,93 @@ impl ItemRow {
         self_.item.replace(item);
     }
 
+    /// Opens the menu of the message shown by this row, if any. Returns whether the menu
+    /// has been opened.
+    fn open_menu(&self, x: f64, y: f64) -> bool {
+        if !matches!(self.child().map(|w| w.is::<MessageRow>()), Some(true)) {
+            return false;
+        }
+
+        if let Some(message) = self.item().as_ref().and_then(Item::message) {
+            let can_be_edited = message.can_be_edited()
+                && matches!(
+                    message.content().0,
+                    MessageContent::MessageText(_)
+                        | MessageContent::MessagePhoto(_)
+                        | MessageContent::MessageDocument(_)
+                );
+            self.action_set_enabled("item-row.forward", message.can_be_forwarded());
+            self.action_set_enabled("item-row.edit", can_be_edited);
+            let can_be_pinned = message.sending_state().0.is_none();
+            self.action_set_enabled("item-row.pin", can_be_pinned && !message.is_pinned());
+            self.action_set_enabled("item-row.unpin", message.is_pinned());
+            self.action_set_enabled(
+                "item-row.delete",
+                message.can_be_deleted_only_for_self() || message.can_be_deleted_for_all_users(),
+            );
+        }
+
+        let self_ = imp::ItemRow::from_instance(self);
+        let menu = self_.menu.get_or_init(|| {
+            let menu = gtk::PopoverMenu::from_model(Some(&message_menu_model()));
+            menu.set_parent(self);
+            menu.set_has_arrow(false);
+            menu.set_halign(gtk::Align::Start);
+            menu
+        });
+
+        menu.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 0, 0)));
+        menu.popup();
+
+        true
+    }
+
+    fn chat_history(&self) -> Option<ChatHistory> {
+        self.ancestor(ChatHistory::static_type())?.downcast().ok()
+    }
+
+    fn select(&self) {
+        if let Some(message) = self.item().as_ref().and_then(Item::message) {
+            self.activate_action("chat-history.select", Some(&message.id().to_variant()));
+        }
+    }
+
+    fn reply(&self) {
+        if let Some(message) = self.item().as_ref().and_then(Item::message) {
+            self.activate_action("chat-history.reply", Some(&message.id().to_variant()));
+        }
+    }
+
+    fn forward(&self) {
+        if let Some(message) = self.item().as_ref().and_then(Item::message) {
+            self.activate_action("chat-history.forward", Some(&message.id().to_variant()));
+        }
+    }
+
+    fn edit(&self) {
+        if let Some(message) = self.item().as_ref().and_then(Item::message) {
+            self.activate_action("chat-history.edit", Some(&message.id().to_variant()));
+        }
+    }
+
+    fn pin(&self) {
+        if let Some(message) = self.item().as_ref().and_then(Item::message) {
+            self.activate_action("chat-history.pin", Some(&message.id().to_variant()));
+        }
+    }
+
+    fn unpin(&self) {
+        if let Some(message) = self.item().as_ref().and_then(Item::message) {
+            self.activate_action("chat-history.unpin", Some(&message.id().to_variant()));
+        }
+    }
+
+    fn delete(&self) {
+        if let Some(message) = self.item().as_ref().and_then(Item::message) {
+            self.activate_action("chat-history.delete", Some(&message.id().to_variant()));
+        }
+    }
+
     fn get_or_create_event_row(&self) -> EventRow {
         if let Some(Ok(child)) = self.child().map(|w| w.downcast::<EventRow>()) {
             child
@@ -160,3 +312,53 @@ impl ItemRow {
         }
     }
 }
+
+fn message_menu_model() -> gio::Menu {
+    let menu = gio::Menu::new();
+    menu.append(Some(&gettext("_Reply")), Some("item-row.reply"));
+    menu.append(Some(&gettext("_Forward")), Some("item-row.forward"));
+    menu.append(Some(&gettext("_Edit")), Some("item-row.edit"));
+
+    // Only show the pin or the unpin item, depending on the message
+    for (label, action) in &[
+        (gettext("_Pin"), "item-row.pin"),
+        (gettext("_Unpin"), "item-row.unpin"),
+    ] {
+        let item = gio::MenuItem::new(Some(label), Some(action));
+        item.set_attribute_value("hidden-when", Some(&"action-disabled".to_variant()));
+        menu.append_item(&item);
+    }
+
+    menu.append(Some(&gettext("_Delete")), Some("item-row.delete"));
+    menu.append(Some(&gettext("_Select")), Some("item-row.select"));
+    menu
+}
+
+/// Returns an expression of the text of the given message if it's a service message, like
+/// "Alice joined the group", or `None` if it's a normal message.
+fn event_label_expression(message: &Message) -> Option<gtk::Expression> {
+    // Check that it's a service message before creating the expressions
+    message.event_text("", &[])?;
+
+    let session = message.chat().session();
+    let mut expressions = vec![message.sender_name_expression()];
+    expressions.extend(message.event_user_ids().into_iter().map(|user_id| {
+        let user = session.user_list().get_or_create_user(user_id);
+        let user_expression = gtk::ConstantExpression::new(&user);
+        User::full_name_expression(&user_expression)
+    }));
+
+    let expression = gtk::ClosureExpression::new(
+        clone!(@weak message => @default-return String::new(), move |args| {
+            let mut names = args[1..]
+                .iter()
+                .map(|arg| escape(arg.get::<&str>().unwrap()));
+            let sender_name = names.next().unwrap();
+            let user_names: Vec<String> = names.collect();
+            message.event_text(&sender_name, &user_names).unwrap_or_default()
+        }),
+        &expressions,
+    );
+
+    Some(expression.upcast())
+}
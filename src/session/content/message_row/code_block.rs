@@ -0,0 +1,131 @@
+use glib::clone;
+use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use sourceview::prelude::*;
+
+mod imp {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-message-code-block.ui")]
+    pub struct MessageCodeBlock {
+        pub dark_handler_id: RefCell<Option<glib::SignalHandlerId>>,
+        #[template_child]
+        pub language_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub source_view: TemplateChild<sourceview::View>,
+        #[template_child]
+        pub buffer: TemplateChild<sourceview::Buffer>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for MessageCodeBlock {
+        const NAME: &'static str = "ContentMessageCodeBlock";
+        type Type = super::MessageCodeBlock;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            sourceview::View::static_type();
+            Self::bind_template(klass);
+
+            klass.install_action("code-block.copy", None, move |widget, _, _| {
+                widget.copy_code();
+            });
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for MessageCodeBlock {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            // Follow the dark style of the app with the colors of the code
+            if let Some(style_manager) = adw::StyleManager::default() {
+                let handler_id = style_manager.connect_dark_notify(clone!(@weak obj => move |_| {
+                    obj.update_style_scheme();
+                }));
+                self.dark_handler_id.replace(Some(handler_id));
+            }
+            obj.update_style_scheme();
+        }
+
+        fn dispose(&self, obj: &Self::Type) {
+            if let Some(handler_id) = self.dark_handler_id.take() {
+                if let Some(style_manager) = adw::StyleManager::default() {
+                    style_manager.disconnect(handler_id);
+                }
+            }
+
+            while let Some(child) = obj.first_child() {
+                child.unparent();
+            }
+        }
+    }
+
+    impl WidgetImpl for MessageCodeBlock {}
+}
+
+glib::wrapper! {
+    /// A block of code of a message, highlighted according to its language.
+    pub struct MessageCodeBlock(ObjectSubclass<imp::MessageCodeBlock>)
+        @extends gtk::Widget;
+}
+
+impl MessageCodeBlock {
+    pub fn new(text: &str, language: &str) -> Self {
+        let code_block: Self = glib::Object::new(&[]).expect("Failed to create MessageCodeBlock");
+        let self_ = imp::MessageCodeBlock::from_instance(&code_block);
+
+        self_.buffer.set_text(text);
+        self_.buffer.set_highlight_syntax(true);
+        self_
+            .buffer
+            .set_language(source_language(language).as_ref());
+
+        self_.language_label.set_label(language);
+
+        code_block
+    }
+
+    fn update_style_scheme(&self) {
+        let self_ = imp::MessageCodeBlock::from_instance(self);
+        let is_dark = adw::StyleManager::default()
+            .map(|style_manager| style_manager.is_dark())
+            .unwrap_or_default();
+        let scheme_id = if is_dark { "Adwaita-dark" } else { "Adwaita" };
+
+        let scheme = sourceview::StyleSchemeManager::default().scheme(scheme_id);
+        self_.buffer.set_style_scheme(scheme.as_ref());
+    }
+
+    fn copy_code(&self) {
+        let self_ = imp::MessageCodeBlock::from_instance(self);
+        let (start, end) = self_.buffer.bounds();
+        let text = self_.buffer.text(&start, &end, false);
+
+        self.clipboard().set_text(&text);
+    }
+}
+
+/// Returns the language of GtkSourceView for the language declared in a message, which
+/// can also be one of its common short names.
+fn source_language(language: &str) -> Option<sourceview::Language> {
+    let language = language.to_lowercase();
+    let id = match language.as_str() {
+        "" => return None,
+        "bash" | "shell" | "zsh" => "sh",
+        "c++" => "cpp",
+        "c#" | "cs" | "csharp" => "c-sharp",
+        "javascript" => "js",
+        "py" => "python",
+        "rs" => "rust",
+        "ts" => "typescript",
+        "yml" => "yaml",
+        id => id,
+    };
+
+    sourceview::LanguageManager::default().language(id)
+}
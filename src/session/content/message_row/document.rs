@@ -0,0 +1,353 @@
+use gettextrs::gettext;
+use glib::clone;
+use gtk::{gdk, gio, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use tdgrand::enums::MessageContent;
+use tdgrand::types::File;
+
+use super::connect_entity_links;
+use crate::session::chat::{BoxedMessageContent, Message};
+use crate::utils::parse_formatted_text;
+
+mod imp {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-message-document.ui")]
+    pub struct MessageDocument {
+        pub message: RefCell<Option<Message>>,
+        pub file: RefCell<Option<File>>,
+        pub file_chooser: RefCell<Option<gtk::FileChooserNative>>,
+        #[template_child]
+        pub file_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub file_icon: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub name_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub size_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub action_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub menu_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub progress_bar: TemplateChild<gtk::ProgressBar>,
+        #[template_child]
+        pub caption_label: TemplateChild<gtk::Label>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for MessageDocument {
+        const NAME: &'static str = "ContentMessageDocument";
+        type Type = super::MessageDocument;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+
+            klass.install_action("message-document.open-with", None, move |widget, _, _| {
+                widget.open_with();
+            });
+            klass.install_action("message-document.save-as", None, move |widget, _, _| {
+                widget.save_as();
+            });
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for MessageDocument {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            connect_entity_links(&self.caption_label);
+            self.action_button
+                .connect_clicked(clone!(@weak obj => move |_| {
+                    obj.handle_action_button();
+                }));
+        }
+
+        fn dispose(&self, _obj: &Self::Type) {
+            self.file_box.unparent();
+            self.progress_bar.unparent();
+            self.caption_label.unparent();
+        }
+    }
+
+    impl WidgetImpl for MessageDocument {}
+}
+
+glib::wrapper! {
+    pub struct MessageDocument(ObjectSubclass<imp::MessageDocument>)
+        @extends gtk::Widget;
+}
+
+impl Default for MessageDocument {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageDocument {
+    pub fn new() -> Self {
+        glib::Object::new(&[]).expect("Failed to create MessageDocument")
+    }
+
+    pub fn set_message(&self, message: &Message) {
+        let self_ = imp::MessageDocument::from_instance(self);
+
+        if message.is_outgoing() {
+            self.add_css_class("outgoing");
+        } else {
+            self.remove_css_class("outgoing");
+        }
+
+        // Set caption label expression
+        let message_expression = gtk::ConstantExpression::new(message);
+        let content_expression = gtk::PropertyExpression::new(
+            Message::static_type(),
+            Some(&message_expression),
+            "content",
+        );
+        let caption_expression = gtk::ClosureExpression::new(
+            move |expressions| -> String {
+                let content = expressions[1].get::<BoxedMessageContent>().unwrap();
+                if let MessageContent::MessageDocument(data) = content.0 {
+                    parse_formatted_text(data.caption)
+                } else {
+                    String::new()
+                }
+            },
+            &[content_expression.upcast()],
+        );
+        let caption_label = self_.caption_label.get();
+        caption_expression.bind(&caption_label, "label", Some(&caption_label));
+
+        // Hide the caption label when the caption is empty
+        let caption_visible_expression = gtk::ClosureExpression::new(
+            |expressions| -> bool { !expressions[1].get::<&str>().unwrap().is_empty() },
+            &[caption_expression.upcast()],
+        );
+        caption_visible_expression.bind(&caption_label, "visible", Some(&caption_label));
+
+        self_.message.replace(Some(message.clone()));
+
+        if let MessageContent::MessageDocument(data) = message.content().0 {
+            let file_name = if data.document.file_name.is_empty() {
+                gettext("Unknown File")
+            } else {
+                data.document.file_name
+            };
+            self_.name_label.set_label(&file_name);
+            self_.name_label.set_tooltip_text(Some(&file_name));
+
+            let content_type = gio::content_type_from_mime_type(&data.document.mime_type)
+                .unwrap_or_else(|| "application/octet-stream".into());
+            let icon = gio::content_type_get_symbolic_icon(&content_type);
+            self_.file_icon.set_from_gicon(&icon);
+
+            let file = data.document.document;
+
+            // Keep listening for the progress of a download that is already active
+            if file.local.is_downloading_active {
+                self.download_file(&file);
+            }
+
+            self.update_file(file);
+        }
+    }
+
+    fn update_file(&self, file: File) {
+        let self_ = imp::MessageDocument::from_instance(self);
+        let local = &file.local;
+
+        let size = file.size.max(file.expected_size) as u64;
+
+        if local.is_downloading_completed {
+            self_.size_label.set_label(&glib::format_size(size));
+            self_.action_button.set_icon_name("document-open-symbolic");
+            self_.action_button.set_tooltip_text(Some(&gettext("Open")));
+            self_.progress_bar.set_visible(false);
+        } else if local.is_downloading_active {
+            self_.size_label.set_label(&format!(
+                "{} / {}",
+                glib::format_size(local.downloaded_size as u64),
+                glib::format_size(size)
+            ));
+            self_.action_button.set_icon_name("process-stop-symbolic");
+            self_
+                .action_button
+                .set_tooltip_text(Some(&gettext("Cancel Download")));
+
+            let fraction = if size > 0 {
+                local.downloaded_size as f64 / size as f64
+            } else {
+                0.0
+            };
+            self_.progress_bar.set_fraction(fraction);
+            self_.progress_bar.set_visible(true);
+        } else {
+            self_.size_label.set_label(&glib::format_size(size));
+            self_
+                .action_button
+                .set_icon_name("folder-download-symbolic");
+            self_
+                .action_button
+                .set_tooltip_text(Some(&gettext("Download")));
+            self_.progress_bar.set_visible(false);
+        }
+
+        self_.action_button.set_sensitive(
+            local.is_downloading_completed
+                || local.is_downloading_active
+                || local.can_be_downloaded,
+        );
+        self_
+            .menu_button
+            .set_visible(local.is_downloading_completed);
+
+        self_.file.replace(Some(file));
+    }
+
+    fn download_file(&self, file: &File) {
+        let self_ = imp::MessageDocument::from_instance(self);
+
+        let (sender, receiver) = glib::MainContext::channel::<File>(Default::default());
+
+        receiver.attach(
+            None,
+            clone!(@weak self as obj => @default-return glib::Continue(false), move |file| {
+                let self_ = imp::MessageDocument::from_instance(&obj);
+
+                // Check that the widget is still showing the same file
+                if self_.file.borrow().as_ref().map(|f| f.id) != Some(file.id) {
+                    return glib::Continue(false);
+                }
+
+                let is_downloading_active = file.local.is_downloading_active;
+                obj.update_file(file);
+
+                glib::Continue(is_downloading_active)
+            }),
+        );
+
+        if let Some(message) = self_.message.borrow().as_ref() {
+            message.chat().session().download_file(file.id, sender);
+        }
+    }
+
+    fn handle_action_button(&self) {
+        let self_ = imp::MessageDocument::from_instance(self);
+
+        let file = match self_.file.borrow().clone() {
+            Some(file) => file,
+            None => return,
+        };
+
+        if file.local.is_downloading_completed {
+            self.open();
+        } else if file.local.is_downloading_active {
+            if let Some(message) = self_.message.borrow().as_ref() {
+                message.chat().session().cancel_download_file(file.id);
+            }
+        } else {
+            self.download_file(&file);
+        }
+    }
+
+    fn downloaded_file(&self) -> Option<gio::File> {
+        let self_ = imp::MessageDocument::from_instance(self);
+        self_
+            .file
+            .borrow()
+            .as_ref()
+            .filter(|file| file.local.is_downloading_completed)
+            .map(|file| gio::File::for_path(&file.local.path))
+    }
+
+    fn parent_window(&self) -> Option<gtk::Window> {
+        self.root()?.downcast().ok()
+    }
+
+    fn open(&self) {
+        if let Some(file) = self.downloaded_file() {
+            gtk::show_uri(
+                self.parent_window().as_ref(),
+                &file.uri(),
+                gdk::CURRENT_TIME,
+            );
+        }
+    }
+
+    fn open_with(&self) {
+        if let Some(file) = self.downloaded_file() {
+            let dialog = gtk::AppChooserDialog::new(
+                self.parent_window().as_ref(),
+                gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+                &file,
+            );
+
+            dialog.connect_response(move |dialog, response| {
+                if response == gtk::ResponseType::Ok {
+                    if let Some(app_info) = dialog.app_info() {
+                        if let Err(e) =
+                            app_info.launch(&[file.clone()], None::<&gio::AppLaunchContext>)
+                        {
+                            log::warn!("Failed to open file: {}", e);
+                        }
+                    }
+                }
+
+                dialog.close();
+            });
+
+            dialog.present();
+        }
+    }
+
+    fn save_as(&self) {
+        let self_ = imp::MessageDocument::from_instance(self);
+
+        let source = match self.downloaded_file() {
+            Some(source) => source,
+            None => return,
+        };
+
+        let file_chooser = gtk::FileChooserNative::new(
+            Some(&gettext("Save File")),
+            self.parent_window().as_ref(),
+            gtk::FileChooserAction::Save,
+            Some(&gettext("_Save")),
+            Some(&gettext("_Cancel")),
+        );
+        file_chooser.set_modal(true);
+        file_chooser.set_current_name(&self_.name_label.label());
+
+        file_chooser.connect_response(clone!(@weak self as obj => move |file_chooser, response| {
+            if response == gtk::ResponseType::Accept {
+                if let Some(destination) = file_chooser.file() {
+                    source.copy_async(
+                        &destination,
+                        gio::FileCopyFlags::OVERWRITE,
+                        glib::PRIORITY_DEFAULT,
+                        None::<&gio::Cancellable>,
+                        None,
+                        |result| {
+                            if let Err(e) = result {
+                                log::warn!("Failed to save file: {}", e);
+                            }
+                        },
+                    );
+                }
+            }
+
+            let self_ = imp::MessageDocument::from_instance(&obj);
+            self_.file_chooser.replace(None);
+        }));
+
+        file_chooser.show();
+        self_.file_chooser.replace(Some(file_chooser));
+    }
+}
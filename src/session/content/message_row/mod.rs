@@ -1,26 +1,64 @@
+mod code_block;
+mod document;
+mod photo;
 mod sticker;
 mod text;
+mod video;
 
+use self::code_block::MessageCodeBlock;
+use self::document::MessageDocument;
+use self::photo::MessagePhoto;
 use self::sticker::MessageSticker;
 use self::text::MessageText;
+use self::video::MessageVideo;
 
 use adw::prelude::BinExt;
+use gettextrs::gettext;
+use glib::{clone, signal::Inhibit};
 use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
-use tdgrand::enums::{ChatType, MessageContent};
+use tdgrand::enums::{self, ChatType, InputMessageContent, MessageContent, MessageSendingState};
+use tdgrand::functions;
+use tdgrand::types::{self, File};
 
-use crate::session::chat::{Message, MessageSender};
+use crate::session::chat::{BoxedMessageContent, Message, MessageSender};
 use crate::session::components::Avatar;
+use crate::session::content::UserDialog;
+use crate::session::{remove_temporary_file, Chat, Session, User};
+use crate::utils::{do_async, message_content_preview, INTERNAL_URI_PREFIX};
+use crate::RUNTIME;
 
 mod imp {
     use super::*;
+    use std::cell::{Cell, RefCell};
 
     #[derive(Debug, Default, CompositeTemplate)]
     #[template(resource = "/com/github/melix99/telegrand/ui/content-message-row.ui")]
     pub struct MessageRow {
+        pub message: RefCell<Option<Message>>,
+        pub uploading_file_id: Cell<i32>,
+        pub read_state_watches: RefCell<Vec<gtk::ExpressionWatch>>,
         #[template_child]
         pub avatar_bin: TemplateChild<adw::Bin>,
         #[template_child]
+        pub content_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub forward_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub reply_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub reply_sender_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub reply_message_label: TemplateChild<gtk::Label>,
+        #[template_child]
         pub content_bin: TemplateChild<adw::Bin>,
+        #[template_child]
+        pub upload_progress_bar: TemplateChild<gtk::ProgressBar>,
+        #[template_child]
+        pub edited_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub status_image: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub failed_button: TemplateChild<gtk::MenuButton>,
     }
 
     #[glib::object_subclass]
@@ -31,6 +69,13 @@ mod imp {
 
         fn class_init(klass: &mut Self::Class) {
             Self::bind_template(klass);
+
+            klass.install_action("message-row.retry", None, move |widget, _, _| {
+                widget.retry();
+            });
+            klass.install_action("message-row.delete", None, move |widget, _, _| {
+                widget.delete();
+            });
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -39,9 +84,28 @@ mod imp {
     }
 
     impl ObjectImpl for MessageRow {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            // Jump to the replied message when clicking on the reply
+            let click_gesture = gtk::GestureClick::new();
+            click_gesture.connect_released(clone!(@weak obj => move |_, _, _, _| {
+                if let Some(message) = obj.message() {
+                    obj.activate_action(
+                        "chat-history.scroll-to-message",
+                        Some(&message.reply_to_message_id().to_variant()),
+                    );
+                }
+            }));
+            self.reply_box.add_controller(&click_gesture);
+        }
+
         fn dispose(&self, _obj: &Self::Type) {
             self.avatar_bin.unparent();
-            self.content_bin.unparent();
+            self.content_box.unparent();
+            self.edited_label.unparent();
+            self.status_image.unparent();
+            self.failed_button.unparent();
         }
     }
 
@@ -64,6 +128,107 @@ impl MessageRow {
         glib::Object::new(&[]).expect("Failed to create MessageRow")
     }
 
+    pub fn message(&self) -> Option<Message> {
+        let self_ = imp::MessageRow::from_instance(self);
+        self_.message.borrow().clone()
+    }
+
+    /// Handles the links of the entities that are handled by the app, returning whether
+    /// the link was handled.
+    fn activate_entity_link(&self, uri: &str) -> bool {
+        let (kind, value) = match uri
+            .strip_prefix(INTERNAL_URI_PREFIX)
+            .and_then(|path| path.split_once('/'))
+        {
+            Some((kind, value)) => match glib::Uri::unescape_string(value, None) {
+                Some(value) => (kind, value.to_string()),
+                None => return false,
+            },
+            None => return false,
+        };
+        let chat = match self.message() {
+            Some(message) => message.chat(),
+            None => return true,
+        };
+        let session = chat.session();
+
+        match kind {
+            "mention" => self.open_public_chat(&session, value),
+            "user" => {
+                if let Ok(user_id) = value.parse() {
+                    self.show_user(&session.user_list().get_or_create_user(user_id));
+                }
+            }
+            "hashtag" => self.activate_action(
+                "chat-history.search-text",
+                Some(&format!("#{}", value).to_variant()),
+            ),
+            "cashtag" => self.activate_action(
+                "chat-history.search-text",
+                Some(&format!("${}", value).to_variant()),
+            ),
+            "command" => {
+                let content = InputMessageContent::InputMessageText(types::InputMessageText {
+                    text: types::FormattedText {
+                        text: format!("/{}", value),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+                let client_id = session.client_id();
+                let chat_id = chat.id();
+
+                RUNTIME.spawn(async move {
+                    functions::SendMessage::new()
+                        .chat_id(chat_id)
+                        .input_message_content(content)
+                        .send(client_id)
+                        .await
+                        .unwrap();
+                });
+            }
+            _ => log::warn!("Unknown internal link: {}", uri),
+        }
+
+        true
+    }
+
+    /// Opens the chat with the given username, or shows the user if it's a private chat.
+    fn open_public_chat(&self, session: &Session, username: String) {
+        let client_id = session.client_id();
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::SearchPublicChat::new()
+                    .username(username)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj, @weak session => move |result| async move {
+                match result {
+                    Ok(enums::Chat::Chat(data)) => {
+                        if let Some(chat) = session.chat_list().get_chat(data.id) {
+                            if let ChatType::Private(data) = chat.type_() {
+                                let user = session.user_list().get_or_create_user(data.user_id);
+                                obj.show_user(&user);
+                            } else {
+                                session.select_chat(&chat);
+                            }
+                        }
+                    }
+                    Err(e) => log::warn!("Failed to find the public chat: {:?}", e),
+                }
+            }),
+        );
+    }
+
+    fn show_user(&self, user: &User) {
+        let parent_window = self.root().and_then(|root| root.downcast().ok());
+        let dialog = UserDialog::new(&parent_window, user);
+        dialog.show();
+    }
+
     pub fn set_message(&self, message: &Message) {
         let self_ = imp::MessageRow::from_instance(self);
 
@@ -112,37 +277,389 @@ impl MessageRow {
             self_.avatar_bin.set_visible(false);
         }
 
+        self_.message.replace(Some(message.clone()));
+        self.update_sending_state(message);
+        self.update_reply(message);
+
+        // Show the original sender, if the message is forwarded
+        match message.forward_sender_name_expression() {
+            Some(forward_sender_name_expression) => {
+                let forward_expression = gtk::ClosureExpression::new(
+                    move |expressions| -> String {
+                        let name = expressions[1].get::<&str>().unwrap();
+                        gettext!("Forwarded from {}", name)
+                    },
+                    &[forward_sender_name_expression],
+                );
+                forward_expression.bind(&*self_.forward_label, "label", gtk::NONE_WIDGET);
+                self_.forward_label.set_visible(true);
+            }
+            None => self_.forward_label.set_visible(false),
+        }
+
+        // Show the edited marker, if the message has been edited
+        let message_expression = gtk::ConstantExpression::new(message);
+        let edit_date_expression = gtk::PropertyExpression::new(
+            Message::static_type(),
+            Some(&message_expression),
+            "edit-date",
+        );
+        let is_edited_expression = gtk::ClosureExpression::new(
+            move |expressions| -> bool { expressions[1].get::<i32>().unwrap() > 0 },
+            &[edit_date_expression.upcast()],
+        );
+        is_edited_expression.bind(&*self_.edited_label, "visible", gtk::NONE_WIDGET);
+
         // Show content widget
         match message.content().0 {
-            // TODO: Support animated stickers
-            MessageContent::MessageSticker(data) if !data.sticker.is_animated => {
-                let content = if let Some(Ok(content)) = self_
-                    .content_bin
-                    .child()
-                    .map(|w| w.downcast::<MessageSticker>())
-                {
-                    content
-                } else {
-                    let content = MessageSticker::new();
-                    self_.content_bin.set_child(Some(&content));
-                    content
-                };
-                content.set_message(message);
+            MessageContent::MessageDocument(_) => {
+                self.content_widget::<MessageDocument>()
+                    .set_message(message);
+            }
+            MessageContent::MessagePhoto(_) => {
+                self.content_widget::<MessagePhoto>().set_message(message);
+            }
+            MessageContent::MessageAnimation(_) | MessageContent::MessageVideo(_) => {
+                self.content_widget::<MessageVideo>().set_message(message);
+            }
+            MessageContent::MessageSticker(_) => {
+                self.content_widget::<MessageSticker>().set_message(message);
             }
             _ => {
-                let content = if let Some(Ok(content)) = self_
-                    .content_bin
-                    .child()
-                    .map(|w| w.downcast::<MessageText>())
-                {
-                    content
-                } else {
-                    let content = MessageText::new();
-                    self_.content_bin.set_child(Some(&content));
-                    content
-                };
-                content.set_message(message);
+                self.content_widget::<MessageText>().set_message(message);
             }
         }
     }
+
+    /// Returns the content widget of the given type, replacing the current one if it's
+    /// of a different type.
+    fn content_widget<T: IsA<gtk::Widget> + Default>(&self) -> T {
+        let self_ = imp::MessageRow::from_instance(self);
+
+        if let Some(Ok(content)) = self_.content_bin.child().map(|w| w.downcast::<T>()) {
+            return content;
+        }
+
+        let content = T::default();
+        self_.content_bin.set_child(Some(&content));
+        content
+    }
+
+    fn update_reply(&self, message: &Message) {
+        let self_ = imp::MessageRow::from_instance(self);
+        let reply_to_message_id = message.reply_to_message_id();
+
+        if reply_to_message_id == 0 {
+            self_.reply_box.set_visible(false);
+            return;
+        }
+
+        self_.reply_box.set_visible(true);
+
+        let chat = message.chat();
+        match chat.history().message_by_id(reply_to_message_id) {
+            Some(reply_to_message) => self.set_reply_to_message(Some(&reply_to_message)),
+            None => {
+                // The replied message is not loaded in the history, so we need to fetch it
+                self.set_reply_to_message(None);
+
+                let client_id = chat.session().client_id();
+                let chat_id = chat.id();
+                let chat = chat.clone();
+                let message = message.clone();
+
+                do_async(
+                    glib::PRIORITY_DEFAULT_IDLE,
+                    async move {
+                        functions::GetMessage::new()
+                            .chat_id(chat_id)
+                            .message_id(reply_to_message_id)
+                            .send(client_id)
+                            .await
+                    },
+                    clone!(@weak self as obj => move |result| async move {
+                        // Check that this widget is still showing the same message
+                        let self_ = imp::MessageRow::from_instance(&obj);
+                        if self_.message.borrow().as_ref() != Some(&message) {
+                            return;
+                        }
+
+                        match result {
+                            Ok(enums::Message::Message(reply_to_message)) => {
+                                let reply_to_message = Message::new(reply_to_message, &chat);
+                                obj.set_reply_to_message(Some(&reply_to_message));
+                            }
+                            Err(_) => {
+                                let deleted_expression =
+                                    gtk::ConstantExpression::new(&gettext("Deleted message"));
+                                deleted_expression.bind(
+                                    &*self_.reply_message_label,
+                                    "label",
+                                    gtk::NONE_WIDGET,
+                                );
+                            }
+                        }
+                    }),
+                );
+            }
+        }
+    }
+
+    /// Shows the sender and the content of the replied message, or empty labels while it's
+    /// still being fetched.
+    fn set_reply_to_message(&self, reply_to_message: Option<&Message>) {
+        let self_ = imp::MessageRow::from_instance(self);
+
+        match reply_to_message {
+            Some(reply_to_message) => {
+                let sender_name_expression = reply_to_message.sender_name_expression();
+                sender_name_expression.bind(&*self_.reply_sender_label, "label", gtk::NONE_WIDGET);
+
+                let message_expression = gtk::ConstantExpression::new(reply_to_message);
+                let content_expression = gtk::PropertyExpression::new(
+                    Message::static_type(),
+                    Some(&message_expression),
+                    "content",
+                );
+                let preview_expression = gtk::ClosureExpression::new(
+                    move |expressions| -> String {
+                        let content = expressions[1].get::<BoxedMessageContent>().unwrap();
+                        message_content_preview(&content.0, false)
+                    },
+                    &[content_expression.upcast()],
+                );
+                preview_expression.bind(&*self_.reply_message_label, "label", gtk::NONE_WIDGET);
+            }
+            None => {
+                let empty_expression = gtk::ConstantExpression::new(&"");
+                empty_expression.bind(&*self_.reply_sender_label, "label", gtk::NONE_WIDGET);
+                empty_expression.bind(&*self_.reply_message_label, "label", gtk::NONE_WIDGET);
+            }
+        }
+    }
+
+    fn update_sending_state(&self, message: &Message) {
+        let self_ = imp::MessageRow::from_instance(self);
+
+        self_.status_image.set_visible(false);
+        self_.failed_button.set_visible(false);
+        self_.upload_progress_bar.set_visible(false);
+        self_.uploading_file_id.set(0);
+
+        for watch in self_.read_state_watches.take() {
+            watch.unwatch();
+        }
+
+        if !message.is_outgoing() {
+            return;
+        }
+
+        match message.sending_state().0 {
+            Some(MessageSendingState::Pending) => {
+                self_
+                    .status_image
+                    .set_icon_name(Some("document-open-recent-symbolic"));
+                self_
+                    .status_image
+                    .set_tooltip_text(Some(&gettext("Sending")));
+                self_.status_image.set_visible(true);
+
+                if let Some(file) = uploading_file(&message.content().0) {
+                    self.watch_upload(message, file);
+                }
+            }
+            Some(MessageSendingState::Failed(data)) => {
+                self_
+                    .failed_button
+                    .set_tooltip_text(Some(&data.error_message));
+                self_.failed_button.set_visible(true);
+                self.action_set_enabled("message-row.retry", data.can_retry);
+            }
+            None => {
+                // Show whether the message has been read, based on the last read outgoing
+                // message of the chat
+                let message_id = message.id();
+                let chat_expression = gtk::ConstantExpression::new(&message.chat());
+                let last_read_outbox_message_id_expression = gtk::PropertyExpression::new(
+                    Chat::static_type(),
+                    Some(&chat_expression),
+                    "last-read-outbox-message-id",
+                );
+                let icon_name_expression = gtk::ClosureExpression::new(
+                    move |args| -> String {
+                        let last_read_outbox_message_id = args[1].get::<i64>().unwrap();
+                        if last_read_outbox_message_id >= message_id {
+                            "message-read-symbolic".to_string()
+                        } else {
+                            "message-sent-symbolic".to_string()
+                        }
+                    },
+                    &[last_read_outbox_message_id_expression.clone().upcast()],
+                );
+                let tooltip_expression = gtk::ClosureExpression::new(
+                    move |args| -> String {
+                        let last_read_outbox_message_id = args[1].get::<i64>().unwrap();
+                        if last_read_outbox_message_id >= message_id {
+                            gettext("Read")
+                        } else {
+                            gettext("Sent")
+                        }
+                    },
+                    &[last_read_outbox_message_id_expression.upcast()],
+                );
+
+                self_.read_state_watches.replace(vec![
+                    icon_name_expression.bind(&*self_.status_image, "icon-name", gtk::NONE_WIDGET),
+                    tooltip_expression.bind(&*self_.status_image, "tooltip-text", gtk::NONE_WIDGET),
+                ]);
+                self_.status_image.set_visible(true);
+            }
+        }
+    }
+
+    fn watch_upload(&self, message: &Message, file: File) {
+        let self_ = imp::MessageRow::from_instance(self);
+
+        self_.uploading_file_id.set(file.id);
+        self.update_upload_progress(&file);
+
+        if file.remote.is_uploading_completed {
+            return;
+        }
+
+        let (sender, receiver) = glib::MainContext::channel::<File>(Default::default());
+
+        receiver.attach(
+            None,
+            clone!(@weak self as obj, @weak message => @default-return glib::Continue(false), move |file| {
+                let self_ = imp::MessageRow::from_instance(&obj);
+
+                // Check that the row is still showing the same upload
+                if self_.uploading_file_id.get() != file.id {
+                    return glib::Continue(false);
+                }
+
+                obj.update_upload_progress(&file);
+
+                // The upload may not have started yet, so keep watching it until it
+                // completes or the message is not being sent anymore
+                let is_pending = matches!(
+                    message.sending_state().0,
+                    Some(MessageSendingState::Pending)
+                );
+                glib::Continue(is_pending && !file.remote.is_uploading_completed)
+            }),
+        );
+
+        message.chat().session().watch_file_upload(file.id, sender);
+    }
+
+    fn update_upload_progress(&self, file: &File) {
+        let self_ = imp::MessageRow::from_instance(self);
+        let size = file.size.max(file.expected_size);
+
+        if file.remote.is_uploading_active && size > 0 {
+            self_
+                .upload_progress_bar
+                .set_fraction(file.remote.uploaded_size as f64 / size as f64);
+            self_.upload_progress_bar.set_visible(true);
+        } else {
+            self_.upload_progress_bar.set_visible(false);
+        }
+    }
+
+    fn retry(&self) {
+        let self_ = imp::MessageRow::from_instance(self);
+
+        if let Some(message) = self_.message.borrow().as_ref() {
+            let session = message.chat().session();
+            let client_id = session.client_id();
+            let chat_id = message.chat().id();
+            let message_id = message.id();
+
+            // The failed message is replaced by a new one, so move its temporary file, if
+            // any, to the new message, before the old one gets deleted
+            let temporary_file = session.take_temporary_file(chat_id, message_id);
+
+            do_async(
+                glib::PRIORITY_DEFAULT_IDLE,
+                async move {
+                    functions::ResendMessages::new()
+                        .chat_id(chat_id)
+                        .message_ids(vec![message_id])
+                        .send(client_id)
+                        .await
+                },
+                move |result| async move {
+                    let path = match temporary_file {
+                        Some(path) => path,
+                        None => return,
+                    };
+
+                    match result {
+                        Ok(enums::Messages::Messages(data)) => {
+                            match data.messages.unwrap_or_default().into_iter().next() {
+                                Some(message) => {
+                                    session.add_temporary_file(chat_id, message.id, path)
+                                }
+                                None => remove_temporary_file(&path),
+                            }
+                        }
+                        Err(e) => {
+                            log::warn!("Failed to resend the message: {:?}", e);
+                            session.add_temporary_file(chat_id, message_id, path);
+                        }
+                    }
+                },
+            );
+        }
+    }
+
+    fn delete(&self) {
+        let self_ = imp::MessageRow::from_instance(self);
+
+        if let Some(message) = self_.message.borrow().as_ref() {
+            let client_id = message.chat().session().client_id();
+            let chat_id = message.chat().id();
+            let message_id = message.id();
+
+            RUNTIME.spawn(async move {
+                functions::DeleteMessages::new()
+                    .chat_id(chat_id)
+                    .message_ids(vec![message_id])
+                    .revoke(true)
+                    .send(client_id)
+                    .await
+                    .unwrap();
+            });
+        }
+    }
+}
+
+/// Makes the links of the entities of a label in a message, like mentions, handled by the
+/// app instead of being opened by the system.
+pub fn connect_entity_links(label: &gtk::Label) {
+    label.connect_activate_link(|label, uri| {
+        let handled = label
+            .ancestor(MessageRow::static_type())
+            .and_then(|row| row.downcast::<MessageRow>().ok())
+            .map(|row| row.activate_entity_link(uri))
+            .unwrap_or_default();
+
+        Inhibit(handled)
+    });
+}
+
+/// Returns the file that is uploaded when sending a message with the given content, if any.
+fn uploading_file(content: &MessageContent) -> Option<File> {
+    match content {
+        MessageContent::MessagePhoto(data) => data
+            .photo
+            .sizes
+            .iter()
+            .max_by_key(|size| size.width * size.height)
+            .map(|size| size.photo.clone()),
+        MessageContent::MessageDocument(data) => Some(data.document.document.clone()),
+        _ => None,
+    }
 }
@@ -0,0 +1,211 @@
+use glib::clone;
+use gtk::{gdk, gdk_pixbuf, gio, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use tdgrand::enums::MessageContent;
+use tdgrand::types::{File, Minithumbnail, PhotoSize};
+
+use super::connect_entity_links;
+use crate::session::chat::{BoxedMessageContent, Message};
+use crate::utils::parse_formatted_text;
+
+// The maximum size of the photo in the chat history
+const MAX_SIZE: i32 = 300;
+
+mod imp {
+    use super::*;
+    use std::cell::Cell;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-message-photo.ui")]
+    pub struct MessagePhoto {
+        pub file_id: Cell<i32>,
+        #[template_child]
+        pub picture: TemplateChild<gtk::Picture>,
+        #[template_child]
+        pub caption_label: TemplateChild<gtk::Label>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for MessagePhoto {
+        const NAME: &'static str = "ContentMessagePhoto";
+        type Type = super::MessagePhoto;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for MessagePhoto {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+            connect_entity_links(&self.caption_label);
+        }
+
+        fn dispose(&self, _obj: &Self::Type) {
+            self.picture.unparent();
+            self.caption_label.unparent();
+        }
+    }
+
+    impl WidgetImpl for MessagePhoto {}
+}
+
+glib::wrapper! {
+    pub struct MessagePhoto(ObjectSubclass<imp::MessagePhoto>)
+        @extends gtk::Widget;
+}
+
+impl Default for MessagePhoto {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessagePhoto {
+    pub fn new() -> Self {
+        glib::Object::new(&[]).expect("Failed to create MessagePhoto")
+    }
+
+    pub fn set_message(&self, message: &Message) {
+        let self_ = imp::MessagePhoto::from_instance(self);
+
+        if message.is_outgoing() {
+            self.add_css_class("outgoing");
+        } else {
+            self.remove_css_class("outgoing");
+        }
+
+        // Set caption label expression
+        let message_expression = gtk::ConstantExpression::new(message);
+        let content_expression = gtk::PropertyExpression::new(
+            Message::static_type(),
+            Some(&message_expression),
+            "content",
+        );
+        let caption_expression = gtk::ClosureExpression::new(
+            move |expressions| -> String {
+                let content = expressions[1].get::<BoxedMessageContent>().unwrap();
+                if let MessageContent::MessagePhoto(data) = content.0 {
+                    parse_formatted_text(data.caption)
+                } else {
+                    String::new()
+                }
+            },
+            &[content_expression.upcast()],
+        );
+        let caption_label = self_.caption_label.get();
+        caption_expression.bind(&caption_label, "label", Some(&caption_label));
+
+        // Hide the caption label when the caption is empty
+        let caption_visible_expression = gtk::ClosureExpression::new(
+            |expressions| -> bool { !expressions[1].get::<&str>().unwrap().is_empty() },
+            &[caption_expression.upcast()],
+        );
+        caption_visible_expression.bind(&caption_label, "visible", Some(&caption_label));
+
+        if let MessageContent::MessagePhoto(data) = message.content().0 {
+            let photo_size = match choose_photo_size(&data.photo.sizes) {
+                Some(photo_size) => photo_size,
+                None => {
+                    self_.file_id.set(0);
+                    self_.picture.set_paintable(None as Option<&gdk::Paintable>);
+                    return;
+                }
+            };
+
+            // Scale the photo to fit it in the chat history, but keeping its
+            // original aspect ratio
+            let (width, height) = scale_to_fit(photo_size.width, photo_size.height);
+            self_.picture.set_size_request(width, height);
+
+            let file = photo_size.photo.clone();
+            self_.file_id.set(file.id);
+
+            if file.local.is_downloading_completed {
+                self.load_photo(&file.local.path);
+            } else {
+                // Show a blurred preview of the photo while the photo is being downloaded
+                self.load_minithumbnail(data.photo.minithumbnail.as_ref());
+
+                let (sender, receiver) = glib::MainContext::channel::<File>(Default::default());
+
+                receiver.attach(
+                    None,
+                    clone!(@weak self as obj => @default-return glib::Continue(false), move |file| {
+                        let self_ = imp::MessagePhoto::from_instance(&obj);
+
+                        // Check that the widget is still showing the same photo
+                        if self_.file_id.get() != file.id {
+                            return glib::Continue(false);
+                        }
+
+                        if file.local.is_downloading_completed {
+                            obj.load_photo(&file.local.path);
+                            return glib::Continue(false);
+                        }
+
+                        glib::Continue(true)
+                    }),
+                );
+
+                message.chat().session().download_file(file.id, sender);
+            }
+        }
+    }
+
+    fn load_minithumbnail(&self, minithumbnail: Option<&Minithumbnail>) {
+        let self_ = imp::MessagePhoto::from_instance(self);
+        let texture = minithumbnail.and_then(minithumbnail_texture);
+
+        self_.picture.set_paintable(texture.as_ref());
+        self_.picture.add_css_class("blurred");
+    }
+
+    fn load_photo(&self, path: &str) {
+        let self_ = imp::MessagePhoto::from_instance(self);
+        let file = gio::File::for_path(path);
+
+        match gdk::Texture::from_file(&file) {
+            Ok(texture) => {
+                self_.picture.set_paintable(Some(&texture));
+                self_.picture.remove_css_class("blurred");
+            }
+            Err(e) => log::warn!("Failed to load photo: {}", e),
+        }
+    }
+}
+
+/// Chooses the smallest photo size that is big enough to be shown in the chat
+/// history, falling back to the biggest one available.
+fn choose_photo_size(sizes: &[PhotoSize]) -> Option<&PhotoSize> {
+    sizes
+        .iter()
+        .filter(|size| size.width >= MAX_SIZE || size.height >= MAX_SIZE)
+        .min_by_key(|size| size.width * size.height)
+        .or_else(|| sizes.iter().max_by_key(|size| size.width * size.height))
+}
+
+/// Decodes the blurred preview of a photo or video.
+pub fn minithumbnail_texture(minithumbnail: &Minithumbnail) -> Option<gdk::Texture> {
+    let data = glib::base64_decode(&minithumbnail.data);
+    let loader = gdk_pixbuf::PixbufLoader::new();
+    loader.write(&data).ok()?;
+    loader.close().ok()?;
+    loader
+        .pixbuf()
+        .map(|pixbuf| gdk::Texture::for_pixbuf(&pixbuf))
+}
+
+pub fn scale_to_fit(width: i32, height: i32) -> (i32, i32) {
+    if width <= 0 || height <= 0 {
+        (MAX_SIZE, MAX_SIZE)
+    } else if width > height {
+        (MAX_SIZE, height * MAX_SIZE / width)
+    } else {
+        (width * MAX_SIZE / height, MAX_SIZE)
+    }
+}
@@ -1,18 +1,23 @@
 use glib::clone;
-use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use gtk::{gdk, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
 use tdgrand::{enums::MessageContent, types::File};
 
 use crate::session::chat::Message;
+use crate::session::components::LottieAnimation;
+use crate::session::content::StickerSetDialog;
 
 mod imp {
     use super::*;
-    use std::cell::Cell;
+    use std::cell::{Cell, RefCell};
 
     #[derive(Debug, Default, CompositeTemplate)]
     #[template(resource = "/com/github/melix99/telegrand/ui/content-message-sticker.ui")]
     pub struct MessageSticker {
+        pub message: RefCell<Option<Message>>,
         pub width: Cell<i32>,
         pub height: Cell<i32>,
+        pub is_animated: Cell<bool>,
+        pub animation: RefCell<Option<LottieAnimation>>,
         #[template_child]
         pub picture: TemplateChild<gtk::Picture>,
     }
@@ -33,12 +38,40 @@ mod imp {
     }
 
     impl ObjectImpl for MessageSticker {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            // Show the set of the sticker when it's clicked
+            let click = gtk::GestureClick::new();
+            click.connect_released(clone!(@weak obj => move |_, _, _, _| {
+                obj.show_sticker_set();
+            }));
+            obj.add_controller(&click);
+        }
+
         fn dispose(&self, _obj: &Self::Type) {
             self.picture.unparent();
         }
     }
 
     impl WidgetImpl for MessageSticker {
+        // Only play animated stickers while they're shown
+        fn map(&self, widget: &Self::Type) {
+            self.parent_map(widget);
+
+            if let Some(animation) = self.animation.borrow().as_ref() {
+                animation.set_playing(true);
+            }
+        }
+
+        fn unmap(&self, widget: &Self::Type) {
+            self.parent_unmap(widget);
+
+            if let Some(animation) = self.animation.borrow().as_ref() {
+                animation.set_playing(false);
+            }
+        }
+
         fn measure(
             &self,
             _widget: &Self::Type,
@@ -79,6 +112,14 @@ impl MessageSticker {
     pub fn set_message(&self, message: &Message) {
         if let MessageContent::MessageSticker(data) = message.content().0 {
             let self_ = imp::MessageSticker::from_instance(self);
+            self_.is_animated.set(data.sticker.is_animated);
+            self_.message.replace(Some(message.clone()));
+
+            // Stop the animation of the previous sticker
+            if let Some(animation) = self_.animation.take() {
+                animation.set_playing(false);
+            }
+            self_.picture.set_paintable(gdk::NONE_PAINTABLE);
 
             // Scale the sticker to fit it in a small container, but keeping its
             // original aspect ratio
@@ -98,8 +139,7 @@ impl MessageSticker {
             if data.sticker.sticker.local.is_downloading_completed {
                 self.load_sticker(&data.sticker.sticker.local.path);
             } else {
-                let (sender, receiver) =
-                    glib::MainContext::sync_channel::<File>(Default::default(), 5);
+                let (sender, receiver) = glib::MainContext::channel::<File>(Default::default());
 
                 receiver.attach(
                     None,
@@ -120,9 +160,44 @@ impl MessageSticker {
         }
     }
 
+    fn show_sticker_set(&self) {
+        let self_ = imp::MessageSticker::from_instance(self);
+
+        if let Some(message) = self_.message.borrow().as_ref() {
+            if let MessageContent::MessageSticker(data) = message.content().0 {
+                // Stickers that aren't part of a set have no set id
+                if data.sticker.set_id == 0 {
+                    return;
+                }
+
+                let parent_window = self.root().and_then(|root| root.downcast().ok());
+                let dialog = StickerSetDialog::new(
+                    &parent_window,
+                    &message.chat().session(),
+                    data.sticker.set_id,
+                );
+                dialog.present();
+            }
+        }
+    }
+
     fn load_sticker(&self, path: &str) {
         let self_ = imp::MessageSticker::from_instance(self);
-        let media = gtk::MediaFile::for_filename(path);
-        self_.picture.set_paintable(Some(&media));
+
+        if self_.is_animated.get() {
+            let scale_factor = self.scale_factor();
+            if let Some(animation) = LottieAnimation::from_file(
+                path,
+                self_.width.get() * scale_factor,
+                self_.height.get() * scale_factor,
+            ) {
+                animation.set_playing(self.is_mapped());
+                self_.picture.set_paintable(Some(&animation));
+                self_.animation.replace(Some(animation));
+            }
+        } else {
+            let media = gtk::MediaFile::for_filename(path);
+            self_.picture.set_paintable(Some(&media));
+        }
     }
 }
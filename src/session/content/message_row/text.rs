@@ -1,9 +1,15 @@
 use gettextrs::gettext;
-use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use glib::clone;
+use gtk::{glib, pango, prelude::*, subclass::prelude::*, CompositeTemplate};
 use tdgrand::enums::{ChatType, MessageContent};
+use tdgrand::types::FormattedText;
 
-use crate::session::chat::{BoxedMessageContent, Message, MessageSender};
-use crate::utils::parse_formatted_text;
+use super::{connect_entity_links, MessageCodeBlock};
+use crate::session::chat::{BoxedMessageContent, History, Message, MessageSender};
+use crate::session::components::WebPagePreview;
+use crate::utils::{
+    highlight_attributes, parse_formatted_text, split_code_blocks, FormattedTextBlock,
+};
 
 mod imp {
     use super::*;
@@ -13,10 +19,16 @@ mod imp {
     #[template(resource = "/com/github/melix99/telegrand/ui/content-message-text.ui")]
     pub struct MessageText {
         pub sender_color_class: RefCell<Option<String>>,
+        pub message: RefCell<Option<Message>>,
+        pub content_handler_id: RefCell<Option<glib::SignalHandlerId>>,
         #[template_child]
         pub sender_label: TemplateChild<gtk::Label>,
         #[template_child]
         pub content_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub blocks_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub web_page_preview: TemplateChild<WebPagePreview>,
     }
 
     #[glib::object_subclass]
@@ -26,6 +38,7 @@ mod imp {
         type ParentType = gtk::Widget;
 
         fn class_init(klass: &mut Self::Class) {
+            WebPagePreview::static_type();
             Self::bind_template(klass);
         }
 
@@ -35,9 +48,17 @@ mod imp {
     }
 
     impl ObjectImpl for MessageText {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            connect_entity_links(&self.content_label);
+        }
+
         fn dispose(&self, _obj: &Self::Type) {
             self.sender_label.unparent();
             self.content_label.unparent();
+            self.blocks_box.unparent();
+            self.web_page_preview.unparent();
         }
     }
 
@@ -114,6 +135,24 @@ impl MessageText {
             self_.sender_label.set_visible(false);
         }
 
+        // Show the blocks of code separately from the rest of the text and the preview
+        // of the link, if any
+        if let Some(handler_id) = self_.content_handler_id.take() {
+            if let Some(old_message) = self_.message.borrow().as_ref() {
+                old_message.disconnect(handler_id);
+            }
+        }
+        let handler_id =
+            message.connect_content_notify(clone!(@weak self as obj => move |message, _| {
+                obj.update_blocks(message);
+                obj.update_web_page(message);
+            }));
+        self_.content_handler_id.replace(Some(handler_id));
+        self.update_blocks(message);
+        self.update_web_page(message);
+
+        self_.message.replace(Some(message.clone()));
+
         // Set content label expression
         let message_expression = gtk::ConstantExpression::new(message);
         let content_expression = gtk::PropertyExpression::new(
@@ -126,11 +165,104 @@ impl MessageText {
                 let content = expressions[1].get::<BoxedMessageContent>().unwrap();
                 format_message_content_text(content.0)
             },
-            &[content_expression.upcast()],
+            &[content_expression.clone().upcast()],
         );
         let content_label = self_.content_label.get();
         text_expression.bind(&content_label, "label", Some(&content_label));
+
+        // Highlight the text searched in the chat
+        let history_expression = gtk::ConstantExpression::new(&message.chat().history());
+        let search_query_expression = gtk::PropertyExpression::new(
+            History::static_type(),
+            Some(&history_expression),
+            "search-query",
+        );
+        let attributes_expression = gtk::ClosureExpression::new(
+            move |expressions| -> pango::AttrList {
+                let label = expressions[0].get::<gtk::Widget>().unwrap();
+                let content = expressions[1].get::<BoxedMessageContent>().unwrap();
+                let search_query = expressions[2].get::<&str>().unwrap();
+                highlight_attributes(
+                    &format_message_content_text(content.0),
+                    search_query,
+                    &label,
+                )
+            },
+            &[
+                content_expression.upcast(),
+                search_query_expression.upcast(),
+            ],
+        );
+        attributes_expression.bind(&content_label, "attributes", Some(&content_label));
     }
+
+    /// Shows the text of the message split in blocks, if it contains any block of code.
+    fn update_blocks(&self, message: &Message) {
+        let self_ = imp::MessageText::from_instance(self);
+
+        while let Some(child) = self_.blocks_box.first_child() {
+            self_.blocks_box.remove(&child);
+        }
+
+        let blocks = match message.content().0 {
+            MessageContent::MessageText(data) => split_code_blocks(data.text),
+            _ => vec![],
+        };
+        let has_code = blocks
+            .iter()
+            .any(|block| matches!(block, FormattedTextBlock::Code { .. }));
+
+        self_.content_label.set_visible(!has_code);
+        self_.blocks_box.set_visible(has_code);
+        if !has_code {
+            return;
+        }
+
+        for block in blocks {
+            match block {
+                FormattedTextBlock::Text(text) => {
+                    self_.blocks_box.append(&text_block_label(text));
+                }
+                FormattedTextBlock::Code { text, language } => {
+                    self_
+                        .blocks_box
+                        .append(&MessageCodeBlock::new(&text, &language));
+                }
+            }
+        }
+    }
+
+    fn update_web_page(&self, message: &Message) {
+        let self_ = imp::MessageText::from_instance(self);
+
+        let web_page = match message.content().0 {
+            MessageContent::MessageText(data) => data.web_page,
+            _ => None,
+        };
+
+        match web_page {
+            Some(web_page) => {
+                let session = message.chat().session();
+                self_.web_page_preview.set_web_page(&session, web_page);
+                self_.web_page_preview.set_visible(true);
+            }
+            None => self_.web_page_preview.set_visible(false),
+        }
+    }
+}
+
+fn text_block_label(text: FormattedText) -> gtk::Label {
+    let label = gtk::Label::builder()
+        .label(&parse_formatted_text(text))
+        .use_markup(true)
+        .selectable(true)
+        .wrap(true)
+        .wrap_mode(pango::WrapMode::WordChar)
+        .xalign(0.0)
+        .build();
+    label.add_css_class("message-text");
+    connect_entity_links(&label);
+    label
 }
 
 fn format_message_content_text(content: MessageContent) -> String {
@@ -0,0 +1,428 @@
+use gettextrs::gettext;
+use glib::clone;
+use gtk::{gdk, gio, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use tdgrand::enums::{MessageContent, ThumbnailFormat};
+use tdgrand::types::{File, Minithumbnail, Thumbnail};
+
+use super::connect_entity_links;
+use super::photo::{minithumbnail_texture, scale_to_fit};
+use crate::session::chat::{BoxedMessageContent, Message};
+use crate::utils::parse_formatted_text;
+
+mod imp {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-message-video.ui")]
+    pub struct MessageVideo {
+        pub message: RefCell<Option<Message>>,
+        pub file: RefCell<Option<File>>,
+        /// Whether this is a GIF, which is played automatically, muted and in a loop
+        pub is_animation: Cell<bool>,
+        pub duration: Cell<i32>,
+        pub media: RefCell<Option<gtk::MediaFile>>,
+        #[template_child]
+        pub overlay: TemplateChild<gtk::Overlay>,
+        #[template_child]
+        pub picture: TemplateChild<gtk::Picture>,
+        #[template_child]
+        pub status_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub action_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub media_controls: TemplateChild<gtk::MediaControls>,
+        #[template_child]
+        pub caption_label: TemplateChild<gtk::Label>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for MessageVideo {
+        const NAME: &'static str = "ContentMessageVideo";
+        type Type = super::MessageVideo;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for MessageVideo {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            connect_entity_links(&self.caption_label);
+            self.action_button
+                .connect_clicked(clone!(@weak obj => move |_| {
+                    obj.handle_action_button();
+                }));
+        }
+
+        fn dispose(&self, _obj: &Self::Type) {
+            self.overlay.unparent();
+            self.caption_label.unparent();
+        }
+    }
+
+    impl WidgetImpl for MessageVideo {
+        // Only play GIFs while they're shown and pause the videos when they're hidden
+        fn map(&self, widget: &Self::Type) {
+            self.parent_map(widget);
+
+            if self.is_animation.get() {
+                if let Some(media) = self.media.borrow().as_ref() {
+                    media.play();
+                }
+            }
+        }
+
+        fn unmap(&self, widget: &Self::Type) {
+            self.parent_unmap(widget);
+
+            if let Some(media) = self.media.borrow().as_ref() {
+                media.pause();
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct MessageVideo(ObjectSubclass<imp::MessageVideo>)
+        @extends gtk::Widget;
+}
+
+impl Default for MessageVideo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageVideo {
+    pub fn new() -> Self {
+        glib::Object::new(&[]).expect("Failed to create MessageVideo")
+    }
+
+    pub fn set_message(&self, message: &Message) {
+        let self_ = imp::MessageVideo::from_instance(self);
+
+        if message.is_outgoing() {
+            self.add_css_class("outgoing");
+        } else {
+            self.remove_css_class("outgoing");
+        }
+
+        // Set caption label expression
+        let message_expression = gtk::ConstantExpression::new(message);
+        let content_expression = gtk::PropertyExpression::new(
+            Message::static_type(),
+            Some(&message_expression),
+            "content",
+        );
+        let caption_expression = gtk::ClosureExpression::new(
+            move |expressions| -> String {
+                let content = expressions[1].get::<BoxedMessageContent>().unwrap();
+                match content.0 {
+                    MessageContent::MessageAnimation(data) => parse_formatted_text(data.caption),
+                    MessageContent::MessageVideo(data) => parse_formatted_text(data.caption),
+                    _ => String::new(),
+                }
+            },
+            &[content_expression.upcast()],
+        );
+        let caption_label = self_.caption_label.get();
+        caption_expression.bind(&caption_label, "label", Some(&caption_label));
+
+        // Stop the playback of the previous message
+        self.stop();
+        self_.message.replace(Some(message.clone()));
+
+        let (file, width, height, duration, minithumbnail, thumbnail, caption, is_animation) =
+            match message.content().0 {
+                MessageContent::MessageAnimation(data) => (
+                    data.animation.animation,
+                    data.animation.width,
+                    data.animation.height,
+                    data.animation.duration,
+                    data.animation.minithumbnail,
+                    data.animation.thumbnail,
+                    data.caption,
+                    true,
+                ),
+                MessageContent::MessageVideo(data) => (
+                    data.video.video,
+                    data.video.width,
+                    data.video.height,
+                    data.video.duration,
+                    data.video.minithumbnail,
+                    data.video.thumbnail,
+                    data.caption,
+                    false,
+                ),
+                _ => return,
+            };
+
+        caption_label.set_visible(!caption.text.is_empty());
+        self_.is_animation.set(is_animation);
+        self_.duration.set(duration);
+
+        // Scale the video to fit it in the chat history, but keeping its
+        // original aspect ratio
+        let (width, height) = scale_to_fit(width, height);
+        self_.picture.set_size_request(width, height);
+
+        // Show the thumbnail until the video is played
+        self.load_minithumbnail(minithumbnail.as_ref());
+        if let Some(thumbnail) = thumbnail {
+            self.load_thumbnail(thumbnail);
+        }
+
+        // Keep listening for the progress of a download that is already active and
+        // download GIFs automatically, so that they can be played right away
+        if file.local.is_downloading_active
+            || (is_animation
+                && !file.local.is_downloading_completed
+                && file.local.can_be_downloaded)
+        {
+            self.download_file(&file);
+        }
+
+        self.update_file(file);
+    }
+
+    fn load_minithumbnail(&self, minithumbnail: Option<&Minithumbnail>) {
+        let self_ = imp::MessageVideo::from_instance(self);
+        let texture = minithumbnail.and_then(minithumbnail_texture);
+
+        self_.picture.set_paintable(texture.as_ref());
+        self_.picture.add_css_class("blurred");
+    }
+
+    fn load_thumbnail(&self, thumbnail: Thumbnail) {
+        // Only JPEG thumbnails can be shown as a still image
+        if !matches!(thumbnail.format, ThumbnailFormat::Jpeg) {
+            return;
+        }
+
+        let file = thumbnail.file;
+        if file.local.is_downloading_completed {
+            self.show_thumbnail(&file);
+        } else if let Some(message) = imp::MessageVideo::from_instance(self)
+            .message
+            .borrow()
+            .as_ref()
+        {
+            let (sender, receiver) = glib::MainContext::channel::<File>(Default::default());
+            let message_id = message.id();
+
+            receiver.attach(
+                None,
+                clone!(@weak self as obj => @default-return glib::Continue(false), move |file| {
+                    let self_ = imp::MessageVideo::from_instance(&obj);
+
+                    // Check that the widget is still showing the same message
+                    if self_.message.borrow().as_ref().map(Message::id) != Some(message_id) {
+                        return glib::Continue(false);
+                    }
+
+                    if file.local.is_downloading_completed {
+                        obj.show_thumbnail(&file);
+                        return glib::Continue(false);
+                    }
+
+                    glib::Continue(true)
+                }),
+            );
+
+            message.chat().session().download_file(file.id, sender);
+        }
+    }
+
+    fn show_thumbnail(&self, file: &File) {
+        let self_ = imp::MessageVideo::from_instance(self);
+
+        // Don't replace a video that is already playing
+        if self_.media.borrow().is_some() {
+            return;
+        }
+
+        match gdk::Texture::from_file(&gio::File::for_path(&file.local.path)) {
+            Ok(texture) => {
+                self_.picture.set_paintable(Some(&texture));
+                self_.picture.remove_css_class("blurred");
+            }
+            Err(e) => log::warn!("Failed to load video thumbnail: {}", e),
+        }
+    }
+
+    fn update_file(&self, file: File) {
+        let self_ = imp::MessageVideo::from_instance(self);
+        let local = &file.local;
+        let size = file.size.max(file.expected_size) as u64;
+        let duration = format_duration(self_.duration.get());
+        let is_animation = self_.is_animation.get();
+
+        if local.is_downloading_completed {
+            self_.status_label.set_label(&if is_animation {
+                gettext("GIF")
+            } else {
+                duration
+            });
+            self_
+                .action_button
+                .set_icon_name("media-playback-start-symbolic");
+            self_.action_button.set_tooltip_text(Some(&gettext("Play")));
+        } else if local.is_downloading_active {
+            self_.status_label.set_label(&format!(
+                "{} / {}",
+                glib::format_size(local.downloaded_size as u64),
+                glib::format_size(size)
+            ));
+            self_.action_button.set_icon_name("process-stop-symbolic");
+            self_
+                .action_button
+                .set_tooltip_text(Some(&gettext("Cancel Download")));
+        } else {
+            self_.status_label.set_label(&if is_animation {
+                gettext("GIF")
+            } else {
+                format!("{}, {}", duration, glib::format_size(size))
+            });
+            self_
+                .action_button
+                .set_icon_name("folder-download-symbolic");
+            self_
+                .action_button
+                .set_tooltip_text(Some(&gettext("Download")));
+        }
+
+        self_.action_button.set_sensitive(
+            local.is_downloading_completed
+                || local.is_downloading_active
+                || local.can_be_downloaded,
+        );
+
+        let is_downloading_completed = local.is_downloading_completed;
+        self_.file.replace(Some(file));
+
+        // GIFs don't need to be started by the user
+        if is_animation && is_downloading_completed {
+            self.play();
+        }
+        self_
+            .action_button
+            .set_visible(self_.media.borrow().is_none());
+    }
+
+    fn download_file(&self, file: &File) {
+        let self_ = imp::MessageVideo::from_instance(self);
+
+        let (sender, receiver) = glib::MainContext::channel::<File>(Default::default());
+
+        receiver.attach(
+            None,
+            clone!(@weak self as obj => @default-return glib::Continue(false), move |file| {
+                let self_ = imp::MessageVideo::from_instance(&obj);
+
+                // Check that the widget is still showing the same file
+                if self_.file.borrow().as_ref().map(|f| f.id) != Some(file.id) {
+                    return glib::Continue(false);
+                }
+
+                let is_downloading_active = file.local.is_downloading_active;
+                obj.update_file(file);
+
+                glib::Continue(is_downloading_active)
+            }),
+        );
+
+        if let Some(message) = self_.message.borrow().as_ref() {
+            message.chat().session().download_file(file.id, sender);
+        }
+    }
+
+    fn handle_action_button(&self) {
+        let self_ = imp::MessageVideo::from_instance(self);
+
+        let file = match self_.file.borrow().clone() {
+            Some(file) => file,
+            None => return,
+        };
+
+        if file.local.is_downloading_completed {
+            self.play();
+        } else if file.local.is_downloading_active {
+            if let Some(message) = self_.message.borrow().as_ref() {
+                message.chat().session().cancel_download_file(file.id);
+            }
+        } else {
+            self.download_file(&file);
+        }
+    }
+
+    /// Starts playing the downloaded file. GIFs are played muted and in a loop, while
+    /// videos get the playback controls.
+    fn play(&self) {
+        let self_ = imp::MessageVideo::from_instance(self);
+
+        if self_.media.borrow().is_some() {
+            return;
+        }
+
+        let path = match self_.file.borrow().as_ref() {
+            Some(file) if file.local.is_downloading_completed => file.local.path.clone(),
+            _ => return,
+        };
+
+        let media = gtk::MediaFile::for_filename(&path);
+        self_.picture.set_paintable(Some(&media));
+        self_.picture.remove_css_class("blurred");
+        self_.action_button.set_visible(false);
+
+        if self_.is_animation.get() {
+            media.set_loop(true);
+            media.set_muted(true);
+            if self.is_mapped() {
+                media.play();
+            }
+        } else {
+            self_.status_label.set_visible(false);
+            self_.media_controls.set_media_stream(Some(&media));
+            self_.media_controls.set_visible(true);
+            media.play();
+        }
+
+        self_.media.replace(Some(media));
+    }
+
+    fn stop(&self) {
+        let self_ = imp::MessageVideo::from_instance(self);
+
+        if let Some(media) = self_.media.take() {
+            media.pause();
+        }
+
+        self_
+            .media_controls
+            .set_media_stream(gtk::NONE_MEDIA_STREAM);
+        self_.media_controls.set_visible(false);
+        self_.status_label.set_visible(true);
+        self_.action_button.set_visible(true);
+    }
+}
+
+/// Formats a duration in seconds like "1:05" or "1:02:05".
+fn format_duration(seconds: i32) -> String {
+    let hours = seconds / 3600;
+    let minutes = seconds % 3600 / 60;
+    let seconds = seconds % 60;
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
@@ -1,14 +1,28 @@
 mod chat_action_bar;
 mod chat_history;
+mod chat_picker_dialog;
+mod chat_pinned_message_bar;
+mod chat_search_bar;
+mod chat_selection_header;
 mod event_row;
 mod item_row;
 mod message_row;
+mod send_media_dialog;
+mod sticker_picker;
+mod sticker_set_dialog;
 mod user_dialog;
 
 use self::chat_action_bar::ChatActionBar;
 use self::chat_history::ChatHistory;
+use self::chat_picker_dialog::ChatPickerDialog;
+use self::chat_pinned_message_bar::ChatPinnedMessageBar;
+use self::chat_search_bar::ChatSearchBar;
+use self::chat_selection_header::ChatSelectionHeader;
 use self::event_row::EventRow;
 use self::item_row::ItemRow;
+use self::send_media_dialog::SendMediaDialog;
+use self::sticker_picker::StickerPicker;
+use self::sticker_set_dialog::StickerSetDialog;
 use self::user_dialog::UserDialog;
 
 use gtk::glib;
@@ -130,6 +144,12 @@ impl Content {
         glib::Object::new(&[]).expect("Failed to create Content")
     }
 
+    /// Shows the given message, opening its chat at it.
+    pub fn show_message(&self, chat: &Chat, message_id: i64) {
+        let self_ = imp::Content::from_instance(self);
+        self_.chat_history.show_message(chat, message_id);
+    }
+
     pub fn chat(&self) -> Option<Chat> {
         let self_ = imp::Content::from_instance(self);
         self_.chat.borrow().clone()
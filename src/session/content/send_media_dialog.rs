@@ -0,0 +1,223 @@
+use gettextrs::gettext;
+use glib::clone;
+use gtk::{gio, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+
+mod imp {
+    use super::*;
+    use adw::subclass::prelude::AdwWindowImpl;
+    use glib::subclass::Signal;
+    use once_cell::sync::{Lazy, OnceCell};
+    use std::cell::Cell;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-send-media-dialog.ui")]
+    pub struct SendMediaDialog {
+        pub file: OnceCell<gio::File>,
+        pub is_photo: Cell<bool>,
+        #[template_child]
+        pub window_title: TemplateChild<adw::WindowTitle>,
+        #[template_child]
+        pub picture: TemplateChild<gtk::Picture>,
+        #[template_child]
+        pub file_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub file_icon: TemplateChild<gtk::Image>,
+        #[template_child]
+        pub name_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub size_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub caption_entry: TemplateChild<gtk::Entry>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SendMediaDialog {
+        const NAME: &'static str = "ContentSendMediaDialog";
+        type Type = super::SendMediaDialog;
+        type ParentType = adw::Window;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+
+            klass.install_action("send-media-dialog.send", None, move |widget, _, _| {
+                widget.send();
+            });
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for SendMediaDialog {
+        fn properties() -> &'static [glib::ParamSpec] {
+            static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+                vec![glib::ParamSpec::new_object(
+                    "file",
+                    "File",
+                    "The file to be sent",
+                    gio::File::static_type(),
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::CONSTRUCT_ONLY,
+                )]
+            });
+            PROPERTIES.as_ref()
+        }
+
+        fn set_property(
+            &self,
+            _obj: &Self::Type,
+            _id: usize,
+            value: &glib::Value,
+            pspec: &glib::ParamSpec,
+        ) {
+            match pspec.name() {
+                "file" => self.file.set(value.get().unwrap()).unwrap(),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn property(&self, obj: &Self::Type, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            match pspec.name() {
+                "file" => obj.file().to_value(),
+                _ => unimplemented!(),
+            }
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![Signal::builder(
+                    "send",
+                    &[String::static_type().into()],
+                    <()>::static_type().into(),
+                )
+                .build()]
+            });
+            SIGNALS.as_ref()
+        }
+
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            self.caption_entry
+                .connect_activate(clone!(@weak obj => move |_| {
+                    obj.activate_action("send-media-dialog.send", None);
+                }));
+
+            obj.setup_preview();
+        }
+    }
+
+    impl WidgetImpl for SendMediaDialog {}
+    impl WindowImpl for SendMediaDialog {}
+    impl AdwWindowImpl for SendMediaDialog {}
+}
+
+glib::wrapper! {
+    pub struct SendMediaDialog(ObjectSubclass<imp::SendMediaDialog>)
+        @extends gtk::Widget, gtk::Window, adw::Window;
+}
+
+impl SendMediaDialog {
+    pub fn new(parent_window: &Option<gtk::Window>, file: &gio::File) -> Self {
+        glib::Object::new(&[("transient-for", parent_window), ("file", file)])
+            .expect("Failed to create SendMediaDialog")
+    }
+
+    fn setup_preview(&self) {
+        // The file could be on a slow location, so don't let it be sent before knowing
+        // how to send it
+        self.action_set_enabled("send-media-dialog.send", false);
+
+        self.file().query_info_async(
+            "standard::content-type,standard::display-name,standard::size",
+            gio::FileQueryInfoFlags::NONE,
+            glib::PRIORITY_DEFAULT,
+            None::<&gio::Cancellable>,
+            clone!(@weak self as obj => move |info| {
+                obj.show_preview(info);
+            }),
+        );
+    }
+
+    fn show_preview(&self, info: Result<gio::FileInfo, glib::Error>) {
+        let self_ = imp::SendMediaDialog::from_instance(self);
+        let file = self.file();
+
+        let (content_type, display_name, size) = match info {
+            Ok(info) => (
+                info.content_type().map(|c| c.to_string()),
+                info.display_name().to_string(),
+                info.size(),
+            ),
+            Err(_) => (
+                None,
+                file.basename()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                0,
+            ),
+        };
+
+        // Only send the formats supported by telegram as compressed photos, the
+        // rest of the files are sent as documents
+        let is_photo = content_type
+            .as_ref()
+            .and_then(|content_type| gio::content_type_get_mime_type(content_type))
+            .map(|mime_type| {
+                matches!(
+                    mime_type.as_str(),
+                    "image/jpeg" | "image/png" | "image/webp" | "image/bmp"
+                )
+            })
+            .unwrap_or_default();
+        self_.is_photo.set(is_photo);
+
+        if is_photo {
+            self_.window_title.set_title(&gettext("Send Photo"));
+            self_.picture.set_file(Some(&file));
+            self_.picture.set_visible(true);
+        } else {
+            self_.window_title.set_title(&gettext("Send File"));
+
+            let content_type =
+                content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+            let icon = gio::content_type_get_symbolic_icon(&content_type);
+            self_.file_icon.set_from_gicon(&icon);
+            self_.name_label.set_label(&display_name);
+            self_.size_label.set_label(&glib::format_size(size as u64));
+            self_.file_box.set_visible(true);
+        }
+
+        self.action_set_enabled("send-media-dialog.send", true);
+        self_.caption_entry.grab_focus();
+    }
+
+    fn send(&self) {
+        let self_ = imp::SendMediaDialog::from_instance(self);
+        let caption = self_.caption_entry.text().trim().to_string();
+
+        self.emit_by_name("send", &[&caption]).unwrap();
+        self.close();
+    }
+
+    pub fn file(&self) -> gio::File {
+        let self_ = imp::SendMediaDialog::from_instance(self);
+        self_.file.get().unwrap().clone()
+    }
+
+    pub fn is_photo(&self) -> bool {
+        let self_ = imp::SendMediaDialog::from_instance(self);
+        self_.is_photo.get()
+    }
+
+    pub fn connect_send<F: Fn(&Self, String) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("send", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            let caption = values[1].get::<String>().unwrap();
+            f(&obj, caption);
+
+            None
+        })
+        .unwrap()
+    }
+}
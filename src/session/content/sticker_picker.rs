@@ -0,0 +1,270 @@
+use gettextrs::gettext;
+use glib::clone;
+use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use tdgrand::{enums, functions, types};
+
+use crate::session::components::{BoxedSticker, Sticker};
+use crate::session::Session;
+use crate::utils::do_async;
+
+const STICKER_SIZE: i32 = 64;
+
+mod imp {
+    use super::*;
+    use glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-sticker-picker.ui")]
+    pub struct StickerPicker {
+        pub session: RefCell<Option<Session>>,
+        /// The grids of stickers of each page, by page name
+        pub grids: RefCell<HashMap<String, gtk::FlowBox>>,
+        /// The sticker sets whose stickers are loaded only when their page is shown
+        pub unloaded_sets: RefCell<HashMap<String, i64>>,
+        #[template_child]
+        pub stack: TemplateChild<gtk::Stack>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for StickerPicker {
+        const NAME: &'static str = "ContentStickerPicker";
+        type Type = super::StickerPicker;
+        type ParentType = gtk::Popover;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for StickerPicker {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![Signal::builder(
+                    "sticker-selected",
+                    &[BoxedSticker::static_type().into()],
+                    <()>::static_type().into(),
+                )
+                .build()]
+            });
+            SIGNALS.as_ref()
+        }
+
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            // Reload the stickers every time the picker is opened, so that they're
+            // always up to date
+            obj.connect_show(|obj| obj.load_stickers());
+
+            self.stack
+                .connect_visible_child_name_notify(clone!(@weak obj => move |_| {
+                    obj.load_visible_sticker_set();
+                }));
+        }
+    }
+
+    impl WidgetImpl for StickerPicker {}
+    impl PopoverImpl for StickerPicker {}
+}
+
+glib::wrapper! {
+    pub struct StickerPicker(ObjectSubclass<imp::StickerPicker>)
+        @extends gtk::Widget, gtk::Popover;
+}
+
+impl Default for StickerPicker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StickerPicker {
+    pub fn new() -> Self {
+        glib::Object::new(&[]).expect("Failed to create StickerPicker")
+    }
+
+    pub fn session(&self) -> Option<Session> {
+        let self_ = imp::StickerPicker::from_instance(self);
+        self_.session.borrow().clone()
+    }
+
+    pub fn set_session(&self, session: Option<Session>) {
+        let self_ = imp::StickerPicker::from_instance(self);
+        self_.session.replace(session);
+    }
+
+    fn load_stickers(&self) {
+        let session = match self.session() {
+            Some(session) => session,
+            None => return,
+        };
+        let self_ = imp::StickerPicker::from_instance(self);
+
+        self_.grids.borrow_mut().clear();
+        self_.unloaded_sets.borrow_mut().clear();
+        while let Some(child) = self_.stack.first_child() {
+            self_.stack.remove(&child);
+        }
+
+        self.add_page(
+            "recent",
+            &gettext("Recent"),
+            Some("document-open-recent-symbolic"),
+        );
+        self.add_page("favorite", &gettext("Favorites"), Some("starred-symbolic"));
+
+        let client_id = session.client_id();
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::GetRecentStickers::new()
+                    .is_attached(false)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                if let Ok(enums::Stickers::Stickers(data)) = result {
+                    obj.fill_page("recent", data.stickers);
+                }
+            }),
+        );
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move { functions::GetFavoriteStickers::new().send(client_id).await },
+            clone!(@weak self as obj => move |result| async move {
+                if let Ok(enums::Stickers::Stickers(data)) = result {
+                    obj.fill_page("favorite", data.stickers);
+                }
+            }),
+        );
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::GetInstalledStickerSets::new()
+                    .is_masks(false)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                if let Ok(enums::StickerSets::StickerSets(data)) = result {
+                    let self_ = imp::StickerPicker::from_instance(&obj);
+
+                    for set in data.sets {
+                        let name = format!("set-{}", set.id);
+                        obj.add_page(&name, &set.title, None);
+                        self_.unloaded_sets.borrow_mut().insert(name, set.id);
+                    }
+                }
+            }),
+        );
+    }
+
+    fn add_page(&self, name: &str, title: &str, icon_name: Option<&str>) {
+        let self_ = imp::StickerPicker::from_instance(self);
+
+        let grid = gtk::FlowBox::builder()
+            .homogeneous(true)
+            .selection_mode(gtk::SelectionMode::None)
+            .min_children_per_line(5)
+            .max_children_per_line(5)
+            .valign(gtk::Align::Start)
+            .build();
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .child(&grid)
+            .build();
+
+        let page = self_.stack.add_titled(&scrolled_window, Some(name), title);
+        if let Some(icon_name) = icon_name {
+            page.set_icon_name(icon_name);
+        }
+
+        self_.grids.borrow_mut().insert(name.to_owned(), grid);
+    }
+
+    fn fill_page(&self, name: &str, stickers: Vec<types::Sticker>) {
+        let self_ = imp::StickerPicker::from_instance(self);
+        let session = match self.session() {
+            Some(session) => session,
+            None => return,
+        };
+        let grid = match self_.grids.borrow().get(name) {
+            Some(grid) => grid.clone(),
+            None => return,
+        };
+
+        for sticker in stickers {
+            let sticker_widget = Sticker::new(STICKER_SIZE, false);
+            sticker_widget.set_sticker(&session, sticker.clone());
+
+            let button = gtk::Button::builder().child(&sticker_widget).build();
+            button.add_css_class("flat");
+            button.set_tooltip_text(Some(&sticker.emoji));
+            button.connect_clicked(clone!(@weak self as obj => move |_| {
+                obj.select_sticker(sticker.clone());
+            }));
+
+            grid.insert(&button, -1);
+        }
+    }
+
+    fn load_visible_sticker_set(&self) {
+        let self_ = imp::StickerPicker::from_instance(self);
+        let name = match self_.stack.visible_child_name() {
+            Some(name) => name.to_string(),
+            None => return,
+        };
+        let set_id = match self_.unloaded_sets.borrow_mut().remove(&name) {
+            Some(set_id) => set_id,
+            None => return,
+        };
+        let client_id = match self.session() {
+            Some(session) => session.client_id(),
+            None => return,
+        };
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::GetStickerSet::new()
+                    .set_id(set_id)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                if let Ok(enums::StickerSet::StickerSet(data)) = result {
+                    obj.fill_page(&name, data.stickers);
+                }
+            }),
+        );
+    }
+
+    fn select_sticker(&self, sticker: types::Sticker) {
+        self.emit_by_name("sticker-selected", &[&BoxedSticker(sticker)])
+            .unwrap();
+        self.popdown();
+    }
+
+    pub fn connect_sticker_selected<F: Fn(&Self, types::Sticker) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_local("sticker-selected", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            let sticker = values[1].get::<BoxedSticker>().unwrap();
+            f(&obj, sticker.0);
+
+            None
+        })
+    }
+}
@@ -0,0 +1,170 @@
+use gettextrs::{gettext, ngettext};
+use glib::clone;
+use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use tdgrand::{enums, functions};
+
+use crate::session::components::Sticker;
+use crate::session::Session;
+use crate::utils::do_async;
+
+const STICKER_SIZE: i32 = 72;
+
+mod imp {
+    use super::*;
+    use adw::subclass::prelude::AdwWindowImpl;
+    use once_cell::sync::OnceCell;
+    use std::cell::Cell;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/com/github/melix99/telegrand/ui/content-sticker-set-dialog.ui")]
+    pub struct StickerSetDialog {
+        pub session: OnceCell<Session>,
+        pub set_id: Cell<i64>,
+        #[template_child]
+        pub window_title: TemplateChild<adw::WindowTitle>,
+        #[template_child]
+        pub grid: TemplateChild<gtk::FlowBox>,
+        #[template_child]
+        pub add_button: TemplateChild<gtk::Button>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for StickerSetDialog {
+        const NAME: &'static str = "ContentStickerSetDialog";
+        type Type = super::StickerSetDialog;
+        type ParentType = adw::Window;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+
+            klass.install_action("sticker-set-dialog.add-set", None, move |widget, _, _| {
+                widget.add_sticker_set();
+            });
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for StickerSetDialog {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            // The set can be added only after knowing that it's not installed yet
+            obj.action_set_enabled("sticker-set-dialog.add-set", false);
+        }
+    }
+
+    impl WidgetImpl for StickerSetDialog {}
+    impl WindowImpl for StickerSetDialog {}
+    impl AdwWindowImpl for StickerSetDialog {}
+}
+
+glib::wrapper! {
+    pub struct StickerSetDialog(ObjectSubclass<imp::StickerSetDialog>)
+        @extends gtk::Widget, gtk::Window, adw::Window;
+}
+
+impl StickerSetDialog {
+    pub fn new(parent_window: &Option<gtk::Window>, session: &Session, set_id: i64) -> Self {
+        let dialog: Self = glib::Object::new(&[("transient-for", parent_window)])
+            .expect("Failed to create StickerSetDialog");
+
+        let self_ = imp::StickerSetDialog::from_instance(&dialog);
+        self_.session.set(session.clone()).unwrap();
+        self_.set_id.set(set_id);
+
+        dialog.load_sticker_set();
+        dialog
+    }
+
+    fn session(&self) -> &Session {
+        let self_ = imp::StickerSetDialog::from_instance(self);
+        self_.session.get().unwrap()
+    }
+
+    fn load_sticker_set(&self) {
+        let self_ = imp::StickerSetDialog::from_instance(self);
+        let client_id = self.session().client_id();
+        let set_id = self_.set_id.get();
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::GetStickerSet::new()
+                    .set_id(set_id)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                let self_ = imp::StickerSetDialog::from_instance(&obj);
+
+                match result {
+                    Ok(enums::StickerSet::StickerSet(data)) => {
+                        self_.window_title.set_title(&data.title);
+                        self_.window_title.set_subtitle(&ngettext!(
+                            "{} sticker",
+                            "{} stickers",
+                            data.stickers.len() as u32,
+                            data.stickers.len()
+                        ));
+
+                        for sticker in data.stickers {
+                            let sticker_widget = Sticker::new(STICKER_SIZE, true);
+                            sticker_widget.set_sticker(obj.session(), sticker);
+                            self_.grid.insert(&sticker_widget, -1);
+                        }
+
+                        obj.update_add_button(data.is_installed && !data.is_archived);
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to load the sticker set: {:?}", e);
+                        self_.window_title.set_subtitle(&gettext("Failed to load the stickers"));
+                    }
+                }
+            }),
+        );
+    }
+
+    fn update_add_button(&self, is_installed: bool) {
+        let self_ = imp::StickerSetDialog::from_instance(self);
+
+        if is_installed {
+            self_.add_button.set_label(&gettext("Added"));
+        } else {
+            self_.add_button.set_label(&gettext("_Add Set"));
+        }
+        self.action_set_enabled("sticker-set-dialog.add-set", !is_installed);
+    }
+
+    fn add_sticker_set(&self) {
+        let self_ = imp::StickerSetDialog::from_instance(self);
+        let client_id = self.session().client_id();
+        let set_id = self_.set_id.get();
+
+        // Avoid adding the set twice while the request is pending
+        self.action_set_enabled("sticker-set-dialog.add-set", false);
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::ChangeStickerSet::new()
+                    .set_id(set_id)
+                    .is_installed(true)
+                    .is_archived(false)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj => move |result| async move {
+                match result {
+                    Ok(_) => obj.update_add_button(true),
+                    Err(e) => {
+                        log::warn!("Failed to add the sticker set: {:?}", e);
+                        obj.update_add_button(false);
+                    }
+                }
+            }),
+        );
+    }
+}
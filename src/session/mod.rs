@@ -15,9 +15,10 @@ use self::sidebar::Sidebar;
 use self::user::User;
 use self::user_list::UserList;
 
-use glib::{clone, SyncSender};
-use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
+use glib::{clone, Sender};
+use gtk::{gio, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
 use std::collections::hash_map::{Entry, HashMap};
+use std::path::{Path, PathBuf};
 use tdgrand::enums::{NotificationSettingsScope, Update, User as TelegramUser};
 use tdgrand::functions;
 use tdgrand::types::{File, ScopeNotificationSettings};
@@ -46,11 +47,16 @@ mod imp {
         pub private_chats_notification_settings: RefCell<BoxedScopeNotificationSettings>,
         pub group_chats_notification_settings: RefCell<BoxedScopeNotificationSettings>,
         pub channel_chats_notification_settings: RefCell<BoxedScopeNotificationSettings>,
-        pub downloading_files: RefCell<HashMap<i32, Vec<SyncSender<File>>>>,
+        pub downloading_files: RefCell<HashMap<i32, Vec<Sender<File>>>>,
+        pub uploading_files: RefCell<HashMap<i32, Vec<Sender<File>>>>,
+        /// The temporary files of the messages being sent, by chat id and message id
+        pub temporary_files: RefCell<HashMap<(i64, i64), PathBuf>>,
         #[template_child]
         pub leaflet: TemplateChild<adw::Leaflet>,
         #[template_child]
         pub sidebar: TemplateChild<Sidebar>,
+        #[template_child]
+        pub content: TemplateChild<Content>,
     }
 
     #[glib::object_subclass]
@@ -219,10 +225,15 @@ impl Session {
     }
 
     pub fn handle_update(&self, update: Update) {
+        self.handle_temporary_files(&update);
+
         match update {
             Update::NewMessage(_)
             | Update::MessageSendSucceeded(_)
+            | Update::MessageSendFailed(_)
             | Update::MessageContent(_)
+            | Update::MessageEdited(_)
+            | Update::MessageIsPinned(_)
             | Update::NewChat(_)
             | Update::ChatTitle(_)
             | Update::ChatPhoto(_)
@@ -230,7 +241,10 @@ impl Session {
             | Update::ChatNotificationSettings(_)
             | Update::ChatPosition(_)
             | Update::ChatReadInbox(_)
+            | Update::ChatReadOutbox(_)
             | Update::ChatDraftMessage(_)
+            | Update::ChatOnlineMemberCount(_)
+            | Update::UserChatAction(_)
             | Update::DeleteMessages(_) => {
                 self.chat_list().handle_update(update);
             }
@@ -258,28 +272,50 @@ impl Session {
         }
     }
 
-    pub fn download_file(&self, file_id: i32, sender: SyncSender<File>) {
+    pub fn download_file(&self, file_id: i32, sender: Sender<File>) {
         let self_ = imp::Session::from_instance(self);
 
-        let mut downloading_files = self_.downloading_files.borrow_mut();
-        match downloading_files.entry(file_id) {
-            Entry::Occupied(mut entry) => {
-                entry.get_mut().push(sender);
-            }
-            Entry::Vacant(entry) => {
-                entry.insert(vec![sender]);
-
-                let client_id = self.client_id();
-                RUNTIME.spawn(async move {
-                    functions::DownloadFile::new()
-                        .file_id(file_id)
-                        .priority(5)
-                        .send(client_id)
-                        .await
-                        .unwrap();
-                });
-            }
-        }
+        self_
+            .downloading_files
+            .borrow_mut()
+            .entry(file_id)
+            .or_default()
+            .push(sender);
+
+        // Request the download even if there are other receivers, because the previous
+        // download could have been cancelled or could have failed
+        let client_id = self.client_id();
+        RUNTIME.spawn(async move {
+            functions::DownloadFile::new()
+                .file_id(file_id)
+                .priority(5)
+                .send(client_id)
+                .await
+                .unwrap();
+        });
+    }
+
+    /// Sends the updates of a file being uploaded to the given sender, until the upload
+    /// completes or the receiver is dropped.
+    pub fn watch_file_upload(&self, file_id: i32, sender: Sender<File>) {
+        let self_ = imp::Session::from_instance(self);
+        self_
+            .uploading_files
+            .borrow_mut()
+            .entry(file_id)
+            .or_default()
+            .push(sender);
+    }
+
+    pub fn cancel_download_file(&self, file_id: i32) {
+        let client_id = self.client_id();
+        RUNTIME.spawn(async move {
+            functions::CancelDownloadFile::new()
+                .file_id(file_id)
+                .send(client_id)
+                .await
+                .unwrap();
+        });
     }
 
     pub fn begin_chats_search(&self) {
@@ -288,21 +324,79 @@ impl Session {
         self_.sidebar.begin_chats_search();
     }
 
+    pub fn set_accounts_menu(&self, accounts_menu: &gio::MenuModel) {
+        let self_ = imp::Session::from_instance(self);
+        self_.sidebar.set_accounts_menu(accounts_menu);
+    }
+
     fn handle_file_update(&self, file: File) {
         let self_ = imp::Session::from_instance(self);
 
         let mut downloading_files = self_.downloading_files.borrow_mut();
-        if let Entry::Occupied(entry) = downloading_files.entry(file.id) {
-            for sender in entry.get() {
-                sender.send(file.clone()).unwrap();
+        if let Entry::Occupied(mut entry) = downloading_files.entry(file.id) {
+            // Forget about the receivers that have been dropped
+            entry
+                .get_mut()
+                .retain(|sender| sender.send(file.clone()).is_ok());
+
+            // Keep the receivers of downloads that have been stopped, so that they're
+            // still updated if the download is resumed
+            if file.local.is_downloading_completed || entry.get().is_empty() {
+                entry.remove();
             }
+        }
+
+        let mut uploading_files = self_.uploading_files.borrow_mut();
+        if let Entry::Occupied(mut entry) = uploading_files.entry(file.id) {
+            entry
+                .get_mut()
+                .retain(|sender| sender.send(file.clone()).is_ok());
 
-            if file.local.is_downloading_completed {
+            // The upload may not have started yet, so only stop when it has completed
+            if file.remote.is_uploading_completed || entry.get().is_empty() {
                 entry.remove();
             }
         }
     }
 
+    /// Removes the temporary file of a message being sent once the message has been sent or
+    /// deleted. The file is kept while sending fails, so that the message can be resent.
+    pub fn add_temporary_file(&self, chat_id: i64, message_id: i64, path: PathBuf) {
+        let self_ = imp::Session::from_instance(self);
+        self_
+            .temporary_files
+            .borrow_mut()
+            .insert((chat_id, message_id), path);
+    }
+
+    /// Stops tracking the temporary file of a message, returning its path, if any.
+    pub fn take_temporary_file(&self, chat_id: i64, message_id: i64) -> Option<PathBuf> {
+        let self_ = imp::Session::from_instance(self);
+        self_
+            .temporary_files
+            .borrow_mut()
+            .remove(&(chat_id, message_id))
+    }
+
+    fn handle_temporary_files(&self, update: &Update) {
+        match update {
+            Update::MessageSendSucceeded(data) => {
+                let chat_id = data.message.chat_id;
+                if let Some(path) = self.take_temporary_file(chat_id, data.old_message_id) {
+                    remove_temporary_file(&path);
+                }
+            }
+            Update::DeleteMessages(data) if data.is_permanent => {
+                for message_id in &data.message_ids {
+                    if let Some(path) = self.take_temporary_file(data.chat_id, *message_id) {
+                        remove_temporary_file(&path);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
     fn log_out(&self) {
         let client_id = self.client_id();
         RUNTIME.spawn(async move {
@@ -330,6 +424,18 @@ impl Session {
         self_.user_list.get_or_init(|| UserList::new(self))
     }
 
+    /// Selects the chat and shows its history.
+    pub fn select_chat(&self, chat: &Chat) {
+        self.set_selected_chat(Some(chat.clone()));
+    }
+
+    /// Selects the chat of the message and scrolls its history to the message.
+    pub fn select_message(&self, chat: &Chat, message_id: i64) {
+        let self_ = imp::Session::from_instance(self);
+        self_.content.show_message(chat, message_id);
+        self.set_selected_chat(Some(chat.clone()));
+    }
+
     fn selected_chat(&self) -> Option<Chat> {
         let self_ = imp::Session::from_instance(self);
         self_.selected_chat.borrow().clone()
@@ -416,3 +522,9 @@ impl Session {
         self.chat_list().fetch(client_id);
     }
 }
+
+pub fn remove_temporary_file(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        log::warn!("Failed to remove the temporary file {:?}: {}", path, e);
+    }
+}
@@ -3,10 +3,12 @@ mod row;
 
 use self::row::Row;
 
+use gettextrs::gettext;
 use glib::clone;
 use gtk::{gio, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
 use tdgrand::{enums, functions, types};
 
+use crate::session::chat::Message;
 use crate::session::{Chat, User};
 use crate::utils::do_async;
 use crate::Session;
@@ -16,6 +18,7 @@ pub use self::avatar::Avatar;
 mod imp {
     use super::*;
     use once_cell::sync::Lazy;
+    use once_cell::unsync::OnceCell;
     use std::cell::{Cell, RefCell};
 
     #[derive(Debug, Default, CompositeTemplate)]
@@ -29,6 +32,12 @@ mod imp {
         pub searched_chats: RefCell<Vec<i64>>,
         pub searched_users: RefCell<Vec<i32>>,
         pub already_searched_users: RefCell<Vec<i32>>,
+        pub found_messages: OnceCell<gio::ListStore>,
+        pub found_messages_total_count: Cell<i32>,
+        pub found_messages_search_id: Cell<u32>,
+        pub is_loading_found_messages: Cell<bool>,
+        #[template_child]
+        pub primary_menu: TemplateChild<gio::Menu>,
         #[template_child]
         pub header_bar: TemplateChild<adw::HeaderBar>,
         #[template_child]
@@ -126,6 +135,43 @@ mod imp {
                     let query = entry.text().to_string();
                     obj.search(query);
                 }));
+
+            let factory = gtk::SignalListItemFactory::new();
+            factory.connect_setup(|_, list_item| {
+                let row = Row::new();
+                row.set_margin_top(6);
+                row.set_margin_bottom(6);
+                list_item.set_child(Some(&row));
+            });
+            factory.connect_bind(clone!(@weak obj => move |_, list_item| {
+                let item = list_item.item();
+                let row = list_item.child().unwrap().downcast::<Row>().unwrap();
+
+                // The section titles of the search results can't be selected
+                let is_section_title = item
+                    .as_ref()
+                    .map(|item| item.is::<gtk::StringObject>())
+                    .unwrap_or_default();
+                list_item.set_selectable(!is_section_title);
+                list_item.set_activatable(!is_section_title);
+
+                let is_message = item.as_ref().map(|item| item.is::<Message>()).unwrap_or_default();
+                row.set_item(item);
+
+                if is_message {
+                    let self_ = imp::Sidebar::from_instance(&obj);
+                    row.highlight(&self_.search_entry.text());
+                }
+            }));
+            self.list_view.set_factory(Some(&factory));
+
+            // Load more found messages when reaching the end of the search results
+            self.scrolled_window
+                .connect_edge_reached(clone!(@weak obj => move |_, position| {
+                    if position == gtk::PositionType::Bottom {
+                        obj.search_messages();
+                    }
+                }));
         }
 
         fn dispose(&self, _obj: &Self::Type) {
@@ -160,54 +206,55 @@ impl Sidebar {
         self_.search_entry.grab_focus();
     }
 
+    pub fn set_accounts_menu(&self, accounts_menu: &gio::MenuModel) {
+        let self_ = imp::Sidebar::from_instance(self);
+        self_.primary_menu.prepend_section(None, accounts_menu);
+    }
+
     fn search(&self, query: String) {
         let self_ = imp::Sidebar::from_instance(self);
         self_.searched_chats.borrow_mut().clear();
         self_.searched_users.borrow_mut().clear();
         self_.already_searched_users.borrow_mut().clear();
 
+        // Forget about the messages found by the previous search
+        self_
+            .found_messages_search_id
+            .set(self_.found_messages_search_id.get() + 1);
+        self_.found_messages_total_count.set(0);
+        self_.is_loading_found_messages.set(false);
+        if let Some(found_messages) = self_.found_messages.get() {
+            found_messages.remove_all();
+        }
+
         if query.is_empty() {
             if let Some(filter) = self_.filter.borrow().as_ref() {
                 filter.changed(gtk::FilterChange::Different);
             }
         } else {
-            let client_id = self
+            let session = self
                 .session()
-                .expect("The session needs to be set to be able to search")
-                .client_id();
+                .expect("The session needs to be set to be able to search");
+            let client_id = session.client_id();
+            let chat_list = session.chat_list().clone();
 
             // Search chats
-            let query_clone = query.clone();
-            do_async(
-                glib::PRIORITY_DEFAULT_IDLE,
-                async move {
-                    functions::SearchChats::new()
-                        .query(query_clone)
-                        .limit(100)
-                        .send(client_id)
-                        .await
-                },
-                clone!(@weak self as obj => move |result| async move {
-                    if let Ok(enums::Chats::Chats(chats)) = result {
-                        let self_ = imp::Sidebar::from_instance(&obj);
+            chat_list.search(
+                query.clone(),
+                clone!(@weak self as obj, @weak chat_list => move |chat_ids| {
+                    let self_ = imp::Sidebar::from_instance(&obj);
 
-                        if let Some(filter) = self_.filter.borrow().as_ref() {
-                            let session = obj
-                                .session()
-                                .expect("The session needs to be set to be able to search");
-                            let chat_list = session.chat_list();
-
-                            self_.already_searched_users.borrow_mut().extend(chats.chat_ids.iter()
-                                .filter_map(|id| chat_list.get_chat(*id))
-                                .filter_map(|chat| match chat.type_() {
-                                    enums::ChatType::Private(types::ChatTypePrivate { user_id }) => Some(*user_id),
-                                    _ => None
-                                }
-                            ));
-
-                            self_.searched_chats.borrow_mut().extend(chats.chat_ids);
-                            filter.changed(gtk::FilterChange::Different);
-                        }
+                    if let Some(filter) = self_.filter.borrow().as_ref() {
+                        self_.already_searched_users.borrow_mut().extend(chat_ids.iter()
+                            .filter_map(|id| chat_list.get_chat(*id))
+                            .filter_map(|chat| match chat.type_() {
+                                enums::ChatType::Private(types::ChatTypePrivate { user_id }) => Some(*user_id),
+                                _ => None
+                            }
+                        ));
+
+                        self_.searched_chats.borrow_mut().extend(chat_ids);
+                        filter.changed(gtk::FilterChange::Different);
                     }
                 }),
             );
@@ -233,7 +280,100 @@ impl Sidebar {
                     }
                 }),
             );
+
+            // Search messages
+            self.search_messages();
+        }
+    }
+
+    /// Searches the messages matching the query in all the chats, continuing from the last
+    /// found message, if any.
+    fn search_messages(&self) {
+        let self_ = imp::Sidebar::from_instance(self);
+        let found_messages = match self_.found_messages.get() {
+            Some(found_messages) => found_messages,
+            None => return,
+        };
+        let query = self_.search_entry.text().to_string();
+
+        // The first item is the title of the section
+        let n_found_messages = found_messages.n_items().saturating_sub(1);
+
+        if query.is_empty()
+            || self_.is_loading_found_messages.get()
+            || (n_found_messages > 0
+                && n_found_messages as i32 >= self_.found_messages_total_count.get())
+        {
+            return;
         }
+
+        // Continue from the last found message
+        let (offset_date, offset_chat_id, offset_message_id) = if n_found_messages > 0 {
+            let message = found_messages
+                .item(n_found_messages)
+                .unwrap()
+                .downcast::<Message>()
+                .unwrap();
+            (message.date(), message.chat().id(), message.id())
+        } else {
+            (0, 0, 0)
+        };
+
+        let session = self.session().unwrap();
+        let client_id = session.client_id();
+        let search_id = self_.found_messages_search_id.get();
+
+        self_.is_loading_found_messages.set(true);
+
+        do_async(
+            glib::PRIORITY_DEFAULT_IDLE,
+            async move {
+                functions::SearchMessages::new()
+                    .query(query)
+                    .offset_date(offset_date)
+                    .offset_chat_id(offset_chat_id)
+                    .offset_message_id(offset_message_id)
+                    .limit(50)
+                    .send(client_id)
+                    .await
+            },
+            clone!(@weak self as obj, @weak session => move |result| async move {
+                let self_ = imp::Sidebar::from_instance(&obj);
+
+                // Ignore the results of an outdated search
+                if self_.found_messages_search_id.get() != search_id {
+                    return;
+                }
+
+                self_.is_loading_found_messages.set(false);
+
+                if let Ok(enums::Messages::Messages(result)) = result {
+                    let chat_list = session.chat_list();
+                    let messages: Vec<glib::Object> = result
+                        .messages
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|message| {
+                            chat_list
+                                .get_chat(message.chat_id)
+                                .map(|chat| Message::new(message, &chat).upcast())
+                        })
+                        .collect();
+
+                    if messages.is_empty() {
+                        return;
+                    }
+
+                    let found_messages = self_.found_messages.get().unwrap();
+                    if found_messages.n_items() == 0 {
+                        found_messages.append(&gtk::StringObject::new(&gettext("Messages")));
+                    }
+
+                    self_.found_messages_total_count.set(result.total_count);
+                    found_messages.splice(found_messages.n_items(), 0, &messages);
+                }
+            }),
+        );
     }
 
     fn selected_chat(&self) -> Option<Chat> {
@@ -272,9 +412,12 @@ impl Sidebar {
 
         if let Some(ref session) = session {
             // Merge ChatList and UserList into a single list model
+            // Merge ChatList, UserList and the found messages into a single list model
+            let found_messages = gio::ListStore::new(glib::Object::static_type());
             let list = gio::ListStore::new(gio::ListModel::static_type());
             list.append(session.chat_list());
             list.append(session.user_list());
+            list.append(&found_messages);
             let model = gtk::FlattenListModel::new(Some(&list));
 
             let filter = gtk::CustomFilter::new(
@@ -291,7 +434,8 @@ impl Sidebar {
                             !self_.already_searched_users.borrow().contains(&user.id())
                                 && self_.searched_users.borrow().contains(&user.id())
                         } else {
-                            false
+                            // Found messages and their section title
+                            true
                         }
                     } else if let Some(chat) = item.downcast_ref::<Chat>() {
                         chat.order() > 0
@@ -304,7 +448,8 @@ impl Sidebar {
                 let chat1 = obj1.downcast_ref::<Chat>();
                 let chat2 = obj2.downcast_ref::<Chat>();
 
-                // Always show chats first and then users
+                // Always show chats first, then users and then the found messages,
+                // which are already sorted
                 if let Some(chat1) = chat1 {
                     if let Some(chat2) = chat2 {
                         chat2.order().cmp(&chat1.order()).into()
@@ -314,7 +459,7 @@ impl Sidebar {
                 } else if chat2.is_some() {
                     gtk::Ordering::Larger
                 } else {
-                    gtk::Ordering::Equal
+                    obj2.is::<User>().cmp(&obj1.is::<User>()).into()
                 }
             });
 
@@ -335,6 +480,8 @@ impl Sidebar {
                     if let Some(item) = selection.selected_item() {
                         if let Some(chat) = item.downcast_ref::<Chat>() {
                             obj.set_selected_chat(Some(chat.to_owned()));
+                        } else if let Some(message) = item.downcast_ref::<Message>() {
+                            session.select_message(message.chat(), message.id());
                         } else if let Some(user) = item.downcast_ref::<User>() {
                             // Create a chat with the user and then select the created chat
                             let user_id = user.id();
@@ -366,6 +513,7 @@ impl Sidebar {
             self_.list_view.set_model(Some(&selection));
             self_.filter.replace(Some(filter));
             self_.selection.replace(Some(selection));
+            self_.found_messages.set(found_messages).unwrap();
         }
 
         self_.session.replace(session);
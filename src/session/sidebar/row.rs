@@ -1,15 +1,15 @@
 use gettextrs::gettext;
 use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate};
-use std::borrow::Cow;
-use tdgrand::enums::{CallDiscardReason, ChatType, InputMessageContent, MessageContent};
-use tdgrand::types::{DraftMessage, MessageCall};
+use tdgrand::enums::{ChatType, MessageSendingState};
 
 use crate::session::chat::{
     BoxedChatNotificationSettings, BoxedDraftMessage, Message, MessageSender,
 };
 use crate::session::sidebar::Avatar;
 use crate::session::{BoxedScopeNotificationSettings, Chat, Session, User};
-use crate::utils::{dim_and_escape, escape, human_friendly_duration};
+use crate::utils::{
+    escape, highlight_attributes, input_message_content_preview, message_content_preview,
+};
 
 mod imp {
     use super::*;
@@ -19,7 +19,7 @@ mod imp {
     #[derive(Debug, Default, CompositeTemplate)]
     #[template(resource = "/com/github/melix99/telegrand/ui/sidebar-row.ui")]
     pub struct Row {
-        /// A `Chat` or `User`
+        /// A `Chat`, `User`, `Message` or a `gtk::StringObject` for the section titles
         pub item: RefCell<Option<glib::Object>>,
         #[template_child]
         pub avatar: TemplateChild<Avatar>,
@@ -30,6 +30,8 @@ mod imp {
         #[template_child]
         pub title_label: TemplateChild<gtk::Label>,
         #[template_child]
+        pub status_image: TemplateChild<gtk::Image>,
+        #[template_child]
         pub timestamp_label: TemplateChild<gtk::Label>,
         #[template_child]
         pub message_label: TemplateChild<gtk::Label>,
@@ -125,8 +127,20 @@ impl Row {
 
         let self_ = imp::Row::from_instance(self);
 
+        let is_section_title = item
+            .as_ref()
+            .map(|item| item.is::<gtk::StringObject>())
+            .unwrap_or_default();
+        self_.avatar.set_visible(!is_section_title);
+        if is_section_title {
+            self_.title_label.add_css_class("dim-label");
+        } else {
+            self_.title_label.remove_css_class("dim-label");
+        }
+
         if let Some(ref item) = item {
             if let Some(chat) = item.downcast_ref::<Chat>() {
+                self_.avatar.set_item(Some(item.clone()));
                 self_.timestamp_label.set_visible(true);
                 self_.bottom_box.set_visible(true);
 
@@ -166,6 +180,16 @@ impl Row {
                     gtk::NONE_EXPRESSION,
                     "session",
                 );
+                let action_description_expression = gtk::PropertyExpression::new(
+                    Chat::static_type(),
+                    gtk::NONE_EXPRESSION,
+                    "action-description",
+                );
+                let last_read_outbox_message_id_expression = gtk::PropertyExpression::new(
+                    Chat::static_type(),
+                    gtk::NONE_EXPRESSION,
+                    "last-read-outbox-message-id",
+                );
 
                 // Title label bindings
                 title_expression.bind(&*self_.title_label, "label", Some(chat));
@@ -201,37 +225,51 @@ impl Row {
                 let timestamp_expression = gtk::ClosureExpression::new(
                     |args| -> String {
                         let date = args[1].get::<i32>().unwrap();
-
-                        let datetime_now = glib::DateTime::new_now_local().unwrap();
-                        let datetime = glib::DateTime::from_unix_utc(date as i64)
-                            .and_then(|t| t.to_local())
-                            .unwrap();
-
-                        let hours_difference = datetime_now.difference(&datetime) / 3600000000;
-                        let days_difference = hours_difference / 24;
-
-                        if hours_difference <= 16 {
-                            // Show the time
-                            let mut time = datetime.format("%X").unwrap().to_string();
-
-                            // Remove seconds
-                            time.replace_range(5..8, "");
-                            time
-                        } else if days_difference < 6 {
-                            // Show the day of the week
-                            datetime.format("%a").unwrap().to_string()
-                        } else if days_difference < 364 {
-                            // Show the day and the month
-                            datetime.format("%d %b").unwrap().to_string()
-                        } else {
-                            // Show the entire date
-                            datetime.format("%x").unwrap().to_string()
-                        }
+                        format_timestamp(date)
                     },
                     &[date_expression.upcast()],
                 );
                 timestamp_expression.bind(&*self_.timestamp_label, "label", Some(chat));
 
+                // Status image bindings
+                let status_visibility_expression = gtk::ClosureExpression::new(
+                    |args| {
+                        let has_draft_message =
+                            args[1].get::<BoxedDraftMessage>().unwrap().0.is_some();
+                        let is_outgoing = args[2]
+                            .get::<Message>()
+                            .map(|m| m.is_outgoing())
+                            .unwrap_or_default();
+                        !has_draft_message && is_outgoing
+                    },
+                    &[
+                        draft_message_expression.clone().upcast(),
+                        last_message_expression.clone().upcast(),
+                    ],
+                );
+                let status_icon_name_expression = gtk::ClosureExpression::new(
+                    |args| -> Option<String> {
+                        let message = args[1].get::<Message>().ok()?;
+                        let last_read_outbox_message_id = args[2].get::<i64>().unwrap();
+
+                        let icon_name = match message.sending_state().0 {
+                            Some(MessageSendingState::Pending) => "document-open-recent-symbolic",
+                            Some(MessageSendingState::Failed(_)) => "dialog-error-symbolic",
+                            None if last_read_outbox_message_id >= message.id() => {
+                                "message-read-symbolic"
+                            }
+                            None => "message-sent-symbolic",
+                        };
+                        Some(icon_name.to_string())
+                    },
+                    &[
+                        last_message_expression.clone().upcast(),
+                        last_read_outbox_message_id_expression.upcast(),
+                    ],
+                );
+                status_visibility_expression.bind(&*self_.status_image, "visible", Some(chat));
+                status_icon_name_expression.bind(&*self_.status_image, "icon-name", Some(chat));
+
                 // Last message and draft message label bindings
                 let content_expression = gtk::PropertyExpression::new(
                     Message::static_type(),
@@ -241,7 +279,16 @@ impl Row {
                 // FIXME: the sender name should be part of this expression.
                 let stringified_message_expression = gtk::ClosureExpression::new(
                     |args| {
-                        // Either, if there is a draft message, retrieve the content from it. ...
+                        // Show what the users are doing in the chat, if anything. ...
+                        let action_description = args[4].get::<&str>().unwrap();
+                        if !action_description.is_empty() {
+                            return format!(
+                                "<span foreground=\"#3584e4\">{}</span>",
+                                escape(action_description)
+                            );
+                        }
+
+                        // ... Or, if there is a draft message, retrieve the content from it. ...
                         args[1]
                             .get::<BoxedDraftMessage>()
                             .unwrap()
@@ -251,7 +298,10 @@ impl Row {
                                 format!(
                                     "<span foreground=\"#e01b24\">{}:</span> {}",
                                     gettext("Draft"),
-                                    stringify_draft_message(message)
+                                    input_message_content_preview(
+                                        &message.input_message_text,
+                                        true
+                                    )
                                 )
                             })
                             .unwrap_or_else(|| {
@@ -271,6 +321,7 @@ impl Row {
                         draft_message_expression.upcast(),
                         last_message_expression.upcast(),
                         content_expression.upcast(),
+                        action_description_expression.upcast(),
                     ],
                 );
                 stringified_message_expression.bind(&*self_.message_label, "label", Some(chat));
@@ -357,6 +408,8 @@ impl Row {
                 );
                 pin_visibility_expression.bind(&*self_.pin_icon, "visible", Some(chat));
             } else if let Some(user) = item.downcast_ref::<User>() {
+                self_.avatar.set_item(Some(item.clone()));
+                self_.status_image.set_visible(false);
                 self_.timestamp_label.set_visible(false);
                 self_.bottom_box.set_visible(false);
 
@@ -365,165 +418,108 @@ impl Row {
 
                 // Title label bindings
                 full_name_expression.bind(&*self_.title_label, "label", gtk::NONE_WIDGET);
+            } else if let Some(message) = item.downcast_ref::<Message>() {
+                // A message found by the messages search
+                let chat = message.chat();
+                self_.avatar.set_item(Some(chat.clone().upcast()));
+                self_.status_image.set_visible(false);
+                self_.pin_icon.set_visible(false);
+                self_.unread_count_label.set_visible(false);
+                self_.timestamp_label.set_visible(true);
+                self_.bottom_box.set_visible(true);
+
+                let title_expression = gtk::PropertyExpression::new(
+                    Chat::static_type(),
+                    gtk::NONE_EXPRESSION,
+                    "title",
+                );
+                title_expression.bind(&*self_.title_label, "label", Some(chat));
+
+                self_
+                    .timestamp_label
+                    .set_label(&format_timestamp(message.date()));
+                self_
+                    .message_label
+                    .set_label(&stringify_message(message.clone()));
+            } else if let Some(section_title) = item.downcast_ref::<gtk::StringObject>() {
+                self_.avatar.set_item(None);
+                self_.status_image.set_visible(false);
+                self_.timestamp_label.set_visible(false);
+                self_.bottom_box.set_visible(false);
+
+                self_.title_label.set_label(&section_title.string());
             } else {
                 unreachable!("Unexpected item type: {:?}", item);
             }
+        } else {
+            self_.avatar.set_item(None);
         }
 
         self_.item.replace(item);
         self.notify("item");
     }
+
+    /// Highlights the occurrences of the query in the message label.
+    pub fn highlight(&self, query: &str) {
+        let self_ = imp::Row::from_instance(self);
+        let attributes =
+            highlight_attributes(&self_.message_label.label(), query, &*self_.message_label);
+        self_.message_label.set_attributes(Some(&attributes));
+    }
+}
+
+fn format_timestamp(date: i32) -> String {
+    let datetime_now = glib::DateTime::new_now_local().unwrap();
+    let datetime = glib::DateTime::from_unix_utc(date as i64)
+        .and_then(|t| t.to_local())
+        .unwrap();
+
+    let hours_difference = datetime_now.difference(&datetime) / 3600000000;
+    let days_difference = hours_difference / 24;
+
+    if hours_difference <= 16 {
+        // Show the time
+        let mut time = datetime.format("%X").unwrap().to_string();
+
+        // Remove seconds
+        time.replace_range(5..8, "");
+        time
+    } else if days_difference < 6 {
+        // Show the day of the week
+        datetime.format("%a").unwrap().to_string()
+    } else if days_difference < 364 {
+        // Show the day and the month
+        datetime.format("%d %b").unwrap().to_string()
+    } else {
+        // Show the entire date
+        datetime.format("%x").unwrap().to_string()
+    }
 }
 
 fn stringify_message(message: Message) -> String {
-    let mut show_sender = match message.chat().type_() {
+    let session = message.chat().session();
+    let user_names: Vec<String> = message
+        .event_user_ids()
+        .into_iter()
+        .map(|user_id| {
+            escape(&stringify_user(
+                &session.user_list().get_or_create_user(user_id),
+                true,
+            ))
+        })
+        .collect();
+    let event_text = message.event_text(&escape(&sender_name(message.sender(), true)), &user_names);
+    if let Some(event_text) = event_text {
+        return event_text;
+    }
+
+    let show_sender = match message.chat().type_() {
         ChatType::BasicGroup(_) => true,
         ChatType::Supergroup(data) => !data.is_channel,
         ChatType::Private(_) | ChatType::Secret(_) => message.is_outgoing(),
     };
 
-    let text_content = match message.content().0 {
-        MessageContent::MessageText(data) => dim_and_escape(&data.text.text),
-        MessageContent::MessageBasicGroupChatCreate(_) => {
-            show_sender = false;
-            gettext!("{} created the group", sender_name(message.sender(), true))
-        }
-        MessageContent::MessageChatAddMembers(data) => {
-            show_sender = false;
-
-            if message.sender().as_user().map(User::id).as_ref() == data.member_user_ids.get(0) {
-                if message.is_outgoing() {
-                    gettext("You joined the group")
-                } else {
-                    gettext!("{} joined the group", sender_name(message.sender(), true))
-                }
-            } else {
-                let session = message.chat().session();
-                let user_list = session.user_list();
-
-                let members = data
-                    .member_user_ids
-                    .into_iter()
-                    .map(|user_id| user_list.get_or_create_user(user_id))
-                    .map(|user| stringify_user(&user, true))
-                    .collect::<Vec<_>>();
-
-                let (last_member, first_members) = members.split_last().unwrap();
-
-                gettext!(
-                    "{} added {}",
-                    sender_name(message.sender(), true),
-                    if first_members.is_empty() {
-                        Cow::Borrowed(last_member)
-                    } else {
-                        Cow::Owned(gettext!(
-                            "{} and {}",
-                            first_members.join(&gettext(", ")),
-                            last_member
-                        ))
-                    }
-                )
-            }
-        }
-        MessageContent::MessageChatDeleteMember(data) => {
-            show_sender = false;
-
-            if message
-                .sender()
-                .as_user()
-                .map(|user| user.id() == data.user_id)
-                .unwrap_or_default()
-            {
-                if message.is_outgoing() {
-                    gettext("You left the group")
-                } else {
-                    gettext!("{} left the group", sender_name(message.sender(), true))
-                }
-            } else {
-                gettext!(
-                    "{} removed {}",
-                    sender_name(message.sender(), true),
-                    stringify_user(
-                        &message
-                            .chat()
-                            .session()
-                            .user_list()
-                            .get_or_create_user(data.user_id),
-                        true
-                    )
-                )
-            }
-        }
-        MessageContent::MessageSticker(data) => {
-            format!("{} {}", data.sticker.emoji, gettext("Sticker"))
-        }
-        MessageContent::MessagePhoto(data) => stringify_message_photo(&data.caption.text),
-        MessageContent::MessageAudio(data) => {
-            stringify_message_audio(&data.audio.performer, &data.audio.title, &data.caption.text)
-        }
-        MessageContent::MessageAnimation(data) => stringify_message_animation(&data.caption.text),
-        MessageContent::MessageVideo(data) => stringify_message_video(&data.caption.text),
-        MessageContent::MessageDocument(data) => {
-            stringify_message_document(&data.document.file_name, &data.caption.text)
-        }
-        MessageContent::MessageVoiceNote(data) => stringify_message_voice_note(&data.caption.text),
-        MessageContent::MessageCall(data) => {
-            match data.discard_reason {
-                CallDiscardReason::Declined => {
-                    if message.is_outgoing() {
-                        // Telegram Desktop/Android labels declined outgoing calls just as
-                        // "Outgoing call" and puts a red arrow in the message bubble. We should be
-                        // more accurate here.
-                        if data.is_video {
-                            gettext("Declined outgoing video call")
-                        } else {
-                            gettext("Declined outgoing call")
-                        }
-                    // Telegram Android labels declined incoming calls as "Incoming call". Telegram
-                    // Desktop labels it as "Declined call" and is a bit inconsistent with outgoing
-                    // calls ^.
-                    } else if data.is_video {
-                        gettext("Declined incoming video call")
-                    } else {
-                        gettext("Declined incoming call")
-                    }
-                }
-                CallDiscardReason::Disconnected
-                | CallDiscardReason::HungUp
-                | CallDiscardReason::Empty => {
-                    stringify_made_message_call(message.is_outgoing(), data)
-                }
-                CallDiscardReason::Missed => {
-                    if message.is_outgoing() {
-                        gettext("Cancelled call")
-                    } else {
-                        gettext("Missed call")
-                    }
-                }
-            }
-        }
-        MessageContent::MessageChatDeletePhoto => {
-            show_sender = false;
-
-            match message.chat().type_() {
-                ChatType::Supergroup(data) if data.is_channel => gettext("Channel photo removed"),
-                _ => {
-                    if message.is_outgoing() {
-                        gettext("You removed the group photo")
-                    } else {
-                        gettext!(
-                            "{} removed the group photo",
-                            sender_name(message.sender(), true)
-                        )
-                    }
-                }
-            }
-        }
-        MessageContent::MessageContactRegistered => {
-            gettext!("{} joined Telegram", sender_name(message.sender(), true))
-        }
-        _ => gettext("Unsupported message"),
-    };
+    let text_content = message_content_preview(&message.content().0, true);
 
     if show_sender {
         let sender_name = if message.is_outgoing() {
@@ -538,135 +534,6 @@ fn stringify_message(message: Message) -> String {
     }
 }
 
-/// This method returns the text for all calls that have actually been made.
-/// This means that the called party has accepted the call.
-fn stringify_made_message_call(is_outgoing: bool, data: MessageCall) -> String {
-    if is_outgoing {
-        if data.duration > 0 {
-            if data.is_video {
-                gettext!(
-                    "Outgoing video call ({})",
-                    human_friendly_duration(data.duration)
-                )
-            } else {
-                gettext!("Outgoing call ({})", human_friendly_duration(data.duration))
-            }
-        } else if data.is_video {
-            gettext("Outgoing video call")
-        } else {
-            gettext("Outgoing call")
-        }
-    } else if data.duration > 0 {
-        if data.is_video {
-            gettext!(
-                "Incoming video call ({})",
-                human_friendly_duration(data.duration)
-            )
-        } else {
-            gettext!("Incoming call ({})", human_friendly_duration(data.duration))
-        }
-    } else if data.is_video {
-        gettext("Incoming video call")
-    } else {
-        gettext("Incoming call")
-    }
-}
-
-fn stringify_draft_message(message: &DraftMessage) -> String {
-    match &message.input_message_text {
-        InputMessageContent::InputMessageAnimation(data) => {
-            stringify_message_animation(&data.caption.text)
-        }
-        InputMessageContent::InputMessageAudio(data) => {
-            stringify_message_audio(&data.performer, &data.title, &data.caption.text)
-        }
-        InputMessageContent::InputMessageDocument(data) => {
-            stringify_message_document(&gettext("Document"), &data.caption.text)
-        }
-        InputMessageContent::InputMessagePhoto(data) => stringify_message_photo(&data.caption.text),
-        InputMessageContent::InputMessageSticker(_) => gettext("Sticker"),
-        InputMessageContent::InputMessageText(data) => dim_and_escape(&data.text.text),
-        InputMessageContent::InputMessageVideo(data) => stringify_message_video(&data.caption.text),
-        InputMessageContent::InputMessageVoiceNote(data) => {
-            stringify_message_voice_note(&data.caption.text)
-        }
-        _ => gettext("Unsupported message"),
-    }
-}
-
-fn stringify_message_animation(caption_text: &str) -> String {
-    format!(
-        "{}{}",
-        gettext("GIF"),
-        if caption_text.is_empty() {
-            String::new()
-        } else {
-            format!(", {}", dim_and_escape(caption_text))
-        }
-    )
-}
-
-fn stringify_message_audio(performer: &str, title: &str, caption_text: &str) -> String {
-    format!(
-        "{} - {}{}",
-        performer,
-        title,
-        if caption_text.is_empty() {
-            String::new()
-        } else {
-            format!(", {}", dim_and_escape(caption_text))
-        }
-    )
-}
-
-fn stringify_message_document(file_name: &str, caption_text: &str) -> String {
-    format!(
-        "{}{}",
-        file_name,
-        if caption_text.is_empty() {
-            String::new()
-        } else {
-            format!(", {}", dim_and_escape(caption_text))
-        }
-    )
-}
-
-fn stringify_message_photo(caption_text: &str) -> String {
-    format!(
-        "{}{}",
-        gettext("Photo"),
-        if caption_text.is_empty() {
-            String::new()
-        } else {
-            format!(", {}", dim_and_escape(caption_text))
-        }
-    )
-}
-
-fn stringify_message_video(caption_text: &str) -> String {
-    format!(
-        "{}{}",
-        gettext("Video"),
-        if caption_text.is_empty() {
-            String::new()
-        } else {
-            format!(", {}", dim_and_escape(caption_text))
-        }
-    )
-}
-
-fn stringify_message_voice_note(caption_text: &str) -> String {
-    format!(
-        "{}{}",
-        gettext("Voice message"),
-        if caption_text.is_empty() {
-            String::new()
-        } else {
-            format!(", {}", dim_and_escape(caption_text))
-        }
-    )
-}
-
 fn sender_name(sender: &MessageSender, use_full_name: bool) -> String {
     match sender {
         MessageSender::User(user) => stringify_user(user, use_full_name),
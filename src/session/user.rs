@@ -303,7 +303,7 @@ impl User {
         .upcast()
     }
 
-    fn status(&self) -> BoxedUserStatus {
+    pub fn status(&self) -> BoxedUserStatus {
         let self_ = imp::User::from_instance(self);
         self_.status.borrow().clone()
     }
@@ -1,15 +1,29 @@
 use gettextrs::gettext;
-use gtk::glib;
+use gtk::{gdk, glib, pango, prelude::*};
 use once_cell::sync::Lazy;
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
+use std::collections::BTreeMap;
 use std::future::Future;
-use tdgrand::enums::TextEntityType;
-use tdgrand::types::FormattedText;
+use std::ops::Range;
+use tdgrand::enums::{CallDiscardReason, InputMessageContent, MessageContent, TextEntityType};
+use tdgrand::types::{
+    FormattedText, MessageCall, TextEntity, TextEntityTypeMentionName, TextEntityTypePreCode,
+    TextEntityTypeTextUrl,
+};
 
 use crate::RUNTIME;
 
+/// The prefix of the links of the entities that are handled by the app, like mentions.
+pub const INTERNAL_URI_PREFIX: &str = "telegrand://";
+
 pub static PROTOCOL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\w+://").unwrap());
 
+/// A loose match of anything that looks like a link, like `https://example.com`,
+/// `www.example.com` or `example.com/page`.
+static LINK_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b(?:\w+://|www\.)\S|\b[\w-]+(?:\.[\w-]+)*\.[a-z]{2,}\b").unwrap()
+});
+
 pub fn escape(text: &str) -> String {
     text.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -35,7 +49,19 @@ pub fn linkify(text: &str) -> String {
     }
 }
 
-pub fn convert_to_markup(text: String, entity: &TextEntityType) -> String {
+/// Returns the link of an entity that is handled by the app, with its value percent-encoded.
+fn internal_uri(kind: &str, value: &str) -> String {
+    format!(
+        "{}{}/{}",
+        INTERNAL_URI_PREFIX,
+        kind,
+        glib::Uri::escape_string(value, None, false)
+    )
+}
+
+/// Converts the text of an entity to markup. The `text` can already contain the markup of
+/// nested entities, while `raw_text` is the original text of the entity.
+pub fn convert_to_markup(text: String, raw_text: &str, entity: &TextEntityType) -> String {
     match entity {
         TextEntityType::Url => format!("<a href='{}'>{}</a>", linkify(&text), text),
         TextEntityType::EmailAddress => format!("<a href='mailto:{0}'>{0}</a>", text),
@@ -48,6 +74,31 @@ pub fn convert_to_markup(text: String, entity: &TextEntityType) -> String {
             format!("<tt>{}</tt>", text)
         }
         TextEntityType::TextUrl(data) => format!("<a href='{}'>{}</a>", escape(&data.url), text),
+        TextEntityType::Mention => format!(
+            "<a href='{}'>{}</a>",
+            internal_uri("mention", raw_text.trim_start_matches('@')),
+            text
+        ),
+        TextEntityType::MentionName(data) => format!(
+            "<a href='{}'>{}</a>",
+            internal_uri("user", &data.user_id.to_string()),
+            text
+        ),
+        TextEntityType::Hashtag => format!(
+            "<a href='{}'>{}</a>",
+            internal_uri("hashtag", raw_text.trim_start_matches('#')),
+            text
+        ),
+        TextEntityType::Cashtag => format!(
+            "<a href='{}'>{}</a>",
+            internal_uri("cashtag", raw_text.trim_start_matches('$')),
+            text
+        ),
+        TextEntityType::BotCommand => format!(
+            "<a href='{}'>{}</a>",
+            internal_uri("command", raw_text.trim_start_matches('/')),
+            text
+        ),
         _ => text,
     }
 }
@@ -57,6 +108,8 @@ pub fn parse_formatted_text(formatted_text: FormattedText) -> String {
     let mut entity = entities.next();
     let mut output = String::new();
     let mut buffer = String::new();
+    // The text of the current entity, without any markup
+    let mut raw_buffer = String::new();
     let mut is_inside_entity = false;
 
     // This is the offset in utf16 code units of the text to parse. We need this variable
@@ -78,6 +131,9 @@ pub fn parse_formatted_text(formatted_text: FormattedText) -> String {
         }
 
         buffer.push(c);
+        if is_inside_entity {
+            raw_buffer.push(c);
+        }
         code_units_offset += c.len_utf16();
 
         if let Some(entity_) = entity {
@@ -91,7 +147,7 @@ pub fn parse_formatted_text(formatted_text: FormattedText) -> String {
                     match entity {
                         Some(entity) => {
                             if entity.offset == entity_.offset {
-                                buffer = convert_to_markup(buffer, &entity.r#type);
+                                buffer = convert_to_markup(buffer, &raw_buffer, &entity.r#type);
                             } else {
                                 break Some(entity);
                             }
@@ -100,8 +156,9 @@ pub fn parse_formatted_text(formatted_text: FormattedText) -> String {
                     }
                 };
 
-                output.push_str(&convert_to_markup(buffer, &entity_.r#type));
+                output.push_str(&convert_to_markup(buffer, &raw_buffer, &entity_.r#type));
                 buffer = String::new();
+                raw_buffer = String::new();
                 is_inside_entity = false;
             }
         }
@@ -115,6 +172,585 @@ pub fn parse_formatted_text(formatted_text: FormattedText) -> String {
     output
 }
 
+/// A part of a formatted text, which is either some text or a block of code.
+pub enum FormattedTextBlock {
+    Text(FormattedText),
+    Code { text: String, language: String },
+}
+
+/// Splits a formatted text in the blocks of code that it contains and the text around
+/// them, so that the blocks of code can be shown separately.
+pub fn split_code_blocks(formatted_text: FormattedText) -> Vec<FormattedTextBlock> {
+    // The text in utf16 code units, because tdlib uses them for the entities
+    let text: Vec<u16> = formatted_text.text.encode_utf16().collect();
+    let mut blocks = Vec::new();
+    let mut offset = 0;
+
+    for entity in &formatted_text.entities {
+        let language = match &entity.r#type {
+            TextEntityType::Pre => String::new(),
+            TextEntityType::PreCode(data) => data.language.clone(),
+            _ => continue,
+        };
+        let start = entity.offset as usize;
+        let end = ((entity.offset + entity.length) as usize).min(text.len());
+        if start < offset || start >= end {
+            continue;
+        }
+
+        blocks.extend(text_block(&formatted_text, &text, offset..start));
+        blocks.push(FormattedTextBlock::Code {
+            text: String::from_utf16_lossy(&text[start..end])
+                .trim_end_matches('\n')
+                .to_string(),
+            language,
+        });
+        offset = end;
+    }
+    blocks.extend(text_block(&formatted_text, &text, offset..text.len()));
+
+    blocks
+}
+
+/// Returns the part of a formatted text in the given range of utf16 code units, without
+/// the newlines around it and the blocks of code.
+fn text_block(
+    formatted_text: &FormattedText,
+    text: &[u16],
+    mut range: Range<usize>,
+) -> Option<FormattedTextBlock> {
+    let newline = '\n' as u16;
+    while range.start < range.end && text[range.start] == newline {
+        range.start += 1;
+    }
+    while range.start < range.end && text[range.end - 1] == newline {
+        range.end -= 1;
+    }
+    if range.is_empty() {
+        return None;
+    }
+
+    let entities = formatted_text
+        .entities
+        .iter()
+        .filter(|entity| {
+            !matches!(
+                entity.r#type,
+                TextEntityType::Pre | TextEntityType::PreCode(_)
+            )
+        })
+        .filter_map(|entity| {
+            let start = (entity.offset as usize).max(range.start);
+            let end = ((entity.offset + entity.length) as usize).min(range.end);
+
+            (start < end).then(|| TextEntity {
+                offset: (start - range.start) as i32,
+                length: (end - start) as i32,
+                r#type: entity.r#type.clone(),
+            })
+        })
+        .collect();
+
+    Some(FormattedTextBlock::Text(FormattedText {
+        text: String::from_utf16_lossy(&text[range]),
+        entities,
+    }))
+}
+
+/// The characters that can be escaped with a backslash in the markdown of the composer.
+const MARKDOWN_SPECIAL_CHARS: &[char] = &['\\', '*', '_', '~', '`', '[', ']', '(', ')'];
+
+/// The url of the markdown links that mention a user by name, followed by the user id.
+pub const MENTION_URL_PREFIX: &str = "tg://user?id=";
+
+/// A piece of markdown found in the text of the composer. The ranges are in chars.
+#[derive(Clone, Debug)]
+pub struct MarkdownSpan {
+    /// The formatting of the content, or `None` if the span is just an escaped character
+    pub entity_type: Option<TextEntityType>,
+    pub content: Range<usize>,
+    /// The ranges of the markers, like `**`, that are removed from the sent text
+    pub markers: Vec<Range<usize>>,
+}
+
+/// Finds the markdown spans in a text, so that they can be styled while composing.
+///
+/// The supported syntax is `**bold**`, `_italic_`, `__underline__`, `~~strikethrough~~`,
+/// `` `code` ``, ```` ```language\ncode``` ```` and `[text](url)`. Links to
+/// `tg://user?id=<user id>` are mentions of users by name. Any of the special characters
+/// can be escaped with a backslash.
+pub fn markdown_spans(text: &str) -> Vec<MarkdownSpan> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans = Vec::new();
+    parse_markdown_range(&chars, 0, chars.len(), &mut spans);
+    spans
+}
+
+fn parse_markdown_range(chars: &[char], start: usize, end: usize, spans: &mut Vec<MarkdownSpan>) {
+    let starts_with = |i: usize, pattern: &str| {
+        pattern
+            .chars()
+            .enumerate()
+            .all(|(n, c)| i + n < end && chars[i + n] == c)
+    };
+    let find = |from: usize, pattern: &str| (from..end).find(|i| starts_with(*i, pattern));
+    let is_word_char = |i: usize| chars.get(i).map_or(false, |c| c.is_alphanumeric());
+
+    let mut i = start;
+    'outer: while i < end {
+        if chars[i] == '\\' && i + 1 < end && MARKDOWN_SPECIAL_CHARS.contains(&chars[i + 1]) {
+            spans.push(MarkdownSpan {
+                entity_type: None,
+                content: i + 1..i + 2,
+                markers: vec![i..i + 1],
+            });
+            i += 2;
+            continue;
+        }
+
+        if starts_with(i, "```") {
+            if let Some(j) = find(i + 4, "```") {
+                // The first line can contain the language of the code
+                let mut content_start = i + 3;
+                let mut entity_type = TextEntityType::Pre;
+                if let Some(newline) = (content_start..j).find(|n| chars[*n] == '\n') {
+                    let language: String = chars[content_start..newline].iter().collect();
+                    if language
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || matches!(c, '+' | '-' | '#' | '_'))
+                    {
+                        if !language.is_empty() {
+                            entity_type =
+                                TextEntityType::PreCode(TextEntityTypePreCode { language });
+                        }
+                        content_start = newline + 1;
+                    }
+                }
+
+                if content_start < j {
+                    spans.push(MarkdownSpan {
+                        entity_type: Some(entity_type),
+                        content: content_start..j,
+                        markers: vec![i..content_start, j..j + 3],
+                    });
+                    i = j + 3;
+                    continue;
+                }
+            }
+        }
+
+        if chars[i] == '`' {
+            if let Some(j) = find(i + 2, "`") {
+                spans.push(MarkdownSpan {
+                    entity_type: Some(TextEntityType::Code),
+                    content: i + 1..j,
+                    markers: vec![i..i + 1, j..j + 1],
+                });
+                i = j + 1;
+                continue;
+            }
+        }
+
+        if chars[i] == '[' {
+            if let Some(j) = find(i + 2, "](") {
+                if let Some(k) = find(j + 3, ")") {
+                    let url: String = chars[j + 2..k].iter().collect();
+                    if !url.contains(char::is_whitespace) {
+                        let entity_type = match url
+                            .strip_prefix(MENTION_URL_PREFIX)
+                            .and_then(|user_id| user_id.parse().ok())
+                        {
+                            Some(user_id) => {
+                                TextEntityType::MentionName(TextEntityTypeMentionName { user_id })
+                            }
+                            None => TextEntityType::TextUrl(TextEntityTypeTextUrl { url }),
+                        };
+                        spans.push(MarkdownSpan {
+                            entity_type: Some(entity_type),
+                            content: i + 1..j,
+                            markers: vec![i..i + 1, j..k + 1],
+                        });
+                        parse_markdown_range(chars, i + 1, j, spans);
+                        i = k + 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        for (delimiter, entity_type) in &[
+            ("**", TextEntityType::Bold),
+            ("__", TextEntityType::Underline),
+            ("~~", TextEntityType::Strikethrough),
+            ("_", TextEntityType::Italic),
+        ] {
+            let delimiter = *delimiter;
+            if !starts_with(i, delimiter) {
+                continue;
+            }
+
+            // Underscores inside of words, like in snake_case or in `self.__init__`, are
+            // not markers
+            let len = delimiter.len();
+            let is_italic = delimiter == "_";
+            let is_underscore = delimiter.starts_with('_');
+            if is_underscore
+                && i > start
+                && (is_word_char(i - 1) || matches!(chars[i - 1], '_' | '.'))
+            {
+                continue;
+            }
+
+            let content_start = i + len;
+            if content_start >= end
+                || chars[content_start].is_whitespace()
+                || (is_italic && chars[content_start] == '_')
+            {
+                continue;
+            }
+
+            let mut from = content_start + 1;
+            while let Some(j) = find(from, delimiter) {
+                from = j + 1;
+
+                // Identifiers like `__init__` are dunder names rather than underlined words
+                let is_dunder_name = delimiter == "__"
+                    && chars[content_start..j]
+                        .iter()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '_');
+
+                if chars[j - 1].is_whitespace()
+                    || is_dunder_name
+                    || (is_underscore
+                        && (is_word_char(j + len) || chars.get(j + len) == Some(&'_')))
+                    || (is_italic && starts_with(j, "__"))
+                {
+                    continue;
+                }
+
+                spans.push(MarkdownSpan {
+                    entity_type: Some(entity_type.clone()),
+                    content: content_start..j,
+                    markers: vec![i..content_start, j..j + len],
+                });
+                parse_markdown_range(chars, content_start, j, spans);
+                i = j + len;
+                continue 'outer;
+            }
+        }
+
+        i += 1;
+    }
+}
+
+/// Converts the markdown of the composer to a formatted text, which is the inverse of
+/// `parse_formatted_text`.
+pub fn parse_markdown(text: &str) -> FormattedText {
+    let chars: Vec<char> = text.chars().collect();
+    let spans = markdown_spans(text);
+
+    let mut is_removed = vec![false; chars.len()];
+    for marker in spans.iter().flat_map(|span| span.markers.iter()) {
+        for n in marker.clone() {
+            is_removed[n] = true;
+        }
+    }
+
+    // The offsets in utf16 code units in the resulting text of every char of the
+    // markdown, because tdlib expects the entities to use them
+    let mut offsets = Vec::with_capacity(chars.len() + 1);
+    let mut output = String::new();
+    let mut offset = 0;
+    for (c, is_removed) in chars.iter().zip(is_removed) {
+        offsets.push(offset);
+        if !is_removed {
+            output.push(*c);
+            offset += c.len_utf16();
+        }
+    }
+    offsets.push(offset);
+
+    let mut entities: Vec<TextEntity> = spans
+        .into_iter()
+        .filter_map(|span| {
+            let offset = offsets[span.content.start];
+            let length = offsets[span.content.end] - offset;
+            let entity_type = span.entity_type?;
+
+            (length > 0).then(|| TextEntity {
+                offset: offset as i32,
+                length: length as i32,
+                r#type: entity_type,
+            })
+        })
+        .collect();
+    entities.sort_by_key(|entity| (entity.offset, -entity.length));
+
+    FormattedText {
+        text: output,
+        entities,
+    }
+}
+
+/// Returns whether the formatted text contains something that could have a link preview.
+pub fn contains_link(text: &FormattedText) -> bool {
+    text.entities
+        .iter()
+        .any(|entity| matches!(entity.r#type, TextEntityType::TextUrl(_)))
+        || LINK_RE.is_match(&text.text)
+}
+
+/// Escapes all the special characters of the markdown of the composer.
+pub fn escape_markdown(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        if MARKDOWN_SPECIAL_CHARS.contains(&c) {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+    output
+}
+
+/// Converts a formatted text to the markdown of the composer, so that it can be edited.
+pub fn formatted_text_to_markdown(formatted_text: &FormattedText) -> String {
+    let mut openings: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    let mut closings: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    let mut code_ranges = Vec::new();
+
+    for entity in &formatted_text.entities {
+        let (opening, closing) = match &entity.r#type {
+            TextEntityType::Bold => ("**".to_string(), "**".to_string()),
+            TextEntityType::Italic => ("_".to_string(), "_".to_string()),
+            TextEntityType::Underline => ("__".to_string(), "__".to_string()),
+            TextEntityType::Strikethrough => ("~~".to_string(), "~~".to_string()),
+            TextEntityType::Code => ("`".to_string(), "`".to_string()),
+            TextEntityType::Pre => ("```\n".to_string(), "```".to_string()),
+            TextEntityType::PreCode(data) => (format!("```{}\n", data.language), "```".to_string()),
+            TextEntityType::TextUrl(data) => ("[".to_string(), format!("]({})", data.url)),
+            TextEntityType::MentionName(data) => (
+                "[".to_string(),
+                format!("]({}{})", MENTION_URL_PREFIX, data.user_id),
+            ),
+            _ => continue,
+        };
+
+        let start = entity.offset as usize;
+        let end = (entity.offset + entity.length) as usize;
+        if matches!(
+            entity.r#type,
+            TextEntityType::Code | TextEntityType::Pre | TextEntityType::PreCode(_)
+        ) {
+            code_ranges.push(start..end);
+        }
+
+        openings.entry(start).or_default().push(opening);
+        closings.entry(end).or_default().push(closing);
+    }
+
+    let mut output = String::new();
+    let mut segment = String::new();
+    let mut segment_start = 0;
+    let mut offset = 0;
+
+    let flush = |output: &mut String, segment: &mut String, segment_start: usize| {
+        // Only escape the text that would be otherwise parsed as markdown
+        let is_code = code_ranges
+            .iter()
+            .any(|range| range.contains(&segment_start));
+        if is_code || markdown_spans(segment).is_empty() {
+            output.push_str(segment);
+        } else {
+            output.push_str(&escape_markdown(segment));
+        }
+        segment.clear();
+    };
+
+    for c in formatted_text
+        .text
+        .chars()
+        .map(Some)
+        .chain(std::iter::once(None))
+    {
+        if closings.contains_key(&offset) || openings.contains_key(&offset) {
+            flush(&mut output, &mut segment, segment_start);
+            segment_start = offset;
+
+            // Close the inner entities first
+            if let Some(markers) = closings.get(&offset) {
+                markers
+                    .iter()
+                    .rev()
+                    .for_each(|marker| output.push_str(marker));
+            }
+            if let Some(markers) = openings.get(&offset) {
+                markers.iter().for_each(|marker| output.push_str(marker));
+            }
+        }
+
+        if let Some(c) = c {
+            segment.push(c);
+            offset += c.len_utf16();
+        }
+    }
+    flush(&mut output, &mut segment, segment_start);
+
+    output
+}
+
+/// Returns a short description of a message content, to be used in previews like the
+/// sidebar or the message that is being replied to. If `markup` is true, the text is escaped
+/// and the user provided parts, like the caption, are dimmed.
+pub fn message_content_preview(content: &MessageContent, markup: bool) -> String {
+    match content {
+        MessageContent::MessageText(data) => preview_text(&data.text.text, markup),
+        MessageContent::MessagePhoto(data) => {
+            preview_with_caption(&gettext("Photo"), &data.caption.text, markup)
+        }
+        MessageContent::MessageSticker(data) => {
+            format!("{} {}", data.sticker.emoji, gettext("Sticker"))
+        }
+        MessageContent::MessageAnimation(data) => {
+            preview_with_caption(&gettext("GIF"), &data.caption.text, markup)
+        }
+        MessageContent::MessageVideo(data) => {
+            preview_with_caption(&gettext("Video"), &data.caption.text, markup)
+        }
+        MessageContent::MessageAudio(data) => preview_with_caption(
+            &format!("{} - {}", data.audio.performer, data.audio.title),
+            &data.caption.text,
+            markup,
+        ),
+        MessageContent::MessageDocument(data) => {
+            preview_with_caption(&data.document.file_name, &data.caption.text, markup)
+        }
+        MessageContent::MessageVoiceNote(data) => {
+            preview_with_caption(&gettext("Voice message"), &data.caption.text, markup)
+        }
+        _ => gettext("Unsupported message"),
+    }
+}
+
+/// Same as `message_content_preview`, but for the content of a draft message.
+pub fn input_message_content_preview(content: &InputMessageContent, markup: bool) -> String {
+    match content {
+        InputMessageContent::InputMessageText(data) => preview_text(&data.text.text, markup),
+        InputMessageContent::InputMessagePhoto(data) => {
+            preview_with_caption(&gettext("Photo"), &data.caption.text, markup)
+        }
+        InputMessageContent::InputMessageSticker(_) => gettext("Sticker"),
+        InputMessageContent::InputMessageAnimation(data) => {
+            preview_with_caption(&gettext("GIF"), &data.caption.text, markup)
+        }
+        InputMessageContent::InputMessageVideo(data) => {
+            preview_with_caption(&gettext("Video"), &data.caption.text, markup)
+        }
+        InputMessageContent::InputMessageAudio(data) => preview_with_caption(
+            &format!("{} - {}", data.performer, data.title),
+            &data.caption.text,
+            markup,
+        ),
+        InputMessageContent::InputMessageDocument(data) => {
+            preview_with_caption(&gettext("Document"), &data.caption.text, markup)
+        }
+        InputMessageContent::InputMessageVoiceNote(data) => {
+            preview_with_caption(&gettext("Voice message"), &data.caption.text, markup)
+        }
+        _ => gettext("Unsupported message"),
+    }
+}
+
+fn preview_text(text: &str, markup: bool) -> String {
+    if markup {
+        dim_and_escape(text)
+    } else {
+        text.to_owned()
+    }
+}
+
+fn preview_with_caption(label: &str, caption_text: &str, markup: bool) -> String {
+    let label = if markup {
+        escape(label)
+    } else {
+        label.to_owned()
+    };
+
+    if caption_text.is_empty() {
+        label
+    } else {
+        format!("{}, {}", label, preview_text(caption_text, markup))
+    }
+}
+
+/// Returns the description of a call, like "Outgoing call (2 min)".
+pub fn message_call_description(is_outgoing: bool, data: MessageCall) -> String {
+    match data.discard_reason {
+        CallDiscardReason::Declined => {
+            if is_outgoing {
+                // Telegram Desktop/Android labels declined outgoing calls just as
+                // "Outgoing call" and puts a red arrow in the message bubble. We should be
+                // more accurate here.
+                if data.is_video {
+                    gettext("Declined outgoing video call")
+                } else {
+                    gettext("Declined outgoing call")
+                }
+            // Telegram Android labels declined incoming calls as "Incoming call". Telegram
+            // Desktop labels it as "Declined call" and is a bit inconsistent with outgoing
+            // calls ^.
+            } else if data.is_video {
+                gettext("Declined incoming video call")
+            } else {
+                gettext("Declined incoming call")
+            }
+        }
+        CallDiscardReason::Disconnected | CallDiscardReason::HungUp | CallDiscardReason::Empty => {
+            stringify_made_message_call(is_outgoing, data)
+        }
+        CallDiscardReason::Missed => {
+            if is_outgoing {
+                gettext("Cancelled call")
+            } else {
+                gettext("Missed call")
+            }
+        }
+    }
+}
+
+/// This method returns the text for all calls that have actually been made.
+/// This means that the called party has accepted the call.
+fn stringify_made_message_call(is_outgoing: bool, data: MessageCall) -> String {
+    if is_outgoing {
+        if data.duration > 0 {
+            if data.is_video {
+                gettext!(
+                    "Outgoing video call ({})",
+                    human_friendly_duration(data.duration)
+                )
+            } else {
+                gettext!("Outgoing call ({})", human_friendly_duration(data.duration))
+            }
+        } else if data.is_video {
+            gettext("Outgoing video call")
+        } else {
+            gettext("Outgoing call")
+        }
+    } else if data.duration > 0 {
+        if data.is_video {
+            gettext!(
+                "Incoming video call ({})",
+                human_friendly_duration(data.duration)
+            )
+        } else {
+            gettext!("Incoming call ({})", human_friendly_duration(data.duration))
+        }
+    } else if data.is_video {
+        gettext("Incoming video call")
+    } else {
+        gettext("Incoming call")
+    }
+}
+
 pub fn human_friendly_duration(mut seconds: i32) -> String {
     let hours = seconds / (60 * 60);
     if hours > 0 {
@@ -141,6 +777,62 @@ pub fn human_friendly_duration(mut seconds: i32) -> String {
     }
 }
 
+/// Returns the attributes that highlight the occurrences of the query in the text of the
+/// given markup, using the accent colors of the theme of the widget.
+pub fn highlight_attributes(
+    markup: &str,
+    query: &str,
+    widget: &impl IsA<gtk::Widget>,
+) -> pango::AttrList {
+    let attributes = pango::AttrList::new();
+
+    if query.is_empty() {
+        return attributes;
+    }
+
+    let style_context = widget.style_context();
+    let background = style_context
+        .lookup_color("accent_bg_color")
+        .unwrap_or_else(|| gdk::RGBA::new(0.208, 0.518, 0.894, 1.0));
+    let foreground = style_context
+        .lookup_color("accent_fg_color")
+        .unwrap_or_else(|| gdk::RGBA::new(1.0, 1.0, 1.0, 1.0));
+
+    if let Ok((_, text, _)) = pango::parse_markup(markup, '\0') {
+        let regex = RegexBuilder::new(&regex::escape(query))
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+
+        for found in regex.find_iter(&text) {
+            let mut background_attribute = pango::Attribute::new_background(
+                pango_color(background.red()),
+                pango_color(background.green()),
+                pango_color(background.blue()),
+            );
+            background_attribute.set_start_index(found.start() as u32);
+            background_attribute.set_end_index(found.end() as u32);
+            attributes.insert(background_attribute);
+
+            let mut foreground_attribute = pango::Attribute::new_foreground(
+                pango_color(foreground.red()),
+                pango_color(foreground.green()),
+                pango_color(foreground.blue()),
+            );
+            foreground_attribute.set_start_index(found.start() as u32);
+            foreground_attribute.set_end_index(found.end() as u32);
+            attributes.insert(foreground_attribute);
+        }
+    }
+
+    attributes
+}
+
+/// Converts a color channel from the `0.0..=1.0` range of gdk to the range of pango.
+fn pango_color(channel: f32) -> u16 {
+    (channel.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
 // Function from https://gitlab.gnome.org/GNOME/fractal/-/blob/fractal-next/src/utils.rs
 pub fn do_async<
     R: Send + 'static,
@@ -1,7 +1,9 @@
 use gettextrs::gettext;
 use glib::{clone, SyncSender};
 use gtk::{gdk, gio, glib, prelude::*, subclass::prelude::*, CompositeTemplate};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tdgrand::enums::{
     self, AuthorizationState, ChatType, MessageContent, MessageSender as TelegramMessageSender,
@@ -12,16 +14,29 @@ use tdgrand::types::{self, Message as TelegramMessage};
 use tokio::task;
 
 use crate::config::{APP_ID, PROFILE};
+use crate::login::tdlib_parameters;
 use crate::session::Chat;
+use crate::utils::do_async;
 use crate::Application;
 use crate::Session;
-use crate::RUNTIME;
+use crate::{DATA_DIR, RUNTIME};
+
+#[derive(Debug)]
+pub struct Client {
+    /// The directory of the TDLib database of this client
+    pub database_directory: PathBuf,
+    /// The session of this client, available once it's logged in
+    pub session: Option<Session>,
+    /// Whether the database must be removed when the client is closed, because it logged
+    /// out or its login has been cancelled
+    pub remove_database: bool,
+}
 
 mod imp {
     use super::*;
     use adw::subclass::prelude::AdwApplicationWindowImpl;
     use std::cell::{Cell, RefCell};
-    use std::collections::HashMap;
+    use std::collections::{HashMap, VecDeque};
 
     use crate::Login;
 
@@ -31,8 +46,14 @@ mod imp {
         pub settings: gio::Settings,
         pub receiver_handle: RefCell<Option<task::JoinHandle<()>>>,
         pub receiver_should_stop: Arc<AtomicBool>,
-        pub clients: RefCell<HashMap<i32, Option<Session>>>,
+        /// The number of clients that the receiver waits to be closed before stopping
+        pub open_clients: Arc<AtomicUsize>,
+        pub clients: RefCell<HashMap<i32, Client>>,
         pub active_client_id: Cell<i32>,
+        /// The restored clients that need the user to log in again, waiting for the login
+        /// to be available
+        pub pending_login_client_ids: RefCell<VecDeque<i32>>,
+        pub accounts_menu: gio::Menu,
         #[template_child]
         pub main_stack: TemplateChild<gtk::Stack>,
         #[template_child]
@@ -50,8 +71,11 @@ mod imp {
                 settings: gio::Settings::new(APP_ID),
                 receiver_handle: RefCell::default(),
                 receiver_should_stop: Arc::default(),
+                open_clients: Arc::default(),
                 clients: RefCell::default(),
                 active_client_id: Cell::default(),
+                pending_login_client_ids: RefCell::default(),
+                accounts_menu: gio::Menu::new(),
                 main_stack: TemplateChild::default(),
                 login: TemplateChild::default(),
             }
@@ -92,8 +116,30 @@ mod imp {
             self.login.connect_new_session(
                 clone!(@weak obj => move |login| obj.create_session(login.client_id())),
             );
-
-            obj.create_client();
+            self.login.connect_cancel(clone!(@weak obj => move |login| {
+                obj.close_client(login.client_id());
+            }));
+
+            // The state of this action is the id of the active client, so that the
+            // accounts menu can show which account is currently selected
+            let switch_account_action = gio::SimpleAction::new_stateful(
+                "switch-account",
+                Some(&i32::static_variant_type()),
+                &0.to_variant(),
+            );
+            switch_account_action.connect_activate(clone!(@weak obj => move |_, parameter| {
+                let client_id = parameter.unwrap().get::<i32>().unwrap();
+                obj.switch_account(client_id);
+            }));
+            obj.add_action(&switch_account_action);
+
+            let add_account_action = gio::SimpleAction::new("add-account", None);
+            add_account_action.connect_activate(clone!(@weak obj => move |_, _| {
+                obj.add_account();
+            }));
+            obj.add_action(&add_account_action);
+
+            obj.restore_clients();
             obj.start_receiver();
 
             // Set the online state of the active client based on
@@ -101,18 +147,7 @@ mod imp {
             obj.connect_is_active_notify(move |obj| {
                 let self_ = imp::Window::from_instance(obj);
                 let client_id = self_.active_client_id.get();
-                let window_is_active = obj.is_active();
-
-                RUNTIME.spawn(async move {
-                    functions::SetOption::new()
-                        .name("online".to_string())
-                        .value(enums::OptionValue::Boolean(types::OptionValueBoolean {
-                            value: window_is_active,
-                        }))
-                        .send(client_id)
-                        .await
-                        .unwrap();
-                });
+                set_online(client_id, obj.is_active());
             });
         }
     }
@@ -121,6 +156,10 @@ mod imp {
     impl WindowImpl for Window {
         // Save window state on delete event
         fn close_request(&self, obj: &Self::Type) -> gtk::Inhibit {
+            // Wait for all the clients to be closed, so that TDLib can finish writing
+            // their databases
+            let n_clients = self.clients.borrow().len();
+            self.open_clients.store(n_clients, Ordering::Release);
             self.receiver_should_stop.store(true, Ordering::Release);
 
             obj.close_clients();
@@ -149,14 +188,73 @@ impl Window {
         glib::Object::new(&[("application", app)]).expect("Failed to create Window")
     }
 
-    fn create_client(&self) {
+    /// Creates a client for every database directory found in the data directory, or a
+    /// new one to log in if there are no saved accounts yet. The clients that are still
+    /// authorized are restored in the background, while the others wait for their turn
+    /// to use the login.
+    fn restore_clients(&self) {
+        let data_dir = DATA_DIR.get().unwrap();
+
+        let mut database_directories = fs::read_dir(data_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_dir())
+                    .filter_map(|entry| {
+                        let file_name = entry.file_name();
+                        let index = file_name
+                            .to_str()?
+                            .strip_prefix("db")?
+                            .parse::<u32>()
+                            .ok()?;
+                        Some((index, entry.path()))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        database_directories.sort_by_key(|(index, _)| *index);
+
+        if database_directories.is_empty() {
+            self.add_account();
+            return;
+        }
+
+        for (_, database_directory) in database_directories {
+            self.create_client(database_directory);
+        }
+    }
+
+    /// Returns the first `dbN` directory of the data directory that's not already used.
+    fn new_database_directory(&self) -> PathBuf {
+        let self_ = imp::Window::from_instance(self);
+        let data_dir = DATA_DIR.get().unwrap();
+        let clients = self_.clients.borrow();
+
+        (0..)
+            .map(|index| data_dir.join(format!("db{}", index)))
+            .find(|path| {
+                !path.exists()
+                    && !clients
+                        .values()
+                        .any(|client| &client.database_directory == path)
+            })
+            .unwrap()
+    }
+
+    /// Creates a client using the given database directory and returns its id. The client
+    /// is authorized in the background, unless it's passed to the login.
+    fn create_client(&self, database_directory: PathBuf) -> i32 {
         let client_id = tdgrand::create_client();
 
         let self_ = imp::Window::from_instance(self);
-        self_.clients.borrow_mut().insert(client_id, None);
-        self_.active_client_id.set(client_id);
-        self_.login.login_client(client_id);
-        self_.main_stack.set_visible_child(&self_.login.get());
+        self_.clients.borrow_mut().insert(
+            client_id,
+            Client {
+                database_directory,
+                session: None,
+                remove_database: false,
+            },
+        );
 
         // This call is important for login because TDLib requires the clients
         // to do at least a request to start receiving updates.
@@ -177,6 +275,146 @@ impl Window {
                 .await
                 .unwrap();
         });
+
+        client_id
+    }
+
+    /// Lets the user log in the client using the login, showing it if no other account
+    /// is shown.
+    fn login_client(&self, client_id: i32) {
+        let self_ = imp::Window::from_instance(self);
+        let database_directory = match self_.clients.borrow().get(&client_id) {
+            Some(client) => client
+                .database_directory
+                .to_str()
+                .expect("Data directory path is not a valid unicode string")
+                .to_owned(),
+            None => return,
+        };
+
+        // The login can be cancelled if there are other accounts to switch to
+        let cancellable = self_.clients.borrow().len() > 1;
+        self_
+            .login
+            .login_client(client_id, database_directory, cancellable);
+
+        if self.session(self_.active_client_id.get()).is_none() {
+            self_.active_client_id.set(client_id);
+            self_.main_stack.set_visible_child(&self_.login.get());
+        }
+    }
+
+    /// Returns whether the login is not used by any client that still needs to log in.
+    fn is_login_available(&self) -> bool {
+        let self_ = imp::Window::from_instance(self);
+        self_
+            .clients
+            .borrow()
+            .get(&self_.login.client_id())
+            .map_or(true, |client| client.session.is_some())
+    }
+
+    /// Authorizes a client that's restored in the background, passing it to the login
+    /// only if the user needs to log in again.
+    fn handle_restored_authorization_state(
+        &self,
+        authorization_state: AuthorizationState,
+        client_id: i32,
+    ) {
+        let self_ = imp::Window::from_instance(self);
+
+        match authorization_state {
+            AuthorizationState::WaitTdlibParameters => {
+                let database_directory = match self_.clients.borrow().get(&client_id) {
+                    Some(client) => client.database_directory.to_string_lossy().into_owned(),
+                    None => return,
+                };
+                let parameters = tdlib_parameters(database_directory, false);
+
+                RUNTIME.spawn(async move {
+                    if let Err(e) = functions::SetTdlibParameters::new()
+                        .parameters(parameters)
+                        .send(client_id)
+                        .await
+                    {
+                        log::warn!("Failed to set the parameters of a client: {:?}", e);
+                    }
+                });
+            }
+            AuthorizationState::WaitEncryptionKey(_) => {
+                RUNTIME.spawn(async move {
+                    if let Err(e) = functions::CheckDatabaseEncryptionKey::new()
+                        .encryption_key(String::new())
+                        .send(client_id)
+                        .await
+                    {
+                        log::warn!("Failed to check the encryption key of a client: {:?}", e);
+                    }
+                });
+            }
+            AuthorizationState::Ready => {
+                if self.session(client_id).is_none() {
+                    self.create_session(client_id);
+                }
+            }
+            AuthorizationState::WaitPhoneNumber
+            | AuthorizationState::WaitCode(_)
+            | AuthorizationState::WaitRegistration(_)
+            | AuthorizationState::WaitPassword(_)
+            | AuthorizationState::WaitOtherDeviceConfirmation(_) => {
+                if self.is_login_available() {
+                    self.login_client(client_id);
+                    self_.login.set_authorization_state(authorization_state);
+                } else {
+                    let mut pending_login_client_ids = self_.pending_login_client_ids.borrow_mut();
+                    if !pending_login_client_ids.contains(&client_id) {
+                        pending_login_client_ids.push_back(client_id);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Passes the next restored client that needs to log in to the login, if any.
+    fn login_next_pending_client(&self) -> bool {
+        let self_ = imp::Window::from_instance(self);
+        let client_id = self_.pending_login_client_ids.borrow_mut().pop_front();
+
+        match client_id {
+            Some(client_id) => {
+                self.login_client(client_id);
+
+                // Ask the current authorization state again, so that the login shows it
+                do_async(
+                    glib::PRIORITY_DEFAULT_IDLE,
+                    async move {
+                        functions::GetAuthorizationState::new()
+                            .send(client_id)
+                            .await
+                    },
+                    clone!(@weak self as obj => move |result| async move {
+                        let self_ = imp::Window::from_instance(&obj);
+                        if let Ok(authorization_state) = result {
+                            if self_.login.client_id() == client_id {
+                                self_.login.set_authorization_state(authorization_state);
+                            }
+                        }
+                    }),
+                );
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn close_client(&self, client_id: i32) {
+        self.set_remove_database(client_id);
+
+        RUNTIME.spawn(async move {
+            functions::Close::new().send(client_id).await.unwrap();
+        });
     }
 
     fn close_clients(&self) {
@@ -204,21 +442,33 @@ impl Window {
     fn start_receiver(&self) {
         let self_ = imp::Window::from_instance(self);
         let receiver_should_stop = self_.receiver_should_stop.clone();
+        let open_clients = self_.open_clients.clone();
         let sender = Arc::new(self.create_update_sender());
         let handle = RUNTIME.spawn(async move {
             loop {
                 let receiver_should_stop = receiver_should_stop.clone();
+                let open_clients = open_clients.clone();
                 let sender = sender.clone();
                 let stop = task::spawn_blocking(move || {
-                    if let Some((update, client_id)) = tdgrand::receive() {
-                        if receiver_should_stop.load(Ordering::Acquire) {
-                            if let Update::AuthorizationState(ref update) = update {
-                                if let AuthorizationState::Closed = update.authorization_state {
-                                    return true;
-                                }
+                    let received = tdgrand::receive();
+
+                    // While the window is closing, the updates aren't handled anymore and
+                    // the receiver only waits for the last client to be closed
+                    if receiver_should_stop.load(Ordering::Acquire) {
+                        if let Some((Update::AuthorizationState(ref update), _)) = received {
+                            if let AuthorizationState::Closed = update.authorization_state {
+                                let _ = open_clients.fetch_update(
+                                    Ordering::AcqRel,
+                                    Ordering::Acquire,
+                                    |n| n.checked_sub(1),
+                                );
                             }
                         }
 
+                        return open_clients.load(Ordering::Acquire) == 0;
+                    }
+
+                    if let Some((update, client_id)) = received {
                         sender.send((update, client_id)).unwrap();
                     }
 
@@ -268,48 +518,133 @@ impl Window {
         let self_ = imp::Window::from_instance(self);
 
         match update {
-            Update::AuthorizationState(update) => {
-                if let AuthorizationState::Closed = update.authorization_state {
-                    let session = self_.clients.borrow_mut().remove(&client_id).unwrap();
-                    if let Some(session) = session {
-                        self_.main_stack.remove(&session);
+            Update::AuthorizationState(update) => match update.authorization_state {
+                AuthorizationState::Closed => self.remove_client(client_id),
+                authorization_state => {
+                    if let AuthorizationState::LoggingOut = authorization_state {
+                        self.set_remove_database(client_id);
                     }
 
-                    self.create_client();
-                } else {
-                    self_
-                        .login
-                        .set_authorization_state(update.authorization_state);
+                    if self_.login.client_id() != client_id {
+                        self.handle_restored_authorization_state(authorization_state, client_id);
+                        return;
+                    }
+
+                    // Show the login if it needs some input from the user. This can happen
+                    // while restoring an account that isn't authorized anymore.
+                    if matches!(
+                        authorization_state,
+                        AuthorizationState::WaitPhoneNumber
+                            | AuthorizationState::WaitCode(_)
+                            | AuthorizationState::WaitRegistration(_)
+                            | AuthorizationState::WaitPassword(_)
+                            | AuthorizationState::WaitOtherDeviceConfirmation(_)
+                    ) {
+                        self_.active_client_id.set(client_id);
+                        self_.main_stack.set_visible_child(&self_.login.get());
+                    }
+
+                    self_.login.set_authorization_state(authorization_state);
                 }
-            }
+            },
             Update::NotificationGroup(update) => {
                 self.add_notifications(update.added_notifications, client_id, update.chat_id);
 
                 let app = self.application().unwrap();
                 for notification_id in update.removed_notification_ids {
-                    app.withdraw_notification(&notification_id.to_string());
+                    app.withdraw_notification(&notification_key(client_id, notification_id));
                 }
             }
             _ => {
-                if let Some(Some(session)) = self_.clients.borrow().get(&client_id) {
+                if let Some(session) = self.session(client_id) {
                     session.handle_update(update);
                 }
             }
         }
     }
 
+    fn remove_client(&self, client_id: i32) {
+        let self_ = imp::Window::from_instance(self);
+
+        let client = match self_.clients.borrow_mut().remove(&client_id) {
+            Some(client) => client,
+            None => return,
+        };
+
+        if let Some(ref session) = client.session {
+            self_.main_stack.remove(session);
+        }
+
+        // The client can also be closed because of a fatal error, so only remove the
+        // database when it's not needed anymore
+        if client.remove_database {
+            if let Err(e) = fs::remove_dir_all(&client.database_directory) {
+                log::warn!(
+                    "Failed to remove the database directory {:?}: {}",
+                    client.database_directory,
+                    e
+                );
+            }
+        }
+
+        self_
+            .pending_login_client_ids
+            .borrow_mut()
+            .retain(|id| *id != client_id);
+
+        self.update_accounts_menu();
+
+        // Let the next restored account that needs it use the login
+        if self_.login.client_id() == client_id {
+            self.login_next_pending_client();
+        }
+
+        if self_.active_client_id.get() == client_id {
+            let other_client_id = self_
+                .clients
+                .borrow()
+                .iter()
+                .find(|(_, client)| client.session.is_some())
+                .map(|(client_id, _)| *client_id);
+
+            if let Some(other_client_id) = other_client_id {
+                self.switch_account(other_client_id);
+            } else if self_.clients.borrow().is_empty() {
+                // There are no accounts left, not even being restored
+                self.add_account();
+            }
+        }
+    }
+
+    fn set_remove_database(&self, client_id: i32) {
+        let self_ = imp::Window::from_instance(self);
+        if let Some(client) = self_.clients.borrow_mut().get_mut(&client_id) {
+            client.remove_database = true;
+        }
+    }
+
     fn add_notifications(
         &self,
         notifications: Vec<types::Notification>,
         client_id: i32,
         chat_id: i64,
     ) {
-        let self_ = imp::Window::from_instance(self);
-
-        if let Some(Some(session)) = self_.clients.borrow().get(&client_id) {
+        if let Some(session) = self.session(client_id) {
             let app = self.application().unwrap();
             let chat = session.chat_list().get_chat(chat_id).unwrap();
 
+            // Tell the user which account the notifications are for, but only if there
+            // are multiple accounts logged in
+            let account_name = if self.n_sessions() > 1 {
+                session.me().map(|me| {
+                    format!("{} {}", me.first_name(), me.last_name())
+                        .trim()
+                        .to_owned()
+                })
+            } else {
+                None
+            };
+
             for notification in notifications {
                 let notification_id = notification.id;
                 let notification = match notification.r#type {
@@ -325,14 +660,18 @@ impl Window {
                             title.insert_str(0, &format!("{} – ", sender_name));
                         }
 
-                        let notification = gio::Notification::new(&title);
+                        let notification =
+                            gio::Notification::new(&account_title(title, account_name.as_deref()));
                         notification.set_body(Some(&body));
 
                         Some(notification)
                     }
                     enums::NotificationType::NewCall(_) => {
                         let body = gettext("Incoming call");
-                        let notification = gio::Notification::new(&chat.title());
+                        let notification = gio::Notification::new(&account_title(
+                            chat.title(),
+                            account_name.as_deref(),
+                        ));
                         notification.set_body(Some(&body));
 
                         Some(notification)
@@ -341,7 +680,10 @@ impl Window {
                 };
 
                 if let Some(notification) = notification {
-                    app.send_notification(Some(&notification_id.to_string()), &notification);
+                    app.send_notification(
+                        Some(&notification_key(client_id, notification_id)),
+                        &notification,
+                    );
                 }
             }
         }
@@ -351,9 +693,34 @@ impl Window {
         let self_ = imp::Window::from_instance(self);
         let session = Session::new(client_id);
 
+        session.set_accounts_menu(self_.accounts_menu.upcast_ref());
+        session.connect_notify_local(
+            Some("me"),
+            clone!(@weak self as obj => move |_, _| {
+                obj.update_accounts_menu();
+            }),
+        );
+
         self_.main_stack.add_child(&session);
-        self_.main_stack.set_visible_child(&session);
-        self_.clients.borrow_mut().insert(client_id, Some(session));
+        if let Some(client) = self_.clients.borrow_mut().get_mut(&client_id) {
+            client.session = Some(session);
+        }
+
+        // Switch to the new session, unless it's being restored in the background
+        // while another account or the login is already shown
+        let active_client_id = self_.active_client_id.get();
+        let is_restoring =
+            active_client_id != client_id && self_.clients.borrow().contains_key(&active_client_id);
+        if !is_restoring {
+            self.switch_account(client_id);
+        }
+
+        self.update_accounts_menu();
+
+        // Let a restored account that needs it use the login, now that it's free
+        if self_.login.client_id() == client_id {
+            self.login_next_pending_client();
+        }
 
         // Enable notifications for this client
         RUNTIME.spawn(async move {
@@ -397,11 +764,138 @@ impl Window {
 
     fn begin_chats_search(&self) {
         let self_ = imp::Window::from_instance(self);
-        let active_client_id = self_.active_client_id.get();
-        if let Some(session) = self_.clients.borrow().get(&active_client_id).unwrap() {
+        if let Some(session) = self.session(self_.active_client_id.get()) {
             session.begin_chats_search();
         }
     }
+
+    fn add_account(&self) {
+        let self_ = imp::Window::from_instance(self);
+
+        // If a login is already in progress, just show it again
+        let login_client_id = self_.login.client_id();
+        let is_login_in_progress = self_
+            .clients
+            .borrow()
+            .get(&login_client_id)
+            .map(|client| client.session.is_none())
+            .unwrap_or_default();
+
+        if is_login_in_progress {
+            self_.active_client_id.set(login_client_id);
+            self_.main_stack.set_visible_child(&self_.login.get());
+        } else {
+            let database_directory = self.new_database_directory();
+            let client_id = self.create_client(database_directory);
+            self.login_client(client_id);
+            self_.active_client_id.set(client_id);
+            self_.main_stack.set_visible_child(&self_.login.get());
+        }
+    }
+
+    fn switch_account(&self, client_id: i32) {
+        let self_ = imp::Window::from_instance(self);
+
+        if let Some(session) = self.session(client_id) {
+            let previous_client_id = self_.active_client_id.get();
+            if previous_client_id != client_id && self.session(previous_client_id).is_some() {
+                set_online(previous_client_id, false);
+            }
+
+            self_.active_client_id.set(client_id);
+            self_.main_stack.set_visible_child(&session);
+            set_online(client_id, self.is_active());
+
+            if let Some(action) = self.lookup_action("switch-account") {
+                action.change_state(&client_id.to_variant());
+            }
+        }
+    }
+
+    fn update_accounts_menu(&self) {
+        let self_ = imp::Window::from_instance(self);
+        let menu = &self_.accounts_menu;
+        menu.remove_all();
+
+        let clients = self_.clients.borrow();
+        let mut sessions = clients
+            .values()
+            .filter_map(|client| {
+                client
+                    .session
+                    .as_ref()
+                    .map(|session| (&client.database_directory, session))
+            })
+            .collect::<Vec<_>>();
+        sessions.sort_by_key(|(database_directory, _)| *database_directory);
+
+        for (_, session) in sessions {
+            let label = session
+                .me()
+                .map(|me| {
+                    format!("{} {}", me.first_name(), me.last_name())
+                        .trim()
+                        .to_owned()
+                })
+                .unwrap_or_else(|| gettext("Account"));
+
+            let item = gio::MenuItem::new(Some(&label), None);
+            item.set_action_and_target_value(
+                Some("win.switch-account"),
+                Some(&session.client_id().to_variant()),
+            );
+            menu.append_item(&item);
+        }
+
+        menu.append(Some(&gettext("_Add Account")), Some("win.add-account"));
+    }
+
+    fn session(&self, client_id: i32) -> Option<Session> {
+        let self_ = imp::Window::from_instance(self);
+        self_
+            .clients
+            .borrow()
+            .get(&client_id)
+            .and_then(|client| client.session.clone())
+    }
+
+    fn n_sessions(&self) -> usize {
+        let self_ = imp::Window::from_instance(self);
+        self_
+            .clients
+            .borrow()
+            .values()
+            .filter(|client| client.session.is_some())
+            .count()
+    }
+}
+
+fn set_online(client_id: i32, online: bool) {
+    RUNTIME.spawn(async move {
+        functions::SetOption::new()
+            .name("online".to_string())
+            .value(enums::OptionValue::Boolean(types::OptionValueBoolean {
+                value: online,
+            }))
+            .send(client_id)
+            .await
+            .unwrap();
+    });
+}
+
+/// Notification ids are unique only inside a single client, so use the client id to
+/// distinguish notifications of different accounts.
+fn notification_key(client_id: i32, notification_id: i32) -> String {
+    format!("{}-{}", client_id, notification_id)
+}
+
+fn account_title(title: String, account_name: Option<&str>) -> String {
+    match account_name {
+        // Translators: This is a notification title, the second placeholder is the
+        // name of the account that received the notification
+        Some(account_name) => gettext!("{} ({})", title, account_name),
+        None => title,
+    }
 }
 
 fn sender_name(sender: &TelegramMessageSender, chat: &Chat) -> String {